@@ -13,6 +13,77 @@
                 : 'ws';
     const url = protocol + '://' + address + base + '.well-known/prank/ws';
 
+    // render a compiler diagnostic, along with its source excerpt
+    function diagnosticCard({severity, code, message, file, span, suggestion, excerpt}) {
+        const card = document.createElement("div");
+        card.style.backgroundColor = "white";
+        card.style.borderLeft = "0.25rem solid " + (severity === "warning" ? "#ffc107" : "#dc3545");
+        card.style.borderRadius = "0.25rem";
+        card.style.padding = "1rem";
+        card.style.marginBottom = "1rem";
+
+        const header = document.createElement("div");
+        header.style.fontWeight = "bold";
+        header.style.paddingBottom = "0.5rem";
+        header.textContent = code;
+        if (file) {
+            const location = document.createElement("span");
+            location.style.fontWeight = "normal";
+            location.style.fontFamily = "monospace";
+            location.style.paddingLeft = "1rem";
+            location.textContent = span ? `${file}:${span.startLine}:${span.startColumn}` : file;
+            header.append(location);
+        }
+        card.append(header);
+
+        if (span && excerpt?.length) {
+            const source = document.createElement("pre");
+            source.style.backgroundColor = "#f6f8fa";
+            source.style.padding = "0.5rem";
+            source.style.overflowX = "auto";
+            const width = String(excerpt[excerpt.length - 1].number).length;
+            for (const {number, text} of excerpt) {
+                const line = document.createElement("div");
+                const gutter = document.createElement("span");
+                gutter.style.color = "#6c757d";
+                gutter.textContent = String(number).padStart(width) + " | ";
+                line.append(gutter);
+
+                const inSpan = number >= span.startLine && number <= span.endLine;
+                const start = number === span.startLine ? span.startColumn - 1 : 0;
+                const end = number === span.endLine ? span.endColumn - 1 : text.length;
+                if (inSpan && end > start) {
+                    const marked = document.createElement("span");
+                    marked.style.textDecoration = "underline wavy #dc3545";
+                    marked.textContent = text.slice(start, end);
+                    line.append(text.slice(0, start), marked, text.slice(end));
+                } else {
+                    line.append(text);
+                }
+                source.append(line);
+            }
+            card.append(source);
+        }
+
+        const text = document.createElement("div");
+        text.style.whiteSpace = "pre-wrap";
+        text.style.fontFamily = "monospace";
+        text.textContent = message.trim();
+        card.append(text);
+
+        if (suggestion) {
+            const help = document.createElement("div");
+            help.style.paddingTop = "0.5rem";
+            help.textContent = "Suggestion: ";
+            const replacement = document.createElement("code");
+            replacement.textContent = suggestion.trim();
+            help.append(replacement);
+            card.append(help);
+        }
+
+        return card;
+    }
+
     class Overlay {
         constructor() {
             // create an overlay
@@ -32,9 +103,11 @@
             const container = document.createElement("div");
             // center it
             container.style.position = "absolute";
-            container.style.top = "30%";
+            container.style.top = "10%";
             container.style.left = "15%";
-            container.style.maxWidth = "85%";
+            container.style.maxWidth = "70%";
+            container.style.maxHeight = "80%";
+            container.style.overflowY = "auto";
 
             this._title = document.createElement("div");
             this._title.innerText = "Build failure";
@@ -57,8 +130,15 @@
             }, 250);
         }
 
-        set reason(reason) {
-            this._message.textContent = reason;
+        show(reason, diagnostics) {
+            this._message.replaceChildren();
+            if (diagnostics.length === 0) {
+                this._message.textContent = reason;
+                return;
+            }
+            for (const diagnostic of diagnostics) {
+                this._message.append(diagnosticCard(diagnostic));
+            }
         }
 
         _inject() {
@@ -70,11 +150,65 @@
 
     }
 
+    class WarningsBanner {
+        constructor() {
+            // a non-fatal banner, which can be expanded to show the warnings
+            this._banner = document.createElement("div");
+            const style = this._banner.style;
+            style.position = "fixed";
+            style.bottom = "0";
+            style.left = "0";
+            style.right = "0";
+            style.maxHeight = "50vh";
+            style.overflowY = "auto";
+            style.backgroundColor = "#fff3cd";
+            style.borderTop = "0.25rem solid #ffc107";
+            style.fontFamily = "sans-serif";
+            style.zIndex = "1000000";
+
+            const bar = document.createElement("div");
+            bar.style.display = "flex";
+            bar.style.alignItems = "center";
+            bar.style.padding = "0.5rem 1rem";
+
+            this._title = document.createElement("div");
+            this._title.style.flexGrow = "1";
+            this._title.style.cursor = "pointer";
+            this._title.onclick = () => {
+                this._details.hidden = !this._details.hidden;
+            };
+
+            const close = document.createElement("button");
+            close.textContent = "\u2715";
+            close.title = "Dismiss";
+            close.onclick = () => this.remove();
+
+            this._details = document.createElement("div");
+            this._details.style.padding = "0 1rem";
+            this._details.hidden = true;
+
+            bar.append(this._title, close);
+            this._banner.append(bar, this._details);
+            document.body?.append(this._banner);
+        }
+
+        show(diagnostics) {
+            const count = diagnostics.length;
+            this._title.textContent = `\u26a0 ${count} PureScript warning${count === 1 ? "" : "s"}`;
+            this._details.replaceChildren(...diagnostics.map(diagnosticCard));
+        }
+
+        remove() {
+            this._banner.remove();
+        }
+    }
+
     class Client {
         constructor(url) {
             this.url = url;
             this.poll_interval = 5000;
             this._overlay = null;
+            this._warnings = null;
         }
 
         start() {
@@ -88,6 +222,12 @@
                     case "buildFailure":
                         this.buildFailure(msg.data)
                         break;
+                    case "modulesChanged":
+                        this.modulesChanged(msg.data);
+                        break;
+                    case "warnings":
+                        this.warnings(msg.data);
+                        break;
                 }
             };
             ws.onclose = () => this.onclose();
@@ -111,16 +251,56 @@
             window.location.reload();
         }
 
-        buildFailure({reason}) {
+        async modulesChanged({modules}) {
+            // after a build failure, start over with a clean page
+            const entries = window.prankEntries ?? [];
+            if (this._overlay || entries.length === 0) {
+                this.reload();
+                return;
+            }
+
+            try {
+                // import the entry points again, the server versions the imports of changed modules
+                const version = Date.now();
+                const fresh = await Promise.all(entries.map((entry) => import(entry + '?t=' + version)));
+                if (!fresh.every((module) => typeof module.hmr === 'function')) {
+                    console.debug("Entry module doesn't export `hmr`, reloading");
+                    this.reload();
+                    return;
+                }
+                fresh.forEach((module) => module.hmr());
+                console.info("Hot replaced modules:", modules);
+            } catch (err) {
+                console.error("Hot module replacement failed, reloading:", err);
+                this.reload();
+            }
+        }
+
+        warnings({diagnostics}) {
+            this._warnings?.remove();
+            this._warnings = null;
+            if (diagnostics.length === 0) {
+                return;
+            }
+
+            console.warn("Build warnings:", diagnostics);
+            this._warnings = new WarningsBanner();
+            this._warnings.show(diagnostics);
+        }
+
+        buildFailure({reason, diagnostics = []}) {
             // also log the console
             console.error("Build failed:", reason);
 
+            this._warnings?.remove();
+            this._warnings = null;
+
             console.debug("Overlay", this._overlay);
 
             if (!this._overlay) {
                 this._overlay = new Overlay();
             }
-            this._overlay.reason = reason;
+            this._overlay.show(reason, diagnostics);
         }
     }
 
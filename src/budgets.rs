@@ -0,0 +1,369 @@
+//! Size budgets of release builds.
+//!
+//! After a release build, the files of the dist dir are measured and checked against the budgets
+//! configured in `build.budgets`. The results are written to the report directory of the dist dir.
+//! Exceeding a budget fails the build, listing the outputs which are too large.
+
+use crate::{
+    common,
+    config::{
+        models::{Budget, Budgets},
+        types::ByteSize,
+        REPORT_DIR,
+    },
+    processing::compress::gzip,
+};
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use std::{
+    fmt::{Display, Formatter},
+    path::Path,
+};
+
+/// The name of the file the results are written to, in the report directory of the dist dir.
+const RESULTS_FILE: &str = "budgets.json";
+
+/// The size which is measured for a budget.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Metric {
+    /// The size of the files, as written to the dist dir.
+    Raw,
+    /// The size of the files, once compressed with gzip.
+    Gzip,
+}
+
+/// The size of some output, checked against its budget.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct BudgetCheck {
+    /// The checked output, e.g. `main bundle (index.js)`.
+    pub output: String,
+    /// The measured size.
+    pub metric: Metric,
+    /// The measured size, in bytes.
+    pub size: u64,
+    /// The maximum size, in bytes.
+    pub limit: u64,
+}
+
+impl BudgetCheck {
+    /// Check if the output exceeds its budget.
+    pub fn exceeded(&self) -> bool {
+        self.size > self.limit
+    }
+}
+
+impl Display for BudgetCheck {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let metric = match self.metric {
+            Metric::Raw => "raw",
+            Metric::Gzip => "gzip",
+        };
+        write!(
+            f,
+            "{} ({metric}): {} of {}",
+            self.output,
+            ByteSize(self.size),
+            ByteSize(self.limit)
+        )?;
+        if self.exceeded() {
+            write!(f, " (+{})", ByteSize(self.size - self.limit))?;
+        }
+        Ok(())
+    }
+}
+
+/// A file of the dist dir.
+struct File {
+    /// The path, relative to the dist dir, using forward slashes.
+    path: String,
+    raw: u64,
+    gzip: u64,
+}
+
+impl File {
+    fn extension(&self) -> Option<&str> {
+        Path::new(&self.path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+    }
+
+    fn size(&self, metric: Metric) -> u64 {
+        match metric {
+            Metric::Raw => self.raw,
+            Metric::Gzip => self.gzip,
+        }
+    }
+}
+
+/// Check the files of a dist dir against the budgets.
+///
+/// The main bundles are the files of the main chunks of the main applications of the pages,
+/// relative to the dist dir: a single bundle, or the entry module along with the modules it
+/// preloads. The results are written to the report directory of the dist dir, whether the
+/// budgets are met or not, so [`enforce`] fails the build only after that.
+pub async fn check(
+    dist: &Path,
+    budgets: &Budgets,
+    main_bundles: &[Vec<String>],
+) -> Result<Vec<BudgetCheck>> {
+    if budgets.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let checks = {
+        let dist = dist.to_path_buf();
+        let budgets = budgets.clone();
+        let main_bundles = main_bundles.to_vec();
+        tokio::task::spawn_blocking(move || {
+            let files = measure(&dist)?;
+            Ok::<_, anyhow::Error>(evaluate(&files, &budgets, &main_bundles))
+        })
+        .await
+        .context("error awaiting size budgets")??
+    };
+
+    let dir = dist.join(REPORT_DIR);
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .with_context(|| format!("error creating report directory {}", dir.display()))?;
+    let json = serde_json::to_string_pretty(&checks).context("error serializing budgets")?;
+    tokio::fs::write(dir.join(RESULTS_FILE), json)
+        .await
+        .context("error writing budget results")?;
+
+    Ok(checks)
+}
+
+/// Fail if any of the checked budgets is exceeded, listing the outputs which are too large.
+pub fn enforce(checks: &[BudgetCheck]) -> Result<()> {
+    if checks.is_empty() {
+        return Ok(());
+    }
+
+    let exceeded = checks
+        .iter()
+        .filter(|check| check.exceeded())
+        .map(|check| format!("  {check}"))
+        .collect::<Vec<_>>();
+    if !exceeded.is_empty() {
+        bail!("size budgets exceeded:\n{}", exceeded.join("\n"));
+    }
+
+    tracing::info!("all {} size budgets are met", checks.len());
+    Ok(())
+}
+
+/// Measure all files of the dist dir, except for the reports.
+fn measure(dist: &Path) -> Result<Vec<File>> {
+    common::dist_files(dist)?
+        .into_iter()
+        .map(|path| {
+            let bytes = std::fs::read(&path)
+                .with_context(|| format!("error reading {}", path.display()))?;
+            Ok(File {
+                path: common::dist_relative(dist, &path)?,
+                raw: bytes.len() as u64,
+                gzip: gzip(&bytes)?.len() as u64,
+            })
+        })
+        .collect()
+}
+
+/// Check the measured files against the budgets.
+fn evaluate(files: &[File], budgets: &Budgets, main_bundles: &[Vec<String>]) -> Vec<BudgetCheck> {
+    let mut checks = Vec::new();
+    let mut check = |output: String, budget: &Budget, size: &dyn Fn(Metric) -> u64| {
+        for (metric, limit) in [(Metric::Raw, budget.raw), (Metric::Gzip, budget.gzip)] {
+            if let Some(ByteSize(limit)) = limit {
+                checks.push(BudgetCheck {
+                    output: output.clone(),
+                    metric,
+                    size: size(metric),
+                    limit,
+                });
+            }
+        }
+    };
+
+    if let Some(budget) = &budgets.main {
+        let bundles = main_bundles
+            .iter()
+            .filter_map(|bundle| {
+                let (entry, modules) = bundle.split_first()?;
+                let files = files
+                    .iter()
+                    .filter(|file| bundle.contains(&file.path))
+                    .collect::<Vec<_>>();
+                (!files.is_empty()).then_some((entry, modules.len(), files))
+            })
+            .collect::<Vec<_>>();
+        if bundles.is_empty() {
+            tracing::warn!("there is no main bundle to check the budget of");
+        }
+        // the modules preloaded by the entry module are loaded along with it
+        for (entry, modules, files) in bundles {
+            let output = match modules {
+                0 => format!("main bundle ({entry})"),
+                1 => format!("main bundle ({entry} and 1 preloaded module)"),
+                _ => format!("main bundle ({entry} and {modules} preloaded modules)"),
+            };
+            check(output, budget, &|metric| {
+                files.iter().map(|file| file.size(metric)).sum()
+            });
+        }
+    }
+
+    let sum = |filter: &dyn Fn(&File) -> bool, metric| -> u64 {
+        files
+            .iter()
+            .filter(|file| filter(file))
+            .map(|file| file.size(metric))
+            .sum()
+    };
+    let is_css = |file: &File| file.extension() == Some("css");
+    let is_shipped = |file: &File| file.extension() != Some("map");
+    if let Some(budget) = &budgets.css {
+        check("CSS".to_string(), budget, &|metric| sum(&is_css, metric));
+    }
+    if let Some(budget) = &budgets.total {
+        check("total".to_string(), budget, &|metric| {
+            sum(&is_shipped, metric)
+        });
+    }
+
+    for (extension, budget) in &budgets.types {
+        let extension = extension.trim_start_matches('.');
+        for file in files
+            .iter()
+            .filter(|file| file.extension() == Some(extension))
+        {
+            check(file.path.clone(), budget, &|metric| file.size(metric));
+        }
+    }
+
+    checks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn file(path: &str, raw: u64, gzip: u64) -> File {
+        File {
+            path: path.to_string(),
+            raw,
+            gzip,
+        }
+    }
+
+    fn budget(raw: Option<u64>, gzip: Option<u64>) -> Budget {
+        Budget {
+            raw: raw.map(ByteSize),
+            gzip: gzip.map(ByteSize),
+        }
+    }
+
+    #[test]
+    fn evaluates_budgets() {
+        let files = [
+            file("index.html", 1000, 400),
+            file("index-1234.js", 50_000, 15_000),
+            file("index-1234.js.map", 90_000, 30_000),
+            file("style-5678.css", 8_000, 2_000),
+            file("worker/Worker.js", 20_000, 6_000),
+        ];
+        let budgets = Budgets {
+            main: Some(budget(None, Some(10_000))),
+            css: Some(budget(Some(10_000), None)),
+            total: Some(budget(Some(100_000), Some(30_000))),
+            types: BTreeMap::from([(".js".to_string(), budget(Some(30_000), None))]),
+        };
+
+        let checks = evaluate(&files, &budgets, &[vec!["index-1234.js".to_string()]]);
+        let results = checks
+            .iter()
+            .map(|check| {
+                (
+                    check.output.as_str(),
+                    check.metric,
+                    check.size,
+                    check.exceeded(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            results,
+            [
+                ("main bundle (index-1234.js)", Metric::Gzip, 15_000, true),
+                ("CSS", Metric::Raw, 8_000, false),
+                ("total", Metric::Raw, 79_000, false),
+                ("total", Metric::Gzip, 23_400, false),
+                ("index-1234.js", Metric::Raw, 50_000, true),
+                ("worker/Worker.js", Metric::Raw, 20_000, false),
+            ]
+        );
+        assert_eq!(
+            checks[0].to_string(),
+            "main bundle (index-1234.js) (gzip): 14.6 KiB of 9.8 KiB (+4.9 KiB)"
+        );
+    }
+
+    #[test]
+    fn sums_preloaded_modules() {
+        let files = [
+            file("Main-1234.js", 200, 100),
+            file("Data.Maybe-5678.js", 3_000, 1_000),
+            file("Admin-9abc.js", 50_000, 20_000),
+        ];
+        let budgets = Budgets {
+            main: Some(budget(None, Some(1_000))),
+            ..Default::default()
+        };
+        let main = ["Main-1234.js", "Data.Maybe-5678.js"].map(str::to_string);
+
+        let checks = evaluate(&files, &budgets, &[main.to_vec()]);
+        assert_eq!(checks.len(), 1);
+        assert_eq!(
+            checks[0].output,
+            "main bundle (Main-1234.js and 1 preloaded module)"
+        );
+        assert_eq!(checks[0].size, 1_100);
+        assert!(checks[0].exceeded());
+    }
+
+    #[tokio::test]
+    async fn fails_when_exceeded() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        std::fs::write(tmp.path().join("index.js"), "x".repeat(2048))?;
+        std::fs::create_dir(tmp.path().join(REPORT_DIR))?;
+        std::fs::write(
+            tmp.path().join(REPORT_DIR).join("report.json"),
+            "x".repeat(4096),
+        )?;
+
+        let mut budgets = Budgets {
+            total: Some(budget(Some(4096), None)),
+            ..Default::default()
+        };
+        let results = tmp.path().join(REPORT_DIR).join(RESULTS_FILE);
+        let checks = check(tmp.path(), &budgets, &[]).await?;
+        assert_eq!(checks.len(), 1);
+        assert_eq!(checks[0].size, 2048);
+        assert!(results.exists());
+        enforce(&checks)?;
+
+        // The results of exceeded budgets are written as well.
+        std::fs::remove_file(&results)?;
+        budgets.total = Some(budget(Some(1024), None));
+        let checks = check(tmp.path(), &budgets, &[]).await?;
+        assert!(std::fs::read_to_string(&results)?.contains(r#""limit": 1024"#));
+        let err = enforce(&checks).err().context("budget must be exceeded")?;
+        assert_eq!(
+            err.to_string(),
+            "size budgets exceeded:\n  total (raw): 2.0 KiB of 1.0 KiB (+1.0 KiB)"
+        );
+        Ok(())
+    }
+}
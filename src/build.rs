@@ -1,5 +1,6 @@
 //! Build system & asset pipelines.
 
+use std::collections::BTreeSet;
 use std::path::PathBuf;
 use std::sync::Arc;
 
@@ -9,11 +10,28 @@ use tokio::fs;
 use tokio::sync::mpsc;
 use tokio_stream::wrappers::ReadDirStream;
 
+use crate::budgets::{self, BudgetCheck};
 use crate::common::{remove_dir_all, BUILDING, ERROR, SUCCESS};
 use crate::config::{rt::RtcBuild, types::WsProtocol, STAGE_DIR};
-use crate::pipelines::HtmlPipeline;
+use crate::hooks::{spawn_hooks, wait_hooks};
+use crate::pipelines::{
+    write_manifest, write_reports, write_service_worker, CompilerErrors, DevSession, Diagnostic,
+    HtmlPipeline, PageOutput, PipelineStage, SharedAssets,
+};
+use crate::processing::compress::precompress_dist;
 
-pub type BuildResult = Result<()>;
+pub type BuildResult = Result<BuildOutput>;
+
+/// The outcome of a successful build.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BuildOutput {
+    /// The PureScript modules which can be hot replaced, `None` if the page must be reloaded.
+    pub hot_modules: Option<BTreeSet<String>>,
+    /// The warnings of the PureScript compiler.
+    pub warnings: Vec<Diagnostic>,
+    /// The size budgets checked by a release build.
+    pub budgets: Vec<BudgetCheck>,
+}
 
 /// The mode in which to run the build.
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
@@ -33,8 +51,8 @@ pub enum BuildMode {
 pub struct BuildSystem {
     /// Runtime config.
     cfg: Arc<RtcBuild>,
-    /// HTML build pipeline.
-    html_pipeline: Arc<HtmlPipeline>,
+    /// HTML build pipelines, one for each page.
+    html_pipelines: Vec<Arc<HtmlPipeline>>,
 }
 
 impl BuildSystem {
@@ -45,31 +63,62 @@ impl BuildSystem {
     pub async fn new(
         cfg: Arc<RtcBuild>,
         ignore_chan: Option<mpsc::Sender<Vec<PathBuf>>>,
+        session: DevSession,
         ws_protocol: Option<WsProtocol>,
     ) -> Result<Self> {
-        let html_pipeline = Arc::new(HtmlPipeline::new(cfg.clone(), ignore_chan, ws_protocol)?);
-        Ok(Self { cfg, html_pipeline })
+        let html_pipelines = cfg
+            .pages
+            .iter()
+            .map(|page| {
+                Ok(Arc::new(HtmlPipeline::new(
+                    cfg.clone(),
+                    page,
+                    ignore_chan.clone(),
+                    session.clone(),
+                    ws_protocol,
+                )?))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            cfg,
+            html_pipelines,
+        })
     }
 
     /// Build the application described in the given build data.
     #[tracing::instrument(level = "trace", skip(self))]
-    pub async fn build(&mut self, mode: BuildMode, changed_paths: Vec<PathBuf>) -> Result<()> {
+    pub async fn build(&mut self, mode: BuildMode, changed_paths: Vec<PathBuf>) -> BuildResult {
         tracing::info!("{}starting build", BUILDING);
         let res = self.do_build(mode, changed_paths).await;
         match res {
-            Ok(_) => {
-                tracing::info!("{}success", SUCCESS);
-                Ok(())
+            Ok(output) => {
+                match output.warnings.len() {
+                    0 => tracing::info!("{}success", SUCCESS),
+                    count => {
+                        for warning in &output.warnings {
+                            tracing::warn!("{}", warning.render());
+                        }
+                        tracing::info!(
+                            "{}success ({count} PureScript warning{})",
+                            SUCCESS,
+                            if count == 1 { "" } else { "s" }
+                        );
+                    }
+                }
+                Ok(output)
             }
             Err(err) => {
-                tracing::error!("{}error\n{:?}", ERROR, err);
+                match CompilerErrors::find(&err) {
+                    Some(errors) => tracing::error!("{}error\n{}", ERROR, errors),
+                    None => tracing::error!("{}error\n{:?}", ERROR, err),
+                }
                 Err(err)
             }
         }
     }
 
     /// Internal business logic of `build`.
-    async fn do_build(&mut self, _mode: BuildMode, changed_paths: Vec<PathBuf>) -> Result<()> {
+    async fn do_build(&mut self, _mode: BuildMode, changed_paths: Vec<PathBuf>) -> BuildResult {
         // Ensure the output dist directories are in place.
         fs::create_dir_all(self.cfg.final_dist.as_path())
             .await
@@ -84,22 +133,143 @@ impl BuildSystem {
             .await
             .context("error preparing build environment")?;
 
-        // Spawn the source HTML pipeline. This will spawn all other pipelines derived from
-        // the source HTML, and will ultimately generate and write the final HTML.
-        self.html_pipeline
-            .clone()
-            .spawn(changed_paths)
-            .await
-            .context("error joining HTML pipeline")?
-            // we name if "build" pipeline here, was that's what it has become, and
-            // what makes more sense to the user
-            .context("error from build pipeline")?;
+        // Spawn and wait on pre-build hooks.
+        wait_hooks(spawn_hooks(self.cfg.clone(), PipelineStage::PreBuild)).await?;
+
+        // Spawn the source HTML pipelines. These will spawn all other pipelines derived from
+        // the source HTML, and will ultimately generate and write the final HTML of each page.
+        let shared = SharedAssets::default();
+        let pages = self
+            .html_pipelines
+            .iter()
+            .map(|pipeline| {
+                pipeline
+                    .clone()
+                    .spawn(changed_paths.clone(), shared.clone())
+            })
+            .collect::<Vec<_>>();
+        // Spawn all build hooks.
+        let build_hooks = spawn_hooks(self.cfg.clone(), PipelineStage::Build);
+
+        // Wait for all pages, even if one fails, so that we don't start a new build while
+        // previous pipelines are still running.
+        let mut outputs = Vec::new();
+        let mut error = None;
+        for page in futures_util::future::join_all(pages).await {
+            let result = page
+                .context("error joining HTML pipeline")
+                .and_then(|result| result)
+                // we name if "build" pipeline here, was that's what it has become, and
+                // what makes more sense to the user
+                .context("error from build pipeline");
+            match result {
+                Ok(output) => outputs.push(output),
+                Err(err) => {
+                    error.get_or_insert(err);
+                }
+            }
+        }
+        if let Some(err) = error {
+            return Err(err);
+        }
+
+        // Wait for all build hooks to finish.
+        wait_hooks(build_hooks).await?;
+
+        let (mut output, main_bundles) = self.finalize_pages(outputs).await?;
+
+        // Spawn and wait on post-build hooks.
+        wait_hooks(spawn_hooks(self.cfg.clone(), PipelineStage::PostBuild)).await?;
+
+        // Check the size budgets of the final output.
+        if self.cfg.release {
+            output.budgets =
+                budgets::check(&self.cfg.staging_dist, &self.cfg.budgets, &main_bundles).await?;
+            budgets::enforce(&output.budgets)?;
+        }
 
         // Move distribution from staging dist to final dist
         self.finalize_dist()
             .await
             .context("error applying built distribution")?;
-        Ok(())
+
+        if !self.cfg.precompress.is_empty() {
+            let count = precompress_dist(
+                &self.cfg.final_dist,
+                &self.cfg.precompress,
+                self.cfg.precompress_threshold,
+            )
+            .await
+            .context("error precompressing the dist dir")?;
+            tracing::info!("precompressed {count} files");
+        }
+        Ok(output)
+    }
+
+    /// Write the outputs of the build shared by its pages, and merge the outputs of the pages.
+    ///
+    /// Returns the output of the build, and the files of the main bundles of the pages.
+    async fn finalize_pages(
+        &self,
+        pages: Vec<PageOutput>,
+    ) -> Result<(BuildOutput, Vec<Vec<String>>)> {
+        let mut hot_modules = Vec::new();
+        let mut warnings = Vec::new();
+        let mut main_bundles = Vec::new();
+        let mut reports = Vec::new();
+        let mut files = Vec::new();
+        for page in pages {
+            hot_modules.extend(page.hot_modules);
+            // pages sharing an application report the same warnings
+            for warning in page.warnings {
+                if !warnings.contains(&warning) {
+                    warnings.push(warning);
+                }
+            }
+            main_bundles.extend(page.main_bundle);
+            reports.extend(page.reports);
+            files.extend(page.files);
+        }
+        main_bundles.sort();
+        main_bundles.dedup();
+
+        if !reports.is_empty() {
+            // the pipelines finish in any order, and pages may share their applications
+            reports.sort_by(|a, b| a.main_module.cmp(&b.main_module));
+            reports.dedup_by(|a, b| a.main_module == b.main_module);
+            for report in &reports {
+                report.log_summary();
+            }
+            write_reports(&self.cfg.staging_dist, &reports).await?;
+        }
+
+        if self.cfg.manifest {
+            write_manifest(&self.cfg.staging_dist, &self.cfg.public_url, files).await?;
+        }
+
+        // Generate the service worker, once all files of the dist dir are written.
+        if self.cfg.should_generate_service_worker() {
+            let precached = write_service_worker(&self.cfg).await?;
+            tracing::info!("generated the service worker, precaching {precached} files");
+        }
+
+        // modules can only be hot replaced if all applications of the pages support it
+        let hot_modules = match hot_modules.is_empty() {
+            true => None,
+            false => hot_modules
+                .into_iter()
+                .try_fold(BTreeSet::new(), |mut acc, modules| {
+                    acc.extend(modules?);
+                    Some(acc)
+                }),
+        };
+
+        let output = BuildOutput {
+            hot_modules,
+            warnings,
+            budgets: vec![],
+        };
+        Ok((output, main_bundles))
     }
 
     /// Creates a "staging area" (dist/.stage) for storing intermediate build results.
@@ -3,7 +3,7 @@ use crate::{
     config::{
         self,
         rt::{self, RtcBuild, RtcBuilder},
-        types::{BaseUrl, Minify},
+        types::{BaseUrl, Minify, Precompress, PureScriptWarnings, SourceMaps, Targets},
         Configuration, Tools,
     },
 };
@@ -16,10 +16,11 @@ use std::{path::PathBuf, sync::Arc};
 #[command(name = "build")]
 #[command(next_help_heading = "Build")]
 pub struct Build {
-    /// The index HTML file to drive the bundling process
+    /// The index HTML file to drive the bundling process, or a glob pattern matching the HTML
+    /// files of several pages
     pub target: Option<PathBuf>,
 
-    /// The name of the output HTML file.
+    /// The name of the output HTML file, when building a single page.
     #[arg(long, env = "PRANK_BUILD_HTML_OUTPUT")]
     pub html_output: Option<String>,
 
@@ -117,6 +118,55 @@ pub struct Build {
     #[arg(default_missing_value="true", num_args=0..=1)]
     pub allow_self_closing_script: Option<bool>,
 
+    /// How to handle warnings of the PureScript compiler
+    #[arg(long, env = "PRANK_BUILD_PURESCRIPT_WARNINGS")]
+    pub purescript_warnings: Option<PureScriptWarnings>,
+
+    /// A comma-separated list of PureScript warning codes to ignore
+    #[arg(
+        long,
+        value_delimiter = ',',
+        env = "PRANK_BUILD_PURESCRIPT_CENSOR_CODES"
+    )]
+    pub purescript_censor_codes: Option<Vec<String>>,
+
+    /// Emit source maps for the release bundles
+    #[arg(long, env = "PRANK_BUILD_SOURCE_MAPS")]
+    pub source_maps: Option<SourceMaps>,
+
+    /// The path below the public URL, at which the PureScript output is served in dev mode
+    #[arg(long, env = "PRANK_BUILD_OUTPUT_MOUNT")]
+    pub output_mount: Option<String>,
+
+    /// Generate the `Prank.Env` module, exposing the build configuration to the application
+    #[arg(long, env = "PRANK_BUILD_ENV_MODULE")]
+    #[arg(default_missing_value="true", num_args=0..=1)]
+    pub env_module: Option<bool>,
+
+    /// Write a report of the bundle sizes to the `.prank` directory of the dist dir (release only)
+    #[arg(long, env = "PRANK_BUILD_REPORT")]
+    #[arg(default_missing_value="true", num_args=0..=1)]
+    pub report: Option<bool>,
+
+    /// Write a `manifest.json` to the dist dir, mapping the sources of the assets to their outputs
+    #[arg(long, env = "PRANK_BUILD_MANIFEST")]
+    #[arg(default_missing_value="true", num_args=0..=1)]
+    pub manifest: Option<bool>,
+
+    /// Render the source HTML of the pages as templates
+    #[arg(long, env = "PRANK_BUILD_TEMPLATE")]
+    #[arg(default_missing_value="true", num_args=0..=1)]
+    pub template: Option<bool>,
+
+    /// Generate a service worker precaching all files of the dist dir (release only)
+    #[arg(long, env = "PRANK_BUILD_SERVICE_WORKER")]
+    #[arg(default_missing_value="true", num_args=0..=1)]
+    pub service_worker: Option<bool>,
+
+    /// A comma-separated list of formats to precompress the files of the dist dir with
+    #[arg(long, value_delimiter = ',', env = "PRANK_BUILD_PRECOMPRESS")]
+    pub precompress: Option<Vec<Precompress>>,
+
     // NOTE: flattened structures come last
     #[command(flatten)]
     pub core: super::core::Core,
@@ -150,10 +200,22 @@ impl Build {
             minify,
             no_sri,
             allow_self_closing_script,
+            purescript_warnings,
+            purescript_censor_codes,
+            source_maps,
+            output_mount,
+            env_module,
+            report,
+            manifest,
+            template,
+            service_worker,
+            precompress,
             tools,
         } = self;
 
-        config.build.target = target.unwrap_or(config.build.target);
+        if let Some(target) = target {
+            config.build.target = Targets(vec![target]);
+        }
         config.build.html_output = html_output.unwrap_or(config.build.html_output);
         config.build.release = release.unwrap_or(config.build.release);
         config.build.spago_profile = spago_profile.or(config.build.spago_profile);
@@ -185,6 +247,18 @@ impl Build {
         config.build.no_sri = no_sri.unwrap_or(config.build.no_sri);
         config.build.allow_self_closing_script =
             allow_self_closing_script.unwrap_or(config.build.allow_self_closing_script);
+        config.build.purescript_warnings =
+            purescript_warnings.unwrap_or(config.build.purescript_warnings);
+        config.build.purescript_censor_codes =
+            purescript_censor_codes.unwrap_or(config.build.purescript_censor_codes);
+        config.build.source_maps = source_maps.unwrap_or(config.build.source_maps);
+        config.build.output_mount = output_mount.unwrap_or(config.build.output_mount);
+        config.build.env_module = env_module.unwrap_or(config.build.env_module);
+        config.build.report = report.unwrap_or(config.build.report);
+        config.build.manifest = manifest.unwrap_or(config.build.manifest);
+        config.build.template = template.unwrap_or(config.build.template);
+        config.build.service_worker = service_worker.unwrap_or(config.build.service_worker);
+        config.build.precompress = precompress.unwrap_or(config.build.precompress);
 
         let config = core.apply_to(config)?;
         let config = tools.apply_to(config)?;
@@ -205,7 +279,7 @@ impl Build {
 
         cfg.core.enforce_version()?;
 
-        let mut system = BuildSystem::new(Arc::new(cfg), None, None).await?;
+        let mut system = BuildSystem::new(Arc::new(cfg), None, Default::default(), None).await?;
         system
             .build(crate::build::BuildMode::Full, Vec::new())
             .await?;
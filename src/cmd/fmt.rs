@@ -0,0 +1,155 @@
+use crate::{
+    common::{self, OutputLine, ERROR, SUCCESS},
+    config::{
+        self,
+        rt::{self, RtcBuild, RtcBuilder},
+        Configuration, Tools,
+    },
+    pipelines::SpagoMetadata,
+    tools::{self, Application},
+};
+use anyhow::{bail, Result};
+use clap::Args;
+use std::path::PathBuf;
+
+/// Format the PureScript sources of the spago package using purs-tidy.
+#[derive(Clone, Args)]
+#[command(name = "fmt")]
+#[command(next_help_heading = "Format")]
+pub struct Fmt {
+    /// Only check the formatting, failing if a source file is not formatted
+    #[arg(long, env = "PRANK_FMT_CHECK")]
+    pub check: bool,
+    /// The package to format, when using a spago workspace
+    #[arg(short, long, env = "PRANK_FMT_PACKAGE")]
+    pub package: Option<String>,
+
+    // NOTE: flattened structures come last
+    #[command(flatten)]
+    pub core: super::core::Core,
+
+    #[command(flatten)]
+    pub tools: Tools,
+}
+
+impl Fmt {
+    /// apply CLI overrides to the configuration
+    pub fn apply_to(self, config: Configuration) -> Result<Configuration> {
+        let Self {
+            check: _,
+            package: _,
+            core,
+            tools,
+        } = self;
+
+        let config = core.apply_to(config)?;
+        tools.apply_to(config)
+    }
+
+    #[tracing::instrument(level = "trace", skip(self, config))]
+    pub async fn run(self, config: Option<PathBuf>) -> Result<()> {
+        let (cfg, working_directory) = config::load(config).await?;
+
+        let cfg = self.clone().apply_to(cfg)?;
+        let cfg = RtcBuild::from_config(cfg, working_directory, |_, core| rt::BuildOptions {
+            core,
+            inject_autoloader: false,
+        })
+        .await?;
+
+        cfg.core.enforce_version()?;
+
+        let manifest = SpagoMetadata::new(
+            &cfg.target_parent.join("spago.yaml"),
+            self.package.as_deref(),
+        )
+        .await?;
+        let globs = manifest.purs_globs();
+        if globs.is_empty() {
+            tracing::warn!(
+                "package '{}' has no PureScript sources to format",
+                manifest.package.name
+            );
+            return Ok(());
+        }
+
+        let purs_tidy = tools::get(
+            Application::PursTidy,
+            cfg.tools.purs_tidy.as_deref(),
+            cfg.offline,
+            &cfg.client_options(),
+        )
+        .await?;
+
+        let command = match self.check {
+            true => "check",
+            false => "format-in-place",
+        };
+        let mut args = vec![command];
+        args.extend(globs.iter().map(String::as_str));
+
+        // purs-tidy picks up the `.tidyrc.json` of the package (or one of its parents) by itself.
+        let mut lines = vec![];
+        let status = common::run_command_with_output(
+            Application::PursTidy.name(),
+            &purs_tidy,
+            &args,
+            &manifest.package_root,
+            |line| {
+                let (OutputLine::Stdout(line) | OutputLine::Stderr(line)) = line;
+                if !line.trim().is_empty() {
+                    tracing::info!("{line}");
+                    lines.push(line.to_string());
+                }
+            },
+        )
+        .await?;
+
+        match (status.success(), self.check) {
+            (true, true) => tracing::info!("{}all PureScript sources are formatted", SUCCESS),
+            (true, false) => tracing::info!("{}formatted the PureScript sources", SUCCESS),
+            (false, true) if only_unformatted(&lines) => {
+                tracing::error!("{}some PureScript sources are not formatted", ERROR);
+                bail!("formatting check failed, run `prank fmt` to format the sources");
+            }
+            (false, _) => bail!("purs-tidy failed with status {status}, see its output above"),
+        }
+        Ok(())
+    }
+}
+
+/// Check if the output of a failed `purs-tidy check` reports unformatted sources only, and no
+/// errors, e.g. sources which can't be parsed.
+fn only_unformatted(lines: &[String]) -> bool {
+    let (unformatted, others): (Vec<_>, Vec<_>) = lines
+        .iter()
+        .partition(|line| line.contains("not formatted"));
+    !unformatted.is_empty()
+        && !others
+            .iter()
+            .any(|line| line.to_lowercase().contains("error"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tells_unformatted_sources_from_errors() {
+        let lines = |lines: &[&str]| {
+            lines
+                .iter()
+                .map(|line| line.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert!(only_unformatted(&lines(&[
+            "src/Error.purs: not formatted",
+            "Some files are not formatted",
+        ])));
+        assert!(!only_unformatted(&lines(&[
+            "src/Main.purs: not formatted",
+            "src/Util.purs: Error: Unexpected token",
+        ])));
+        assert!(!only_unformatted(&lines(&["purs-tidy: unknown option"])));
+    }
+}
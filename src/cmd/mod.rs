@@ -4,6 +4,8 @@ pub mod build;
 pub mod clean;
 pub mod config;
 pub mod core;
+pub mod fmt;
 pub mod serve;
+pub mod test;
 pub mod tools;
 pub mod watch;
@@ -0,0 +1,111 @@
+use crate::{
+    config::{
+        self,
+        rt::{self, RtcBuild, RtcBuilder, RtcWatch},
+        Configuration,
+    },
+    test::TestSystem,
+    watch::WatchSystem,
+};
+use anyhow::{Context, Result};
+use clap::Args;
+use std::{path::PathBuf, sync::Arc};
+use tokio::sync::broadcast;
+
+/// Run the test suite of the PureScript package.
+#[derive(Clone, Args)]
+#[command(name = "test")]
+#[command(next_help_heading = "Test")]
+pub struct Test {
+    /// Re-run the tests when files change
+    #[arg(long, env = "PRANK_TEST_WATCH")]
+    pub watch: bool,
+    /// Run the tests on the output of purs-backend-es, instead of the one of spago
+    #[arg(long, env = "PRANK_TEST_OPTIMIZE")]
+    pub optimize: bool,
+    /// The package to test, when using a spago workspace
+    #[arg(short, long, env = "PRANK_TEST_PACKAGE")]
+    pub package: Option<String>,
+
+    // NOTE: flattened structures come last
+    #[command(flatten)]
+    pub build: super::build::Build,
+}
+
+impl Test {
+    /// apply CLI overrides to the configuration
+    pub fn apply_to(self, config: Configuration) -> Result<Configuration> {
+        let Self {
+            watch: _,
+            optimize: _,
+            package: _,
+            build,
+        } = self;
+
+        build.apply_to(config)
+    }
+
+    #[tracing::instrument(level = "trace", skip(self, config))]
+    pub async fn run(self, config: Option<PathBuf>) -> Result<()> {
+        let (cfg, working_directory) = config::load(config).await?;
+
+        let cfg = self.clone().apply_to(cfg)?;
+
+        if !self.watch {
+            let cfg = RtcBuild::from_config(cfg, working_directory, |_, core| rt::BuildOptions {
+                core,
+                inject_autoloader: false,
+            })
+            .await?;
+
+            cfg.core.enforce_version()?;
+
+            let mut system =
+                TestSystem::new(Arc::new(cfg), self.package.as_deref(), self.optimize, None)
+                    .await?;
+            return system.test().await;
+        }
+
+        let cfg = RtcWatch::from_config(cfg, working_directory, |_, core| rt::WatchOptions {
+            build: rt::BuildOptions {
+                core,
+                inject_autoloader: false,
+            },
+            poll: None,
+            enable_cooldown: false,
+            clear_screen: false,
+            no_error_reporting: false,
+        })
+        .await?;
+
+        cfg.enforce_version()?;
+
+        let (shutdown_tx, _shutdown_rx) = broadcast::channel(1);
+
+        let build = cfg.build.clone();
+        let (package, optimize) = (self.package, self.optimize);
+        let mut system = WatchSystem::with_runner(
+            Arc::new(cfg),
+            shutdown_tx.clone(),
+            None,
+            |ignore_tx| async move {
+                TestSystem::new(build, package.as_deref(), optimize, Some(ignore_tx)).await
+            },
+        )
+        .await?;
+
+        system.build().await.ok();
+        let system_handle = tokio::spawn(system.run());
+        tokio::signal::ctrl_c()
+            .await
+            .context("error awaiting shutdown signal")?;
+        tracing::debug!("received shutdown signal");
+        shutdown_tx.send(()).ok();
+        drop(shutdown_tx); // Ensure other components see the drop to avoid race conditions.
+        system_handle
+            .await
+            .context("error awaiting system shutdown")?;
+
+        Ok(())
+    }
+}
@@ -35,6 +35,14 @@ pub struct Watch {
     /// Clear the screen before each run
     #[arg(short, long = "clear", env = "PRANK_WATCH_CLEAR")]
     pub clear_screen: bool,
+    /// Rebuild changed PureScript modules through a long-lived `purs ide server`
+    #[arg(long, env = "PRANK_WATCH_PURS_IDE")]
+    #[arg(default_missing_value="true", num_args=0..=1)]
+    pub purs_ide: Option<bool>,
+    /// Serve the PureScript output in dev mode as a single bundle
+    #[arg(long, env = "PRANK_WATCH_DEV_BUNDLE")]
+    #[arg(default_missing_value="true", num_args=0..=1)]
+    pub dev_bundle: Option<bool>,
 
     // NOTE: flattened structures come last
     #[command(flatten)]
@@ -51,11 +59,15 @@ impl Watch {
             poll_interval: _,
             enable_cooldown: _,
             clear_screen: _,
+            purs_ide,
+            dev_bundle,
             build,
         } = self;
 
         config.watch.watch = watch.unwrap_or(config.watch.watch);
         config.watch.ignore = ignore.unwrap_or(config.watch.ignore);
+        config.watch.purs_ide = purs_ide.unwrap_or(config.watch.purs_ide);
+        config.watch.dev_bundle = dev_bundle.unwrap_or(config.watch.dev_bundle);
 
         let config = build.apply_to(config)?;
 
@@ -84,7 +96,8 @@ impl Watch {
 
         let (shutdown_tx, _shutdown_rx) = broadcast::channel(1);
 
-        let mut system = WatchSystem::new(Arc::new(cfg), shutdown_tx.clone(), None, None).await?;
+        let mut system =
+            WatchSystem::new(Arc::new(cfg), shutdown_tx.clone(), None, None, None).await?;
 
         system.build().await.ok();
         let system_handle = tokio::spawn(system.run());
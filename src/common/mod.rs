@@ -1,6 +1,9 @@
 //! Common functionality and types.
 pub mod html_rewrite;
+#[cfg(test)]
+pub mod test_util;
 
+use crate::config::REPORT_DIR;
 use anyhow::{anyhow, bail, Context, Result};
 use base64::{engine::general_purpose, Engine};
 use console::Emoji;
@@ -13,9 +16,13 @@ use std::{
     fs::Metadata,
     io::ErrorKind,
     path::{Component, Path, PathBuf},
-    process::Stdio,
+    process::{ExitStatus, Stdio},
+};
+use tokio::{
+    fs,
+    io::{AsyncBufRead, AsyncBufReadExt, BufReader, Lines},
+    process::{Child, Command},
 };
-use tokio::{fs, process::Command};
 
 pub static BUILDING: Emoji = Emoji("📦 ", "");
 pub static SUCCESS: Emoji = Emoji("✅ ", "");
@@ -24,6 +31,7 @@ pub static SERVER: Emoji = Emoji("📡 ", "");
 pub static LOCAL: Emoji = Emoji("🏠 ", "");
 pub static NETWORK: Emoji = Emoji("💻 ", "");
 pub static STARTING: Emoji = Emoji("🚀 ", "");
+pub static TESTING: Emoji = Emoji("🧪 ", "");
 #[cfg(feature = "update_check")]
 pub static UPDATE: Emoji = Emoji("⏫ ", "");
 
@@ -86,6 +94,30 @@ where
     Ok(collector)
 }
 
+/// List the files of a dist dir recursively, excluding the build reports.
+///
+/// The files are sorted by path.
+pub fn dist_files(dist: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dist.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let entries =
+            std::fs::read_dir(&dir).with_context(|| format!("error reading {}", dir.display()))?;
+        for entry in entries {
+            let path = entry
+                .with_context(|| format!("error reading {}", dir.display()))?
+                .path();
+            match path.is_dir() {
+                true if path != dist.join(REPORT_DIR) => dirs.push(path),
+                true => {}
+                false => files.push(path),
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
 /// A utility function to recursively delete a directory.
 ///
 /// Use this instead of fs::remove_dir_all(...) because of Windows compatibility issues, per
@@ -169,22 +201,9 @@ pub async fn run_command(
     args: &[impl AsRef<OsStr> + Debug],
     working_dir: impl AsRef<Path> + Debug,
 ) -> Result<()> {
-    tracing::debug!(?args, "{name} args");
-
     let path = path.as_ref();
 
-    let status = Command::new(path)
-        .current_dir(working_dir.as_ref())
-        .args(args)
-        .stdout(Stdio::inherit())
-        .stderr(Stdio::inherit())
-        .spawn()
-        .with_context(|| {
-            format!(
-                "error running {name} using executable '{}' with args: '{args:?}'",
-                path.display(),
-            )
-        })?
+    let status = spawn_command(name, path, args, working_dir.as_ref(), false)?
         .wait()
         .await
         .with_context(|| format!("error during {name} call"))?;
@@ -199,6 +218,91 @@ pub async fn run_command(
     Ok(())
 }
 
+/// A line of output of a command.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputLine<'a> {
+    /// A line written to the standard output.
+    Stdout(&'a str),
+    /// A line written to the standard error.
+    Stderr(&'a str),
+}
+
+/// Run a global command with the given arguments, passing each line of its output to `on_line`
+/// while it runs.
+///
+/// Unlike [`run_command`], a bad exit status is not an error, but returned for the caller to
+/// interpret along with the output.
+#[tracing::instrument(level = "trace", skip(name, args, on_line))]
+pub async fn run_command_with_output(
+    name: &str,
+    path: impl AsRef<Path> + Debug,
+    args: &[impl AsRef<OsStr> + Debug],
+    working_dir: impl AsRef<Path> + Debug,
+    mut on_line: impl FnMut(OutputLine<'_>),
+) -> Result<ExitStatus> {
+    let mut child = spawn_command(name, path.as_ref(), args, working_dir.as_ref(), true)?;
+
+    let read_error = || format!("error reading the output of {name}");
+    let mut stdout = child.stdout.take().map(|out| BufReader::new(out).lines());
+    let mut stderr = child.stderr.take().map(|err| BufReader::new(err).lines());
+    while stdout.is_some() || stderr.is_some() {
+        tokio::select! {
+            line = next_line(&mut stdout) => match line.with_context(read_error)? {
+                Some(line) => on_line(OutputLine::Stdout(&line)),
+                None => stdout = None,
+            },
+            line = next_line(&mut stderr) => match line.with_context(read_error)? {
+                Some(line) => on_line(OutputLine::Stderr(&line)),
+                None => stderr = None,
+            },
+        }
+    }
+
+    child
+        .wait()
+        .await
+        .with_context(|| format!("error during {name} call"))
+}
+
+/// The next line of an output stream, pending forever once the stream was closed.
+async fn next_line<R: AsyncBufRead + Unpin>(
+    lines: &mut Option<Lines<R>>,
+) -> std::io::Result<Option<String>> {
+    match lines {
+        Some(lines) => lines.next_line().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Spawn a global command with the given arguments, capturing its output or passing it on.
+fn spawn_command(
+    name: &str,
+    path: &Path,
+    args: &[impl AsRef<OsStr> + Debug],
+    working_dir: &Path,
+    capture: bool,
+) -> Result<Child> {
+    tracing::debug!(?args, "{name} args");
+
+    let output = || match capture {
+        true => Stdio::piped(),
+        false => Stdio::inherit(),
+    };
+    Command::new(path)
+        .current_dir(working_dir)
+        .args(args)
+        .stdout(output())
+        .stderr(output())
+        .spawn()
+        .map_err(|err| check_target_not_found_err(err.into(), &path.display().to_string()))
+        .with_context(|| {
+            format!(
+                "error running {name} using executable '{}' with args: '{args:?}'",
+                path.display(),
+            )
+        })
+}
+
 /// Handle invocation errors indicating that the target binary was not found, simply wrapping the
 /// error in additional context stating more clearly that the target was not found.
 pub fn check_target_not_found_err(err: anyhow::Error, target: &str) -> anyhow::Error {
@@ -293,3 +397,47 @@ pub fn nonce_attr(attr: &Option<String>) -> String {
         None => "".to_string(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn reports_missing_commands() {
+        let err = run_command_with_output(
+            "missing",
+            "prank-missing-command",
+            &["--version"],
+            ".",
+            |_| {},
+        )
+        .await
+        .err()
+        .map(|err| format!("{err:#}"))
+        .unwrap_or_default();
+        assert!(err.contains("'prank-missing-command' not found"), "{err}");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn streams_output_lines() -> Result<()> {
+        let mut lines = vec![];
+        let status = run_command_with_output(
+            "sh",
+            "sh",
+            &["-c", "echo out; echo err >&2; exit 3"],
+            ".",
+            |line| {
+                lines.push(match line {
+                    OutputLine::Stdout(line) => format!("stdout: {line}"),
+                    OutputLine::Stderr(line) => format!("stderr: {line}"),
+                })
+            },
+        )
+        .await?;
+        assert_eq!(status.code(), Some(3));
+        lines.sort();
+        assert_eq!(lines, ["stderr: err", "stdout: out"]);
+        Ok(())
+    }
+}
@@ -0,0 +1,14 @@
+//! Helpers shared by the tests.
+
+use anyhow::Result;
+use std::path::Path;
+
+/// Write a file below a root directory, creating its parent directories.
+pub fn write(root: &Path, path: &str, content: impl AsRef<[u8]>) -> Result<()> {
+    let path = root.join(path);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, content)?;
+    Ok(())
+}
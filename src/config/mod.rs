@@ -68,5 +68,7 @@ pub mod types;
 pub const DIST_DIR: &str = "dist";
 /// The name of the directory used to stage build artifacts during an active build.
 pub const STAGE_DIR: &str = ".stage";
+/// The name of the directory of the dist dir, which build reports are written to.
+pub const REPORT_DIR: &str = ".prank";
 
 pub use models::{load, Clean, Configuration, Hooks, Tools, Watch};
@@ -0,0 +1,44 @@
+//! Configuration for the size budgets of release builds
+use crate::config::types::ByteSize;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Maximum sizes of the output of release builds, failing the build when exceeded.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+pub struct Budgets {
+    /// The budget of the main application bundle, including the modules it preloads
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub main: Option<Budget>,
+
+    /// The budget of all CSS files, together
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub css: Option<Budget>,
+
+    /// The budget of all files of the dist dir, together (excluding source maps)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total: Option<Budget>,
+
+    /// The budgets of every single file of an asset type, keyed by file extension (e.g. `js`)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub types: BTreeMap<String, Budget>,
+}
+
+/// Maximum sizes of some output.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+pub struct Budget {
+    /// The maximum size, as written to the dist dir
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw: Option<ByteSize>,
+
+    /// The maximum size, once compressed with gzip
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gzip: Option<ByteSize>,
+}
+
+impl Budgets {
+    /// Check if no budget is configured at all.
+    pub fn is_empty(&self) -> bool {
+        self.main.is_none() && self.css.is_none() && self.total.is_none() && self.types.is_empty()
+    }
+}
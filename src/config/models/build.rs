@@ -1,11 +1,11 @@
 use crate::config::{
-    models::ConfigModel,
-    types::{BaseUrl, Minify},
+    models::{Budgets, ConfigModel},
+    types::{BaseUrl, ByteSize, Minify, Precompress, PureScriptWarnings, SourceMaps, Targets},
 };
 use schemars::JsonSchema;
 use serde::{de, Deserialize, Deserializer, Serialize};
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     fmt::{Display, Formatter},
     marker::PhantomData,
     path::PathBuf,
@@ -15,11 +15,12 @@ use std::{
 /// Config options for the build system.
 #[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
 pub struct Build {
-    /// The index HTML file to drive the bundling process
-    #[serde(default = "default::target")]
-    pub target: PathBuf,
+    /// The HTML files of the pages to build, a path or glob pattern, or a list of them
+    /// [default: "index.html"]
+    #[serde(default)]
+    pub target: Targets,
 
-    /// The name of the output HTML file.
+    /// The name of the output HTML file, when building a single page.
     ///
     /// If not set, the file is named "index.html"
     #[serde(default = "default::html_output")]
@@ -73,6 +74,14 @@ pub struct Build {
     #[schemars(schema_with = "schema::features")]
     pub features: Vec<String>,
 
+    /// Features to activate, unless building without default features
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub default_features: Vec<String>,
+
+    /// All features of the application, activated when building with all features
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub available_features: Vec<String>,
+
     /// Whether to include hash values in the output file names
     #[serde(default = "default::filehash")]
     pub filehash: bool,
@@ -160,6 +169,75 @@ pub struct Build {
     /// The placeholder which is used in the 'nonce' attribute.
     #[serde(default = "default::nonce_placeholder")]
     pub nonce_placeholder: String,
+
+    /// How to handle warnings of the PureScript compiler [default: warn]
+    #[serde(default)]
+    pub purescript_warnings: PureScriptWarnings,
+
+    /// PureScript warning codes to ignore, e.g. "UnusedImport"
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub purescript_censor_codes: Vec<String>,
+
+    /// Emit source maps for the single release bundles, not for shared modules or chunks [default: none]
+    #[serde(default)]
+    pub source_maps: SourceMaps,
+
+    /// PureScript modules to emit as lazily loaded chunks in release builds
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub split: Vec<String>,
+
+    /// The path below the public URL, at which the PureScript output directory is served in dev
+    /// mode [default: "output"]
+    #[serde(default = "default::output_mount")]
+    pub output_mount: String,
+
+    /// Generate the `Prank.Env` module, exposing the build configuration to the application
+    /// [default: false]
+    #[serde(default)]
+    pub env_module: bool,
+
+    /// Write a report of the bundle sizes of release builds to the `.prank` directory of the dist
+    /// dir [default: false]
+    #[serde(default)]
+    pub report: bool,
+
+    /// Write a `manifest.json` to the dist dir, mapping the sources of the assets to their output
+    /// files [default: false]
+    #[serde(default)]
+    pub manifest: bool,
+
+    /// Render the source HTML of the pages as templates [default: false]
+    #[serde(default)]
+    pub template: bool,
+
+    /// Variables of the templates of the pages, besides the built-in `release`, `profile`,
+    /// `public_url` and `env` ones.
+    ///
+    /// These values can only be provided via config file.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub template_vars: BTreeMap<String, serde_json::Value>,
+
+    /// Generate a service worker for release builds, precaching all files of the dist dir
+    /// [default: false]
+    #[serde(default)]
+    pub service_worker: bool,
+
+    /// Maximum sizes of the output of release builds, failing the build when exceeded
+    #[serde(default, skip_serializing_if = "Budgets::is_empty")]
+    pub budgets: Budgets,
+
+    /// Formats to precompress the files of the dist dir with, written next to them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub precompress: Vec<Precompress>,
+
+    /// The minimum size of the files to precompress [default: "1KiB"]
+    #[serde(default = "default::precompress_threshold")]
+    pub precompress_threshold: ByteSize,
+
+    /// Inline the files referenced by the `url()`s of stylesheets as data URIs when smaller than
+    /// this size [default: 0, disabled]
+    #[serde(default)]
+    pub css_inline_limit: ByteSize,
 }
 
 fn string_or_vec<'de, T, D>(deserializer: D) -> Result<Vec<T>, D::Error>
@@ -207,7 +285,7 @@ where
 impl Default for Build {
     fn default() -> Self {
         Self {
-            target: default::target(),
+            target: Targets::default(),
             html_output: default::html_output(),
             release: false,
             spago_profile: None,
@@ -220,6 +298,8 @@ impl Default for Build {
             no_default_features: false,
             all_features: false,
             features: vec![],
+            default_features: vec![],
+            available_features: vec![],
             example: None,
             filehash: default::filehash(),
             pattern_script: None,
@@ -233,22 +313,33 @@ impl Default for Build {
             allow_self_closing_script: false,
             create_nonce: false,
             nonce_placeholder: default::nonce_placeholder(),
+            purescript_warnings: Default::default(),
+            purescript_censor_codes: vec![],
+            source_maps: Default::default(),
+            split: vec![],
+            output_mount: default::output_mount(),
+            env_module: false,
+            report: false,
+            manifest: false,
+            template: false,
+            template_vars: Default::default(),
+            service_worker: false,
+            budgets: Default::default(),
+            precompress: vec![],
+            precompress_threshold: default::precompress_threshold(),
+            css_inline_limit: Default::default(),
         }
     }
 }
 
 mod default {
-    use crate::config::DIST_DIR;
+    use crate::config::{types::ByteSize, DIST_DIR};
     use std::path::PathBuf;
 
     pub fn dist() -> PathBuf {
         DIST_DIR.into()
     }
 
-    pub fn target() -> PathBuf {
-        "index.html".into()
-    }
-
     pub fn html_output() -> String {
         "index.html".into()
     }
@@ -261,9 +352,17 @@ mod default {
         true
     }
 
+    pub fn output_mount() -> String {
+        "output".to_string()
+    }
+
     pub fn nonce_placeholder() -> String {
         "{{__PRANK NONCE__}}".to_string()
     }
+
+    pub const fn precompress_threshold() -> ByteSize {
+        ByteSize(1024)
+    }
 }
 
 mod schema {
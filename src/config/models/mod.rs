@@ -5,6 +5,7 @@
 
 pub mod source;
 
+mod budgets;
 mod build;
 mod clean;
 mod core;
@@ -14,6 +15,7 @@ mod serve;
 mod tools;
 mod watch;
 
+pub use budgets::*;
 pub use build::*;
 pub use clean::*;
 pub use core::*;
@@ -34,6 +34,11 @@ pub struct Tools {
     #[serde(default)]
     #[arg(env = "PRANK_TOOLS_PURS_TIDY")]
     pub purs_tidy: Option<String>,
+
+    /// Version of `purs`, the PureScript compiler, to use.
+    #[serde(default)]
+    #[arg(env = "PRANK_TOOLS_PURS")]
+    pub purs: Option<String>,
 }
 
 impl Tools {
@@ -45,6 +50,7 @@ impl Tools {
             .or(config.tools.purescript_backend_es);
         config.tools.spago = self.spago.or(config.tools.spago);
         config.tools.purs_tidy = self.purs_tidy.or(config.tools.purs_tidy);
+        config.tools.purs = self.purs.or(config.tools.purs);
         Ok(config)
     }
 }
@@ -17,6 +17,16 @@ pub struct Watch {
     /// Paths to reload on change [default: []]
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub reload: Vec<String>,
+
+    /// Rebuild changed PureScript modules through a long-lived `purs ide server`, instead of
+    /// running `spago build` [default: false]
+    #[serde(default)]
+    pub purs_ide: bool,
+
+    /// Serve the PureScript output in dev mode as a single bundle, instead of individual
+    /// modules [default: false]
+    #[serde(default)]
+    pub dev_bundle: bool,
 }
 
 impl ConfigModel for Watch {}
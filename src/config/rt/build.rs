@@ -1,15 +1,20 @@
 use super::{super::STAGE_DIR, RtcBuilder};
 use crate::{
+    common::dist_relative,
     config::{
-        models::{Configuration, Hook, Tools},
+        models::{Budgets, Configuration, Hook, Tools},
         rt::{CoreOptions, RtcCore},
-        types::{BaseUrl, Minify},
+        types::{common_parent, BaseUrl, Minify, Precompress, PureScriptWarnings, SourceMaps},
         Hooks,
     },
     tools::HttpClientOptions,
 };
 use anyhow::{ensure, Context};
-use std::{collections::HashMap, ops::Deref, path::PathBuf};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    ops::Deref,
+    path::PathBuf,
+};
 
 /// Config options for the spago build command
 #[derive(Clone, Debug)]
@@ -25,15 +30,52 @@ pub enum Features {
     },
 }
 
+impl Features {
+    /// The names of the activated features, sorted and without duplicates.
+    pub fn enabled(&self, default: &[String], available: &[String]) -> Vec<String> {
+        let mut enabled = BTreeSet::new();
+        match self {
+            Self::All => enabled.extend(default.iter().chain(available).cloned()),
+            Self::Custom {
+                features,
+                no_default_features,
+            } => {
+                if !no_default_features {
+                    enabled.extend(default.iter().cloned());
+                }
+                enabled.extend(
+                    features
+                        .iter()
+                        .flat_map(|features| {
+                            features.split(|c: char| c == ',' || c.is_whitespace())
+                        })
+                        .filter(|feature| !feature.is_empty())
+                        .map(str::to_string),
+                );
+            }
+        }
+        enabled.into_iter().collect()
+    }
+}
+
+/// A page of the application, built from a source HTML file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RtcPage {
+    /// The source HTML file.
+    pub target: PathBuf,
+    /// The path of the output HTML file, relative to the dist dir, using forward slashes.
+    pub output: String,
+}
+
 /// Runtime config for the build system.
 #[derive(Clone, Debug)]
 pub struct RtcBuild {
     pub core: RtcCore,
-    /// The index HTML file to drive the bundling process.
+    /// The HTML file of the first page.
     pub target: PathBuf,
-    /// The name of the output HTML file.
-    pub html_output_filename: String,
-    /// The parent directory of the target index HTML file.
+    /// The pages to build, at least one.
+    pub pages: Vec<RtcPage>,
+    /// The common parent directory of the target HTML files.
     pub target_parent: PathBuf,
     /// Build in release mode.
     pub release: bool,
@@ -56,6 +98,10 @@ pub struct RtcBuild {
     pub staging_dist: PathBuf,
     /// The configuration of the features passed to spago.
     pub spago_features: Features,
+    /// Features to activate, unless building without default features.
+    pub default_features: Vec<String>,
+    /// All features of the application, activated when building with all features.
+    pub available_features: Vec<String>,
     /// Optional example to be passed to spago.
     pub spago_example: Option<String>,
     /// Configuration for automatic application download.
@@ -92,6 +138,37 @@ pub struct RtcBuild {
     pub allow_self_closing_script: bool,
     /// When set, create nonce attributes with the option as placeholder
     pub create_nonce: Option<String>,
+    /// How to handle warnings of the PureScript compiler
+    pub purescript_warnings: PureScriptWarnings,
+    /// PureScript warning codes to ignore
+    pub purescript_censor_codes: Vec<String>,
+    /// Emit source maps for the release bundles
+    pub source_maps: SourceMaps,
+    /// PureScript modules to emit as lazily loaded chunks in release builds
+    pub split: Vec<String>,
+    /// The path below the public URL, at which the PureScript output directory is served in dev
+    /// mode, without leading or trailing slashes.
+    pub output_mount: String,
+    /// Generate the `Prank.Env` module
+    pub env_module: bool,
+    /// Write a report of the bundle sizes of release builds
+    pub report: bool,
+    /// Write the asset manifest to the dist dir
+    pub manifest: bool,
+    /// Render the source HTML of the pages as templates
+    pub template: bool,
+    /// Variables of the templates of the pages
+    pub template_vars: BTreeMap<String, serde_json::Value>,
+    /// Generate a service worker for release builds
+    pub service_worker: bool,
+    /// Maximum sizes of the output of release builds
+    pub budgets: Budgets,
+    /// Formats to precompress the files of the dist dir with
+    pub precompress: Vec<Precompress>,
+    /// The minimum size of the files to precompress, in bytes
+    pub precompress_threshold: u64,
+    /// The size below which the files referenced by stylesheets are inlined, in bytes
+    pub css_inline_limit: u64,
 }
 
 impl Deref for RtcBuild {
@@ -126,27 +203,6 @@ impl RtcBuild {
 
         let core = RtcCore::new(core_config, core_opts)?;
 
-        // Get the canonical path to the target HTML file.
-        let mut pre_target = build.target.clone();
-        if !pre_target.is_absolute() {
-            pre_target = core.working_directory.join(pre_target);
-        }
-        let target = pre_target.canonicalize().with_context(|| {
-            format!(
-                "error getting the canonical path to the build target HTML file {:?}",
-                &pre_target
-            )
-        })?;
-
-        let html_output_filename = build.html_output;
-
-        // Get the target HTML's parent dir, falling back to OS specific root, as that is the only
-        // time when no parent could be determined.
-        let target_parent = target
-            .parent()
-            .map(|path| path.to_owned())
-            .unwrap_or_else(|| PathBuf::from(std::path::MAIN_SEPARATOR.to_string()));
-
         // Ensure the final dist dir exists and that we have a canonical path to the dir. Normally
         // we would want to avoid such an action at this layer, however to ensure that other layers
         // have a reliable FS path to work with, we make an exception here.
@@ -160,6 +216,31 @@ impl RtcBuild {
             .context("error taking canonical path to dist dir")?;
         let staging_dist = final_dist.join(STAGE_DIR);
 
+        // Get the canonical paths to the target HTML files, and their common parent dir.
+        let targets = build
+            .target
+            .resolve(&core.working_directory, &[&final_dist])?;
+        let target_parent = common_parent(&targets);
+        let target = targets[0].clone();
+
+        // A single page is written to the configured output file, several pages are written
+        // to the same paths in the dist dir as in the source tree.
+        let pages = match targets.as_slice() {
+            [target] => vec![RtcPage {
+                target: target.clone(),
+                output: build.html_output,
+            }],
+            targets => targets
+                .iter()
+                .map(|target| {
+                    Ok(RtcPage {
+                        target: target.clone(),
+                        output: dist_relative(&target_parent, target)?,
+                    })
+                })
+                .collect::<anyhow::Result<_>>()?,
+        };
+
         // Highlander-rule: There can be only one (prohibits contradicting arguments):
         ensure!(
             !(build.all_features && (build.no_default_features || !build.features.is_empty())),
@@ -185,10 +266,23 @@ impl RtcBuild {
 
         let create_nonce = build.create_nonce.then_some(build.nonce_placeholder);
 
+        if build.report && !build.release {
+            tracing::warn!("bundle size reports are only written for release builds");
+        }
+        if build.service_worker && !build.release {
+            tracing::warn!("the service worker is only generated for release builds");
+        }
+
+        let output_mount = build.output_mount.trim_matches('/').to_string();
+        ensure!(
+            !output_mount.is_empty(),
+            "The path of the PureScript output (build.output_mount) must not be empty"
+        );
+
         Ok(Self {
             core,
             target,
-            html_output_filename,
+            pages,
             target_parent,
             release: build.release,
             spago_profile: build.spago_profile,
@@ -197,6 +291,8 @@ impl RtcBuild {
             staging_dist,
             final_dist,
             spago_features,
+            default_features: build.default_features,
+            available_features: build.available_features,
             spago_example: build.example,
             tools,
             hooks,
@@ -216,6 +312,21 @@ impl RtcBuild {
             no_sri: build.no_sri,
             allow_self_closing_script: build.allow_self_closing_script,
             create_nonce,
+            purescript_warnings: build.purescript_warnings,
+            purescript_censor_codes: build.purescript_censor_codes,
+            source_maps: build.source_maps,
+            split: build.split,
+            output_mount,
+            env_module: build.env_module,
+            report: build.report,
+            manifest: build.manifest,
+            template: build.template,
+            template_vars: build.template_vars,
+            service_worker: build.service_worker,
+            budgets: build.budgets,
+            precompress: build.precompress,
+            precompress_threshold: build.precompress_threshold.0,
+            css_inline_limit: build.css_inline_limit.0,
         })
     }
 
@@ -223,7 +334,10 @@ impl RtcBuild {
     #[cfg(test)]
     pub async fn new_test(tmpdir: &std::path::Path) -> anyhow::Result<Self> {
         let target = tmpdir.join("index.html");
-        let html_output_filename = String::from("index.html");
+        let pages = vec![RtcPage {
+            target: target.clone(),
+            output: String::from("index.html"),
+        }];
         let target_parent = tmpdir.to_path_buf();
         let final_dist = tmpdir.join("dist");
         let staging_dist = final_dist.join(".stage");
@@ -233,7 +347,7 @@ impl RtcBuild {
         Ok(Self {
             core: RtcCore::new_test(tmpdir),
             target,
-            html_output_filename,
+            pages,
             target_parent,
             release: false,
             spago_profile: None,
@@ -242,6 +356,8 @@ impl RtcBuild {
             final_dist,
             staging_dist,
             spago_features: Features::All,
+            default_features: vec![],
+            available_features: vec![],
             spago_example: None,
             tools: Default::default(),
             hooks: Vec::new(),
@@ -259,6 +375,21 @@ impl RtcBuild {
             no_sri: false,
             allow_self_closing_script: false,
             create_nonce: None,
+            purescript_warnings: PureScriptWarnings::Warn,
+            purescript_censor_codes: vec![],
+            source_maps: SourceMaps::None,
+            split: vec![],
+            output_mount: "output".to_string(),
+            env_module: false,
+            report: false,
+            manifest: false,
+            template: false,
+            template_vars: Default::default(),
+            service_worker: false,
+            budgets: Default::default(),
+            precompress: vec![],
+            precompress_threshold: 1024,
+            css_inline_limit: 0,
         })
     }
 
@@ -267,6 +398,11 @@ impl RtcBuild {
         !no_minify && self.should_minify()
     }
 
+    /// Check if the service worker should be generated and registered.
+    pub fn should_generate_service_worker(&self) -> bool {
+        self.service_worker && self.release
+    }
+
     /// Evaluate a global minify state, assets might override this.
     pub fn should_minify(&self) -> bool {
         match (self.minify, self.release) {
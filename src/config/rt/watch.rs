@@ -64,6 +64,10 @@ pub struct RtcWatch {
     pub clear_screen: bool,
     /// No error reporting.
     pub no_error_reporting: bool,
+    /// Rebuild changed PureScript modules through `purs ide server`.
+    pub purs_ide: bool,
+    /// Serve the PureScript output in dev mode as a single bundle.
+    pub dev_bundle: bool,
 }
 
 impl Deref for RtcWatch {
@@ -102,13 +106,14 @@ impl RtcWatch {
             watch,
             ignore,
             reload,
+            purs_ide,
+            dev_bundle,
         } = config.watch.clone();
 
         let build = RtcBuild::new(config, build_opts)?;
 
         tracing::debug!("Disable error reporting: {no_error_reporting}");
 
-
         // Take the canonical path of each of the specified watch targets.
         let mut paths = vec![];
         for path in watch {
@@ -194,6 +199,8 @@ impl RtcWatch {
             enable_cooldown,
             clear_screen,
             no_error_reporting,
+            purs_ide,
+            dev_bundle,
         })
     }
 }
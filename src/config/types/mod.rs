@@ -5,7 +5,12 @@ mod base_url;
 mod cross_origin;
 mod duration;
 mod minify;
+mod precompress;
+mod size;
+mod source_maps;
+mod targets;
 mod uri;
+mod warnings;
 mod ws;
 
 pub use address_family::*;
@@ -13,5 +18,10 @@ pub use base_url::*;
 pub use cross_origin::*;
 pub use duration::*;
 pub use minify::*;
+pub use precompress::*;
+pub use size::*;
+pub use source_maps::*;
+pub use targets::*;
 pub use uri::*;
+pub use warnings::*;
 pub use ws::*;
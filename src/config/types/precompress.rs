@@ -0,0 +1,35 @@
+use clap::ValueEnum;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// A format to precompress the files of the dist dir with.
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Deserialize,
+    Serialize,
+    ValueEnum,
+    JsonSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Precompress {
+    /// Write `.gz` files
+    Gzip,
+    /// Write `.br` files
+    Brotli,
+}
+
+impl Precompress {
+    /// The extension of the compressed files.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Gzip => "gz",
+            Self::Brotli => "br",
+        }
+    }
+}
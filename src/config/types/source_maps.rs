@@ -0,0 +1,25 @@
+use clap::ValueEnum;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Whether to emit source maps for the release bundles.
+#[derive(
+    Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize, ValueEnum, JsonSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceMaps {
+    /// Don't emit source maps
+    #[default]
+    None,
+    /// Emit source maps, and reference them from the bundles
+    External,
+    /// Emit source maps, without referencing them from the bundles
+    Hidden,
+}
+
+impl SourceMaps {
+    /// Whether source maps are emitted at all.
+    pub fn enabled(&self) -> bool {
+        !matches!(self, Self::None)
+    }
+}
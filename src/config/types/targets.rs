@@ -0,0 +1,287 @@
+use crate::pipelines::partials;
+use anyhow::{bail, Context, Result};
+use globset::GlobBuilder;
+use schemars::gen::SchemaGenerator;
+use schemars::schema::Schema;
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::path::{Component, Path, PathBuf};
+
+/// The source HTML files of the pages to build.
+///
+/// It is configured as a path or glob pattern, e.g. `"index.html"` or `"pages/**/*.html"`, or as a
+/// list of them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Targets(pub Vec<PathBuf>);
+
+/// The representation of the targets in the configuration.
+#[derive(Deserialize, Serialize, JsonSchema)]
+#[serde(untagged)]
+enum Repr {
+    One(PathBuf),
+    Many(Vec<PathBuf>),
+}
+
+impl Targets {
+    /// Resolve the targets to the canonical paths of the HTML files, in the order of the targets.
+    ///
+    /// Glob patterns are matched against the files below their literal prefix, skipping hidden
+    /// directories, `node_modules` and the `skip`ped directories (e.g. the dist dir), and must
+    /// match at least one file. The partials included by the pages are no pages themselves, so
+    /// glob patterns don't match them.
+    pub fn resolve(&self, working_directory: &Path, skip: &[&Path]) -> Result<Vec<PathBuf>> {
+        let mut targets = Vec::new();
+        let mut listed = Vec::new();
+        for target in &self.0 {
+            let target = working_directory.join(target);
+            let matches = match is_glob(&target) {
+                true => glob(&target, skip)?,
+                false => {
+                    let target = target.canonicalize().with_context(|| {
+                        format!(
+                            "error getting the canonical path to the build target HTML file {target:?}"
+                        )
+                    })?;
+                    listed.push(target.clone());
+                    vec![target]
+                }
+            };
+            for target in matches {
+                if !targets.contains(&target) {
+                    targets.push(target);
+                }
+            }
+        }
+
+        // only listing a partial explicitly makes it a page
+        let included = targets
+            .iter()
+            .flat_map(|target| partials(target))
+            .collect::<Vec<_>>();
+        targets.retain(|target| listed.contains(target) || !included.contains(target));
+
+        if targets.is_empty() {
+            bail!("no build target HTML file configured");
+        }
+        Ok(targets)
+    }
+}
+
+/// Check if a path is a glob pattern.
+fn is_glob(path: &Path) -> bool {
+    path.to_string_lossy().contains(['*', '?', '[', '{'])
+}
+
+/// Find the files matching a glob pattern, sorted by their path.
+fn glob(pattern: &Path, skip: &[&Path]) -> Result<Vec<PathBuf>> {
+    let matcher = GlobBuilder::new(&pattern.to_string_lossy())
+        .literal_separator(true)
+        .build()
+        .with_context(|| format!("invalid build target pattern {pattern:?}"))?
+        .compile_matcher();
+
+    // only walk the directory tree below the literal prefix of the pattern
+    let root = pattern
+        .components()
+        .take_while(|component| !is_glob(component.as_os_str().as_ref()))
+        .collect::<PathBuf>();
+
+    let mut matches = Vec::new();
+    let mut dirs = vec![root];
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries {
+            let entry = entry.with_context(|| format!("error reading {dir:?}"))?;
+            let path = entry.path();
+            let file_type = entry
+                .file_type()
+                .with_context(|| format!("error reading the metadata of {path:?}"))?;
+            if file_type.is_dir() {
+                let name = entry.file_name();
+                let hidden = name.to_string_lossy().starts_with('.');
+                if !hidden && name != "node_modules" && !skip.contains(&path.as_path()) {
+                    dirs.push(path);
+                }
+            } else if matcher.is_match(&path) {
+                matches.push(path.canonicalize().with_context(|| {
+                    format!(
+                        "error getting the canonical path to the build target HTML file {path:?}"
+                    )
+                })?);
+            }
+        }
+    }
+
+    if matches.is_empty() {
+        bail!("no build target HTML file matches {pattern:?}");
+    }
+    matches.sort();
+    Ok(matches)
+}
+
+/// The deepest common ancestor of the directories of some files.
+pub fn common_parent(files: &[PathBuf]) -> PathBuf {
+    let mut parents = files.iter().filter_map(|file| file.parent());
+    let Some(first) = parents.next() else {
+        return PathBuf::from(std::path::MAIN_SEPARATOR.to_string());
+    };
+    let mut common = first.components().collect::<Vec<Component>>();
+    for parent in parents {
+        let shared = common
+            .iter()
+            .zip(parent.components())
+            .take_while(|(a, b)| **a == *b)
+            .count();
+        common.truncate(shared);
+    }
+    common.into_iter().collect()
+}
+
+impl Default for Targets {
+    fn default() -> Self {
+        Self(vec!["index.html".into()])
+    }
+}
+
+impl<'de> Deserialize<'de> for Targets {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(match Repr::deserialize(deserializer)? {
+            Repr::One(target) => Self(vec![target]),
+            Repr::Many(targets) => Self(targets),
+        })
+    }
+}
+
+impl Serialize for Targets {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self.0.as_slice() {
+            [target] => target.serialize(serializer),
+            targets => targets.serialize(serializer),
+        }
+    }
+}
+
+impl JsonSchema for Targets {
+    fn schema_name() -> String {
+        "Targets".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        Repr::json_schema(gen)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn deserialize() -> Result<()> {
+        let one: Targets = serde_json::from_str(r#""index.html""#)?;
+        assert_eq!(one, Targets(vec!["index.html".into()]));
+        let many: Targets = serde_json::from_str(r#"["index.html", "pages/*.html"]"#)?;
+        assert_eq!(
+            many,
+            Targets(vec!["index.html".into(), "pages/*.html".into()])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn resolve() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let root = tmp.path().canonicalize()?;
+        for file in [
+            "index.html",
+            "about.html",
+            "app/index.html",
+            "app/style.css",
+            "dist/index.html",
+            "node_modules/pkg/index.html",
+        ] {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().context("no parent")?)?;
+            std::fs::write(path, "")?;
+        }
+
+        let targets = Targets(vec!["index.html".into(), "**/*.html".into()]);
+        let dist = root.join("dist");
+        let resolved = targets.resolve(&root, &[&dist])?;
+        assert_eq!(
+            resolved,
+            [
+                root.join("index.html"),
+                root.join("about.html"),
+                root.join("app/index.html"),
+            ]
+        );
+        assert_eq!(common_parent(&resolved), root);
+        assert_eq!(common_parent(&resolved[2..]), root.join("app"));
+
+        let missing = Targets(vec!["pages/*.html".into()]);
+        assert!(missing.resolve(&root, &[]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn skips_partials() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let root = tmp.path().canonicalize()?;
+        for (file, content) in [
+            (
+                "index.html",
+                r#"<link data-prank rel="include" href="partials/header.html"/>"#,
+            ),
+            (
+                "blog/index.html",
+                r#"<link data-prank rel="include" href="../partials/footer.html"/>"#,
+            ),
+            (
+                "partials/header.html",
+                r#"<header><link data-prank rel="include" href="nav.html"/></header>"#,
+            ),
+            ("partials/nav.html", "<nav></nav>"),
+            ("partials/footer.html", "<footer></footer>"),
+        ] {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().context("no parent")?)?;
+            std::fs::write(path, content)?;
+        }
+
+        let targets = Targets(vec!["**/*.html".into()]);
+        assert_eq!(
+            targets.resolve(&root, &[])?,
+            [root.join("blog/index.html"), root.join("index.html")]
+        );
+
+        // a partial listed explicitly is a page of its own
+        let targets = Targets(vec!["**/*.html".into(), "partials/nav.html".into()]);
+        assert_eq!(
+            targets.resolve(&root, &[])?,
+            [
+                root.join("blog/index.html"),
+                root.join("index.html"),
+                root.join("partials/nav.html"),
+            ]
+        );
+
+        // partials are recognized by the pages including them
+        let targets = Targets(vec!["partials/*.html".into()]);
+        assert_eq!(
+            targets.resolve(&root, &[])?,
+            [
+                root.join("partials/footer.html"),
+                root.join("partials/header.html"),
+            ]
+        );
+        Ok(())
+    }
+}
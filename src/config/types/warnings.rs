@@ -0,0 +1,18 @@
+use clap::ValueEnum;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// How to handle the warnings of the PureScript compiler.
+#[derive(
+    Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize, ValueEnum, JsonSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum PureScriptWarnings {
+    /// Fail the build on warnings
+    Deny,
+    /// Report warnings, without failing the build
+    #[default]
+    Warn,
+    /// Ignore warnings
+    Allow,
+}
@@ -1,4 +1,7 @@
-use crate::{config::rt::RtcBuild, pipelines::PipelineStage};
+use crate::{
+    config::rt::RtcBuild,
+    pipelines::{PipelineStage, MANIFEST_FILE},
+};
 use anyhow::{bail, Context, Result};
 use futures_util::stream::{FuturesUnordered, StreamExt};
 use std::{process::Stdio, sync::Arc};
@@ -27,6 +30,9 @@ pub fn spawn_hooks(cfg: Arc<RtcBuild>, stage: PipelineStage) -> HookHandles {
                 .env("PRANK_STAGING_DIR", &cfg.staging_dist)
                 .env("PRANK_DIST_DIR", &cfg.final_dist)
                 .env("PRANK_PUBLIC_URL", &cfg.public_url);
+            if cfg.manifest && stage == PipelineStage::PostBuild {
+                command.env("PRANK_MANIFEST", cfg.staging_dist.join(MANIFEST_FILE));
+            }
 
             tracing::info!(command_arguments = ?hook_cfg.command_arguments(), "spawned hook {}", hook_cfg.command());
 
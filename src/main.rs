@@ -1,6 +1,7 @@
 #![deny(clippy::expect_used)]
 #![deny(clippy::unwrap_used)]
 
+mod budgets;
 mod build;
 mod cmd;
 mod common;
@@ -10,6 +11,7 @@ mod pipelines;
 mod processing;
 mod proxy;
 mod serve;
+mod test;
 mod tls;
 mod tools;
 mod version;
@@ -179,6 +181,8 @@ impl Prank {
             PrankSubcommands::Clean(inner) => inner.run(self.config).await,
             PrankSubcommands::Serve(inner) => inner.run(self.config).await,
             PrankSubcommands::Watch(inner) => inner.run(self.config).await,
+            PrankSubcommands::Test(inner) => inner.run(self.config).await,
+            PrankSubcommands::Fmt(inner) => inner.run(self.config).await,
             PrankSubcommands::Config(inner) => inner.run(self.config).await,
             PrankSubcommands::Tools(inner) => inner.run(self.config).await,
         }
@@ -194,6 +198,10 @@ enum PrankSubcommands {
     Watch(cmd::watch::Watch),
     /// Build, watch & serve the web app and all of its assets.
     Serve(cmd::serve::Serve),
+    /// Run the test suite of the PureScript package.
+    Test(cmd::test::Test),
+    /// Format the PureScript sources of the package using purs-tidy.
+    Fmt(cmd::fmt::Fmt),
     /// Clean output artifacts.
     Clean(cmd::clean::Clean),
     /// Prank config controls.
@@ -84,7 +84,8 @@ impl CopyDir {
 }
 
 /// The output of a CopyDir build pipeline.
-pub struct CopyDirOutput(usize);
+#[derive(Clone)]
+pub struct CopyDirOutput(pub usize);
 
 impl CopyDirOutput {
     pub async fn finalize(self, dom: &mut Document) -> Result<()> {
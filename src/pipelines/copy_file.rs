@@ -66,7 +66,7 @@ impl CopyFile {
         let dir_out =
             target_path(&self.cfg.staging_dist, self.target_path.as_deref(), None).await?;
 
-        let _ = self
+        let file = self
             .asset
             .copy(
                 &self.cfg.staging_dist,
@@ -78,15 +78,24 @@ impl CopyFile {
             .await?;
         tracing::debug!(path = ?rel_path, "finished copying file");
 
-        Ok(PrankAssetPipelineOutput::CopyFile(CopyFileOutput(self.id)))
+        Ok(PrankAssetPipelineOutput::CopyFile(CopyFileOutput {
+            id: self.id,
+            file,
+        }))
     }
 }
 
 /// The output of a CopyFile build pipeline.
-pub struct CopyFileOutput(usize);
+#[derive(Clone)]
+pub struct CopyFileOutput {
+    /// The ID of this pipeline.
+    pub id: usize,
+    /// Name of the copied file, relative to the dist dir.
+    pub file: String,
+}
 
 impl CopyFileOutput {
     pub async fn finalize(self, dom: &mut Document) -> Result<()> {
-        dom.remove(&super::prank_id_selector(self.0))
+        dom.remove(&super::prank_id_selector(self.id))
     }
 }
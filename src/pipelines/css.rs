@@ -1,14 +1,16 @@
 //! CSS asset pipeline.
 
 use super::{
-    data_target_path, AssetFile, AttrWriter, Attrs, PrankAssetPipelineOutput, ATTR_HREF,
-    ATTR_NO_MINIFY,
+    css_urls::rewrite_css_urls, data_target_path, AssetFile, AttrWriter, Attrs,
+    PrankAssetPipelineOutput, ATTR_HREF, ATTR_NO_MINIFY,
 };
 use crate::{
     common::{html_rewrite::Document, target_path},
     config::rt::RtcBuild,
-    pipelines::AssetFileType,
-    processing::integrity::{IntegrityType, OutputDigest},
+    processing::{
+        integrity::{IntegrityType, OutputDigest},
+        minify::minify_css,
+    },
 };
 use anyhow::{Context, Result};
 use std::path::PathBuf;
@@ -80,14 +82,31 @@ impl Css {
         let result_path =
             target_path(&self.cfg.staging_dist, self.target_path.as_deref(), None).await?;
 
+        let minify = self.cfg.minify_asset(self.no_minify);
+        let css = self.asset.read_to_string().await?;
+        let css = rewrite_css_urls(
+            &self.cfg,
+            css,
+            &self.asset.path,
+            &result_path,
+            false,
+            minify,
+        )
+        .await?;
+        let bytes = match minify {
+            true => minify_css(css.into_bytes()),
+            false => css.into_bytes(),
+        };
+
         let file = self
             .asset
-            .copy(
+            .write_variant(
                 &self.cfg.staging_dist,
                 &result_path,
                 self.cfg.filehash,
-                self.cfg.minify_asset(self.no_minify),
-                AssetFileType::Css,
+                "",
+                self.asset.ext.as_deref().unwrap_or("css"),
+                bytes,
             )
             .await?;
         tracing::debug!(path = ?rel_path, "finished copying & hashing css");
@@ -112,6 +131,7 @@ impl Css {
 }
 
 /// The output of a CSS build pipeline.
+#[derive(Clone)]
 pub struct CssOutput {
     /// The runtime build config.
     pub cfg: Arc<RtcBuild>,
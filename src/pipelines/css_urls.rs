@@ -0,0 +1,283 @@
+//! Rewriting of the `url()` references of stylesheets.
+
+use super::{AssetFile, AssetFileType};
+use crate::{config::rt::RtcBuild, processing::image::ImageType};
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use lightningcss::{
+    dependencies::{Dependency, DependencyOptions},
+    stylesheet::{ParserOptions, PrinterOptions, StyleSheet},
+};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+/// The prefix of the comment linking a stylesheet to its source map.
+const SOURCE_MAPPING_URL: &str = "/*# sourceMappingURL=";
+
+/// Copy the local files referenced by the `url()`s of a stylesheet to the result dir, and rewrite
+/// the URLs accordingly. The files are hashed for cache control, and optimized when minifying.
+/// Files smaller than the configured limit are inlined as data URIs instead, unless their URL
+/// has a query or fragment.
+///
+/// The URLs are resolved relative to the source file of the stylesheet. The rewritten URLs are
+/// relative to the result dir, or absolute if the stylesheet is inlined into the page. URLs with a
+/// scheme, absolute paths and fragments are left alone, as are the files which can't be found.
+///
+/// Stylesheets without local references are returned as is. Otherwise, they are printed by
+/// lightningcss, which drops their comments.
+pub async fn rewrite_css_urls(
+    cfg: &RtcBuild,
+    css: String,
+    source: &Path,
+    result_dir: &Path,
+    inline: bool,
+    minify: bool,
+) -> Result<String> {
+    let source_dir = source
+        .parent()
+        .context("CSS file has no parent directory")?;
+    let filename = source.to_string_lossy();
+    let Some(Analyzed {
+        code,
+        urls,
+        imports,
+    }) = analyze(&css, &filename, minify)
+    else {
+        return Ok(css);
+    };
+    if !urls.iter().any(|(_, url)| local_path(url).is_some()) {
+        return Ok(css);
+    }
+
+    // imports keep their URL, the same URL has the same placeholder, the files are only copied
+    // once
+    let mut rewritten = imports.into_iter().collect::<HashMap<_, _>>();
+    for (placeholder, url) in &urls {
+        if rewritten.contains_key(placeholder) {
+            continue;
+        }
+        let new_url = match local_path(url) {
+            Some((path, suffix)) => {
+                // data URIs can't have a query or fragment
+                let may_inline = suffix.is_empty();
+                match rewrite_url(
+                    cfg, source_dir, path, result_dir, inline, may_inline, minify,
+                )
+                .await?
+                {
+                    Some(new_url) => format!("{new_url}{suffix}"),
+                    None => url.clone(),
+                }
+            }
+            None => url.clone(),
+        };
+        rewritten.insert(placeholder.clone(), new_url);
+    }
+
+    let mut code = code;
+    for (placeholder, url) in rewritten {
+        code = code.replace(&format!("\"{placeholder}\""), &css_string(&url));
+    }
+    if let Some(index) = css.rfind(SOURCE_MAPPING_URL) {
+        code.push('\n');
+        code.push_str(css[index..].trim_end());
+    }
+    Ok(code)
+}
+
+/// A stylesheet printed with placeholders in place of its URLs.
+struct Analyzed {
+    /// The printed stylesheet.
+    code: String,
+    /// The URLs of the `url()`s, by placeholder.
+    urls: Vec<(String, String)>,
+    /// The URLs of the `@import`s, by placeholder.
+    imports: Vec<(String, String)>,
+}
+
+/// Print a stylesheet with placeholders in place of its URLs.
+///
+/// Returns `None` if the stylesheet can't be parsed or printed.
+fn analyze(css: &str, filename: &str, minify: bool) -> Option<Analyzed> {
+    let options = ParserOptions {
+        filename: filename.to_string(),
+        ..Default::default()
+    };
+    let stylesheet = StyleSheet::parse(css, options)
+        .map_err(|err| tracing::warn!("CSS parsing failed, not rewriting its URLs: {err}"))
+        .ok()?;
+    let result = stylesheet
+        .to_css(PrinterOptions {
+            minify,
+            analyze_dependencies: Some(DependencyOptions {
+                remove_imports: false,
+            }),
+            ..Default::default()
+        })
+        .map_err(|err| tracing::warn!("CSS generation failed, not rewriting its URLs: {err}"))
+        .ok()?;
+
+    let mut urls = vec![];
+    let mut imports = vec![];
+    for dependency in result.dependencies.unwrap_or_default() {
+        match dependency {
+            Dependency::Import(import) => imports.push((import.placeholder, import.url)),
+            Dependency::Url(url) => urls.push((url.placeholder, url.url)),
+        }
+    }
+    Some(Analyzed {
+        code: result.code,
+        urls,
+        imports,
+    })
+}
+
+/// The path of a URL referencing a local file relative to the stylesheet, and the query and
+/// fragment following it.
+fn local_path(url: &str) -> Option<(&str, &str)> {
+    if url.is_empty() || url.starts_with(['/', '#']) || url.contains(':') {
+        return None;
+    }
+    let (path, suffix) = url.split_at(url.find(['?', '#']).unwrap_or(url.len()));
+    (!path.is_empty()).then_some((path, suffix))
+}
+
+/// Copy a local file referenced by a stylesheet, or inline it if allowed, returning its new URL.
+///
+/// Returns `None` if the file doesn't exist.
+async fn rewrite_url(
+    cfg: &RtcBuild,
+    source_dir: &Path,
+    path: &str,
+    result_dir: &Path,
+    inline: bool,
+    may_inline: bool,
+    minify: bool,
+) -> Result<Option<String>> {
+    let mut file = PathBuf::new();
+    file.extend(path.split('/'));
+    let Ok(asset) = AssetFile::new(source_dir, file).await else {
+        tracing::warn!(
+            "file {path:?} referenced by {} not found, not rewriting its URL",
+            crate::common::strip_prefix(&source_dir.join(path)).display()
+        );
+        return Ok(None);
+    };
+    let file_type = match ImageType::from_path(&asset.path) {
+        ImageType::Other => AssetFileType::Other,
+        image_type => AssetFileType::Image(image_type),
+    };
+
+    let size = tokio::fs::metadata(&asset.path).await?.len();
+    if may_inline && size < cfg.css_inline_limit {
+        let bytes = tokio::fs::read(&asset.path)
+            .await
+            .with_context(|| format!("error reading file {:?}", &asset.path))?;
+        let bytes = match (minify, file_type) {
+            (true, AssetFileType::Image(image_type)) => {
+                crate::processing::image::optimize(bytes, image_type)?
+            }
+            _ => bytes,
+        };
+        let mime_type = mime_guess::from_path(&asset.path).first_or_octet_stream();
+        return Ok(Some(format!(
+            "data:{};base64,{}",
+            mime_type.essence_str(),
+            STANDARD.encode(bytes)
+        )));
+    }
+
+    let file = asset
+        .copy(
+            &cfg.staging_dist,
+            result_dir,
+            cfg.filehash,
+            minify,
+            file_type,
+        )
+        .await?;
+    Ok(Some(match inline {
+        true => format!("{}{file}", cfg.public_url),
+        false => asset_file_name(&file),
+    }))
+}
+
+/// The name of a file copied to the result dir, relative to it.
+fn asset_file_name(file: &str) -> String {
+    Path::new(file)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| file.to_string())
+}
+
+/// Serialize a CSS string.
+fn css_string(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\a ");
+    format!("\"{escaped}\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_local_paths() {
+        assert_eq!(local_path("img/bg.png"), Some(("img/bg.png", "")));
+        assert_eq!(
+            local_path("../fonts/icons.woff2?v=3#iefix"),
+            Some(("../fonts/icons.woff2", "?v=3#iefix"))
+        );
+        assert_eq!(local_path("/img/bg.png"), None);
+        assert_eq!(local_path("#gradient"), None);
+        assert_eq!(local_path("data:image/png;base64,AAAA"), None);
+        assert_eq!(local_path("https://example.com/bg.png"), None);
+    }
+
+    #[tokio::test]
+    async fn rewrites_urls() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let root = tmp.path().canonicalize()?;
+        std::fs::create_dir_all(root.join("styles"))?;
+        std::fs::create_dir_all(root.join("fonts"))?;
+        std::fs::create_dir_all(root.join("dist/css"))?;
+        std::fs::write(root.join("fonts/icons.woff2"), "font".repeat(8))?;
+        std::fs::write(root.join("styles/dot.svg"), "<svg/>")?;
+        // imported stylesheets are not assets of their own, even if small enough to be inlined
+        std::fs::write(root.join("styles/base.css"), "a{}")?;
+
+        let mut cfg = RtcBuild::new_test(&root).await?;
+        cfg.staging_dist = root.join("dist");
+        cfg.filehash = false;
+        cfg.css_inline_limit = 16;
+
+        let css = r#"@import "base.css";
+@font-face { font-family: Icons; src: url(../fonts/icons.woff2?v=3) format("woff2") }
+.dot { background: url('dot.svg'), url(/logo.png), url(missing.png) }"#;
+        let source = root.join("styles/main.css");
+        let result_dir = root.join("dist/css");
+
+        let rewritten =
+            rewrite_css_urls(&cfg, css.to_string(), &source, &result_dir, false, true).await?;
+        assert_eq!(
+            rewritten,
+            r#"@import "base.css";@font-face{font-family:Icons;src:url("icons.woff2?v=3")format("woff2")}.dot{background:url("data:image/svg+xml;base64,PHN2Zy8+"),url("/logo.png"),url("missing.png")}"#
+        );
+        assert!(root.join("dist/css/icons.woff2").exists());
+        assert!(!root.join("dist/css/base.css").exists());
+
+        let inlined =
+            rewrite_css_urls(&cfg, css.to_string(), &source, &result_dir, true, true).await?;
+        assert!(inlined.contains(r#"url("/css/icons.woff2?v=3")"#));
+
+        let css = "@import 'base.css';\n.logo { background: url(/logo.png) }";
+        let untouched =
+            rewrite_css_urls(&cfg, css.to_string(), &source, &result_dir, false, true).await?;
+        assert_eq!(untouched, css);
+        Ok(())
+    }
+}
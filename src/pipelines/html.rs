@@ -2,20 +2,31 @@
 
 use crate::{
     common::{
+        dist_relative,
         html_rewrite::{Document, DocumentOptions},
         nonce_attr,
     },
-    config::{rt::RtcBuild, types::WsProtocol},
-    hooks::{spawn_hooks, wait_hooks},
+    config::{
+        rt::{RtcBuild, RtcPage},
+        types::WsProtocol,
+    },
     pipelines::{
-        purescript::PureScriptApp, Attrs, PipelineStage, PrankAsset, PrankAssetPipelineOutput,
-        PrankAssetReference, PRANK_ID,
+        include,
+        manifest::{self, AssetElement, ManifestFile, OutputFile},
+        purescript::{BundleReport, DevSession, Diagnostic, PureScriptApp, PureScriptAppType},
+        shared::{asset_key, SharedAssets},
+        Attrs, PrankAsset, PrankAssetPipelineOutput, PrankAssetReference, ATTR_HREF, ATTR_REL,
+        ATTR_SRC, PRANK_ID,
     },
     processing::minify::minify_html,
 };
-use anyhow::{ensure, Context, Result};
+use anyhow::{Context, Result};
 use futures_util::stream::{FuturesUnordered, StreamExt};
-use std::{path::PathBuf, sync::Arc};
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::PathBuf,
+    sync::Arc,
+};
 use tokio::{
     fs,
     sync::mpsc,
@@ -25,21 +36,47 @@ use tokio::{
 const PUBLIC_URL_MARKER_ATTR: &str = "data-prank-public-url";
 const RELOAD_SCRIPT: &str = include_str!("../autoreload.js");
 
+/// The key of the default PureScript application, of a single page build.
+const DEFAULT_APP_KEY: &str = "purescript default";
+/// The suffix of the keys of PureScript applications sharing their modules with the other
+/// applications of their page.
+const SHARED_MODULES_SUFFIX: &str = " shared-modules";
+
 type AssetPipelineHandles = FuturesUnordered<JoinHandle<Result<PrankAssetPipelineOutput>>>;
 
+/// The outcome of the build of a page.
+#[derive(Clone, Debug, Default)]
+pub struct PageOutput {
+    /// The PureScript modules which can be hot replaced by each application of the page, `None`
+    /// if the page must be reloaded.
+    pub hot_modules: Vec<Option<BTreeSet<String>>>,
+    /// The warnings of the PureScript compiler.
+    pub warnings: Vec<Diagnostic>,
+    /// The files of the main chunk of the main application of the page, relative to the dist dir.
+    pub main_bundle: Option<Vec<String>>,
+    /// The bundle reports of the PureScript applications of the page.
+    pub reports: Vec<BundleReport>,
+    /// The output files of the assets of the page, for the asset manifest.
+    pub files: Vec<ManifestFile>,
+}
+
 /// An HTML assets build pipeline.
 ///
-/// This build pipeline is responsible for processing the source HTML of the application, as well
-/// as spawning child pipelines for any assets found in the source HTML.
+/// This build pipeline is responsible for processing the source HTML of a page, as well as
+/// spawning child pipelines for any assets found in the source HTML.
 pub struct HtmlPipeline {
     /// Runtime config.
     cfg: Arc<RtcBuild>,
-    /// The path to the source HTML document from which the output `index.html` will be built.
+    /// The path to the source HTML document from which the output HTML file will be built.
     target_html_path: PathBuf,
     /// The parent directory of `target_html_path`.
     target_html_dir: Arc<PathBuf>,
+    /// The path of the output HTML file, relative to the dist dir.
+    output: String,
     /// An optional channel to be used to communicate ignore paths to the watcher.
     ignore_chan: Option<mpsc::Sender<Vec<PathBuf>>>,
+    /// The helpers kept between the builds of PureScript applications in watch mode.
+    session: DevSession,
     /// Protocol used for autoreload WebSockets connection.
     ws_protocol: Option<WsProtocol>,
 }
@@ -48,10 +85,12 @@ impl HtmlPipeline {
     /// Create a new instance.
     pub fn new(
         cfg: Arc<RtcBuild>,
+        page: &RtcPage,
         ignore_chan: Option<mpsc::Sender<Vec<PathBuf>>>,
+        session: DevSession,
         ws_protocol: Option<WsProtocol>,
     ) -> Result<Self> {
-        let target_html_path = cfg
+        let target_html_path = page
             .target
             .canonicalize()
             .context("failed to get canonical path of target HTML file")?;
@@ -66,27 +105,34 @@ impl HtmlPipeline {
             cfg,
             target_html_path,
             target_html_dir,
+            output: page.output.clone(),
             ignore_chan,
+            session,
             ws_protocol,
         })
     }
 
-    /// Spawn a new pipeline.
-    #[tracing::instrument(level = "trace", skip(self))]
-    pub fn spawn(self: Arc<Self>, changed_paths: Vec<PathBuf>) -> JoinHandle<Result<()>> {
-        tokio::spawn(self.run(changed_paths))
+    /// Spawn a new pipeline, sharing the asset pipelines with the other pages of the build.
+    #[tracing::instrument(level = "trace", skip(self, shared))]
+    pub fn spawn(
+        self: Arc<Self>,
+        changed_paths: Vec<PathBuf>,
+        shared: SharedAssets,
+    ) -> JoinHandle<Result<PageOutput>> {
+        tokio::spawn(self.run(changed_paths, shared))
     }
 
     /// Run this pipeline.
-    #[tracing::instrument(level = "trace", skip(self))]
-    async fn run(self: Arc<Self>, changed_paths: Vec<PathBuf>) -> Result<()> {
-        tracing::debug!("spawning asset pipelines");
-
-        // Spawn and wait on pre-build hooks.
-        wait_hooks(spawn_hooks(self.cfg.clone(), PipelineStage::PreBuild)).await?;
-
-        // Open the source HTML file for processing.
-        let raw_html = fs::read(&self.target_html_path).await?;
+    #[tracing::instrument(level = "trace", skip(self, shared))]
+    async fn run(
+        self: Arc<Self>,
+        changed_paths: Vec<PathBuf>,
+        shared: SharedAssets,
+    ) -> Result<PageOutput> {
+        tracing::debug!(page = %self.output, "spawning asset pipelines");
+
+        // Open the source HTML file for processing, expanding its includes.
+        let raw_html = include::read_page(self.cfg.clone(), self.target_html_path.clone()).await?;
         let mut target_html = Document::new(
             raw_html,
             DocumentOptions {
@@ -94,6 +140,11 @@ impl HtmlPipeline {
             },
         )?;
         let mut partial_assets = vec![];
+        // The keys identifying the assets between the pages.
+        let mut keys = vec![];
+        // The elements of the assets, by their ID, for the asset manifest.
+        let mut elements = HashMap::new();
+        let page_dir = dist_relative(&self.cfg.target_parent, &self.target_html_dir)?;
 
         // Since the `lol_html` doesn't provide an iterator for elements, we must use our own id.
         let mut id = 0;
@@ -124,10 +175,24 @@ impl HtmlPipeline {
                     acc
                 });
 
+                let (source, r#type) = match el.tag_name().as_str() {
+                    "link" => (ATTR_HREF, attrs.get(ATTR_REL).map(|rel| rel.value.clone())),
+                    _ => (ATTR_SRC, Some("js".to_string())),
+                };
+                if let (Some(source), Some(r#type)) = (attrs.get(source), r#type) {
+                    let source = manifest::asset_source(&page_dir, &source.value);
+                    elements.insert(id, AssetElement { source, r#type });
+                }
+                keys.push((
+                    asset_key(&el.tag_name(), &attrs, &self.target_html_dir),
+                    Some(id),
+                ));
+
                 let asset = PrankAsset::from_html(
                     self.cfg.clone(),
                     self.target_html_dir.clone(),
                     self.ignore_chan.clone(),
+                    self.session.clone(),
                     asset_constructor(attrs),
                     id,
                     changed_paths.clone(),
@@ -147,37 +212,46 @@ impl HtmlPipeline {
         // Ensure we have a PureScript app pipeline to spawn.
         let purescript_app_nodes = target_html
             .len(r#"link[data-prank][rel="purescript"][data-type="main"], link[data-prank][rel="purescript"]:not([data-type])"#)?;
-        ensure!(
-            purescript_app_nodes <= 1,
-            r#"only one <link data-prank rel="purescript" data-type="main" .../> may be specified"#
-        );
-        if purescript_app_nodes == 0 {
+        if purescript_app_nodes > 1 {
+            // Several applications on the same page share their common code.
+            for (asset, (key, _)) in assets.iter_mut().zip(&mut keys) {
+                if let PrankAsset::PureScriptApp(app) = asset {
+                    if app.is_main() {
+                        app.share_modules();
+                        key.push_str(SHARED_MODULES_SUFFIX);
+                    }
+                }
+            }
+        } else if purescript_app_nodes == 0 && self.cfg.pages.len() == 1 {
             if let Some(app) = PureScriptApp::new_default(
                 self.cfg.clone(),
                 self.target_html_dir.clone(),
                 self.ignore_chan.clone(),
+                self.session.clone(),
             )
             .await?
             {
                 assets.push(PrankAsset::PureScriptApp(app));
+                keys.push((DEFAULT_APP_KEY.to_string(), None));
             } else {
                 tracing::warn!("no PureScript project found")
             };
         }
 
-        // Spawn all asset pipelines.
+        // Spawn all asset pipelines, or share the ones spawned by other pages.
         let mut pipelines: AssetPipelineHandles = FuturesUnordered::new();
-        pipelines.extend(assets.into_iter().map(PrankAsset::spawn));
-        // Spawn all build hooks.
-        let build_hooks = spawn_hooks(self.cfg.clone(), PipelineStage::Build);
+        pipelines.extend(
+            assets
+                .into_iter()
+                .zip(keys)
+                .map(|(asset, (key, id))| shared.spawn(key, id, asset)),
+        );
 
         // Finalize asset pipelines.
-        self.finalize_asset_pipelines(&mut target_html, pipelines)
+        let output = self
+            .finalize_asset_pipelines(&mut target_html, pipelines, &elements)
             .await?;
 
-        // Wait for all build hooks to finish.
-        wait_hooks(build_hooks).await?;
-
         // Finalize HTML.
         self.finalize_html(&mut target_html)?;
 
@@ -187,17 +261,17 @@ impl HtmlPipeline {
             false => target_html.into_inner(),
         };
 
-        fs::write(
-            self.cfg.staging_dist.join(&self.cfg.html_output_filename),
-            &output_html,
-        )
-        .await
-        .context("error writing finalized HTML output")?;
-
-        // Spawn and wait on post-build hooks.
-        wait_hooks(spawn_hooks(self.cfg.clone(), PipelineStage::PostBuild)).await?;
+        let output_path = self.cfg.staging_dist.join(&self.output);
+        if let Some(parent) = output_path.parent() {
+            fs::create_dir_all(parent)
+                .await
+                .with_context(|| format!("error creating directory {}", parent.display()))?;
+        }
+        fs::write(&output_path, &output_html)
+            .await
+            .context("error writing finalized HTML output")?;
 
-        Ok(())
+        Ok(output)
     }
 
     /// Finalize asset pipelines & prep the DOM for final output.
@@ -205,18 +279,45 @@ impl HtmlPipeline {
         &self,
         target_html: &mut Document,
         mut pipelines: AssetPipelineHandles,
-    ) -> Result<()> {
+        elements: &HashMap<usize, AssetElement>,
+    ) -> Result<PageOutput> {
         let mut errors = Vec::new();
+        let mut hot_modules = Vec::new();
+        let mut warnings = Vec::new();
+        let mut reports = Vec::new();
+        let mut main_bundle = None;
+        let mut files = Vec::new();
 
         /// finalize an asset pipeline with a single result
         async fn finalize(
             asset_res: std::result::Result<Result<PrankAssetPipelineOutput>, JoinError>,
             target_html: &mut Document,
+            hot_modules: &mut Vec<Option<BTreeSet<String>>>,
+            warnings: &mut Vec<Diagnostic>,
+            reports: &mut Vec<BundleReport>,
+            main_bundle: &mut Option<Vec<String>>,
+            files: &mut Vec<OutputFile>,
         ) -> Result<()> {
             let asset = asset_res
                 .context("failed to await asset pipeline")?
                 .context("error from asset pipeline")?;
 
+            if let PrankAssetPipelineOutput::PureScriptApp(app) = &asset {
+                hot_modules.push(app.hot_modules.clone());
+                // applications of the same workspace might report the same warnings
+                for warning in &app.warnings {
+                    if !warnings.contains(warning) {
+                        warnings.push(warning.clone());
+                    }
+                }
+                reports.extend(app.report.clone());
+                if app.r#type == PureScriptAppType::Main {
+                    *main_bundle = Some(app.main_chunk());
+                }
+            }
+
+            files.extend(asset.output_file());
+
             asset
                 .finalize(target_html)
                 .await
@@ -227,7 +328,17 @@ impl HtmlPipeline {
 
         // pull all results and store their errors
         while let Some(asset_res) = pipelines.next().await {
-            if let Err(err) = finalize(asset_res, target_html).await {
+            if let Err(err) = finalize(
+                asset_res,
+                target_html,
+                &mut hot_modules,
+                &mut warnings,
+                &mut reports,
+                &mut main_bundle,
+                &mut files,
+            )
+            .await
+            {
                 // store the error, but don't return, so that we can still await all others
                 errors.push(err);
             }
@@ -244,8 +355,13 @@ impl HtmlPipeline {
 
         // return only once all pipeline steps have completed, so that we don't start a new build
         // while previous pipelines are still running
-
-        Ok(())
+        Ok(PageOutput {
+            hot_modules,
+            warnings,
+            main_bundle,
+            reports,
+            files: manifest::page_files(elements, files),
+        })
     }
 
     /// Prepare the document for final output.
@@ -7,8 +7,11 @@ use super::{
 use crate::{
     common::{html_rewrite::Document, nonce_attr, target_path},
     config::rt::RtcBuild,
-    pipelines::{AssetFileType, ImageType},
-    processing::integrity::{IntegrityType, OutputDigest},
+    pipelines::AssetFileType,
+    processing::{
+        image::ImageType,
+        integrity::{IntegrityType, OutputDigest},
+    },
 };
 use anyhow::{Context, Result};
 use std::{collections::HashMap, path::PathBuf, sync::Arc};
@@ -72,11 +75,7 @@ impl Icon {
     async fn run(self) -> Result<PrankAssetPipelineOutput> {
         let rel_path = crate::common::strip_prefix(&self.asset.path);
         tracing::debug!(path = ?rel_path, "copying & hashing icon");
-        let mime_type = mime_guess::from_path(&self.asset.path).first_or_octet_stream();
-        let image_type = match mime_type.type_().as_str() {
-            "image/png" => ImageType::Png,
-            _ => ImageType::Other,
-        };
+        let image_type = ImageType::from_path(&self.asset.path);
 
         let result_dir =
             target_path(&self.cfg.staging_dist, self.target_path.as_deref(), None).await?;
@@ -88,7 +87,7 @@ impl Icon {
                 &result_dir,
                 self.cfg.filehash,
                 self.cfg.minify_asset(self.no_minify),
-                AssetFileType::Icon(image_type),
+                AssetFileType::Image(image_type),
             )
             .await?;
 
@@ -96,7 +95,7 @@ impl Icon {
         let integrity = OutputDigest::generate(self.integrity, || std::fs::read(&result_file))
             .with_context(|| {
                 format!(
-                    "Failed to generate digest for CSS file '{}'",
+                    "Failed to generate digest for icon file '{}'",
                     result_file.display()
                 )
             })?;
@@ -112,6 +111,7 @@ impl Icon {
 }
 
 /// The output of an Icon build pipeline.
+#[derive(Clone)]
 pub struct IconOutput {
     /// The runtime build config.
     pub cfg: Arc<RtcBuild>,
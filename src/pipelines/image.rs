@@ -0,0 +1,479 @@
+//! Image asset pipeline.
+
+use super::{
+    data_target_path, prank_id_selector, AssetFile, Attr, AttrWriter, Attrs,
+    PrankAssetPipelineOutput, ATTR_HREF, ATTR_NO_MINIFY, ATTR_REL, ATTR_SRC, ATTR_TARGET_PATH,
+    ATTR_TYPE, PRANK_ID,
+};
+use crate::{
+    common::{html_rewrite::Document, target_path},
+    config::rt::RtcBuild,
+    processing::{
+        image::{self, ImageType},
+        integrity::{IntegrityType, OutputDigest},
+    },
+};
+use anyhow::{bail, Context, Result};
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::task::JoinHandle;
+
+const ATTR_WIDTHS: &str = "data-widths";
+/// The unprefixed form of `data-widths`, accepted as well.
+const ATTR_WIDTHS_SHORT: &str = "widths";
+const ATTR_FORMATS: &str = "data-formats";
+const ATTR_QUALITY: &str = "data-quality";
+const ATTR_INTEGRITY: &str = "data-integrity";
+const ATTR_SIZES: &str = "sizes";
+
+/// The attributes of the link which are not copied to the image.
+const EXCLUDE_IMAGE: &[&str] = &[
+    PRANK_ID,
+    ATTR_HREF,
+    ATTR_REL,
+    ATTR_SRC,
+    ATTR_TYPE,
+    ATTR_NO_MINIFY,
+    ATTR_TARGET_PATH,
+    ATTR_WIDTHS,
+    ATTR_WIDTHS_SHORT,
+    ATTR_FORMATS,
+    ATTR_QUALITY,
+    ATTR_INTEGRITY,
+];
+
+/// An Image asset pipeline.
+pub struct Image {
+    /// The ID of this pipeline's source HTML element.
+    id: usize,
+    /// Runtime build config.
+    cfg: Arc<RtcBuild>,
+    /// The asset file being processed.
+    asset: AssetFile,
+    /// The type of the image.
+    image_type: ImageType,
+    /// The widths of the responsive variants, in ascending order.
+    widths: Vec<u32>,
+    /// The additional formats to generate.
+    formats: Vec<ImageType>,
+    /// The quality to encode the images with.
+    quality: Option<u8>,
+    /// The required integrity setting
+    integrity: IntegrityType,
+    /// Whether to minify or not
+    no_minify: bool,
+    /// Optional target path inside the dist dir.
+    target_path: Option<PathBuf>,
+    /// The attributes of the source element, copied to the image.
+    attrs: Attrs,
+}
+
+impl Image {
+    pub const TYPE_IMAGE: &'static str = "image";
+
+    pub async fn new(
+        cfg: Arc<RtcBuild>,
+        html_dir: Arc<PathBuf>,
+        attrs: Attrs,
+        id: usize,
+    ) -> Result<Self> {
+        // Build the path to the target asset.
+        let href_attr = attrs.get(ATTR_HREF).context(
+            r#"required attr `href` missing for <link data-prank rel="image" .../> element"#,
+        )?;
+        let mut path = PathBuf::new();
+        path.extend(href_attr.split('/'));
+        let asset = AssetFile::new(&html_dir, path).await?;
+        let image_type = ImageType::from_path(&asset.path);
+
+        let widths = attrs
+            .get(ATTR_WIDTHS)
+            .or_else(|| attrs.get(ATTR_WIDTHS_SHORT))
+            .map(|attr| parse_widths(attr))
+            .transpose()?
+            .unwrap_or_default();
+        let formats = attrs
+            .get(ATTR_FORMATS)
+            .map(|attr| parse_formats(attr, image_type))
+            .transpose()?
+            .unwrap_or_default();
+        let quality = attrs
+            .get(ATTR_QUALITY)
+            .map(|attr| parse_quality(attr))
+            .transpose()?;
+
+        let integrity = IntegrityType::from_attrs(&attrs, &cfg)?;
+        let no_minify = attrs.contains_key(ATTR_NO_MINIFY);
+        let target_path = data_target_path(&attrs)?;
+
+        Ok(Self {
+            id,
+            cfg,
+            asset,
+            image_type,
+            widths,
+            formats,
+            quality,
+            integrity,
+            no_minify,
+            target_path,
+            attrs,
+        })
+    }
+
+    /// Spawn the pipeline for this asset type.
+    #[tracing::instrument(level = "trace", skip(self))]
+    pub fn spawn(self) -> JoinHandle<Result<PrankAssetPipelineOutput>> {
+        tokio::spawn(self.run())
+    }
+
+    /// Run this pipeline.
+    #[tracing::instrument(level = "trace", skip(self))]
+    async fn run(mut self) -> Result<PrankAssetPipelineOutput> {
+        let rel_path = crate::common::strip_prefix(&self.asset.path);
+        tracing::debug!(path = ?rel_path, "optimizing image");
+
+        let encodes = !self.widths.is_empty() || !self.formats.is_empty() || self.quality.is_some();
+        if encodes && !self.image_type.is_raster() {
+            tracing::warn!(
+                path = ?rel_path,
+                "only raster images can be resized or converted, ignoring the widths, formats and \
+                 quality of the image"
+            );
+            self.widths.clear();
+            self.formats.clear();
+            self.quality = None;
+        }
+        let magick = match encodes && self.image_type.is_raster() {
+            true => Some(image::magick()?),
+            false => None,
+        };
+
+        let source = tokio::fs::read(&self.asset.path)
+            .await
+            .with_context(|| format!("error reading image {:?}", &self.asset.path))?;
+        // the widths of the variants, `None` for the original width
+        let widths = match self.widths.is_empty() {
+            true => vec![None],
+            false => {
+                let width = image::width(&source, self.image_type).with_context(|| {
+                    format!(
+                        "error reading the width of image {:?} to resize it",
+                        &self.asset.path
+                    )
+                })?;
+                variant_widths(&self.widths, width)
+                    .into_iter()
+                    .map(Some)
+                    .collect()
+            }
+        };
+
+        let result_dir =
+            target_path(&self.cfg.staging_dist, self.target_path.as_deref(), None).await?;
+        let mut variants = Vec::new();
+        for format in std::iter::once(self.image_type).chain(self.formats.iter().copied()) {
+            let mut candidates = Vec::new();
+            for &width in &widths {
+                let file = self
+                    .variant(&source, magick.as_deref(), width, format, &result_dir)
+                    .await?;
+                candidates.push(Candidate { file, width });
+            }
+            variants.push((format, candidates));
+        }
+
+        // the original format, at the largest width, is the fallback image
+        let (_, candidates) = variants.remove(0);
+        let file = candidates
+            .last()
+            .map(|candidate| candidate.file.clone())
+            .unwrap_or_default();
+        let result_file = self.cfg.staging_dist.join(&file);
+        let integrity = OutputDigest::generate(self.integrity, || std::fs::read(&result_file))
+            .with_context(|| {
+                format!(
+                    "Failed to generate digest for image file '{}'",
+                    result_file.display()
+                )
+            })?;
+
+        tracing::debug!(path = ?rel_path, "finished optimizing image");
+        Ok(PrankAssetPipelineOutput::Image(ImageOutput {
+            cfg: self.cfg.clone(),
+            id: self.id,
+            file,
+            integrity,
+            srcset: candidates
+                .into_iter()
+                .filter(|candidate| candidate.width.is_some())
+                .collect(),
+            sources: variants
+                .into_iter()
+                .map(|(format, candidates)| ImageSource {
+                    mime_type: format.mime_type(),
+                    candidates,
+                })
+                .collect(),
+            attrs: self.attrs,
+        }))
+    }
+
+    /// Write a variant of the image to the result dir, returning its file name.
+    async fn variant(
+        &self,
+        source: &[u8],
+        magick: Option<&Path>,
+        width: Option<u32>,
+        format: ImageType,
+        result_dir: &Path,
+    ) -> Result<String> {
+        let original = width.is_none() && format == self.image_type && self.quality.is_none();
+        let bytes = match magick {
+            Some(magick) if !original => {
+                image::encode(
+                    magick,
+                    &self.asset.path,
+                    self.image_type,
+                    width,
+                    format,
+                    self.quality,
+                )
+                .await?
+            }
+            _ => source.to_vec(),
+        };
+        let bytes = match self.cfg.minify_asset(self.no_minify) {
+            true => image::optimize(bytes, format)
+                .with_context(|| format!("error optimizing image {:?}", &self.asset.path))?,
+            false => bytes,
+        };
+
+        let suffix = width.map(|width| format!("-{width}w")).unwrap_or_default();
+        let ext = match (format == self.image_type, self.asset.ext.as_deref()) {
+            (true, Some(ext)) => ext,
+            _ => format.extension(),
+        };
+        self.asset
+            .write_variant(
+                &self.cfg.staging_dist,
+                result_dir,
+                self.cfg.filehash,
+                &suffix,
+                ext,
+                bytes,
+            )
+            .await
+    }
+}
+
+/// Parse the widths of the responsive variants, e.g. `320,640,1280`.
+fn parse_widths(value: &str) -> Result<Vec<u32>> {
+    let mut widths = value
+        .split(',')
+        .map(str::trim)
+        .filter(|width| !width.is_empty())
+        .map(|width| match width.parse::<u32>() {
+            Ok(width) if width > 0 => Ok(width),
+            _ => bail!("invalid image width `{width}`, expected a number of pixels"),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    widths.sort_unstable();
+    widths.dedup();
+    Ok(widths)
+}
+
+/// Parse the additional formats of an image, e.g. `avif,webp`, skipping its own format.
+fn parse_formats(value: &str, image_type: ImageType) -> Result<Vec<ImageType>> {
+    let mut formats = Vec::new();
+    for format in value.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+        let format = ImageType::from_format(&format.to_lowercase()).with_context(|| {
+            format!("unsupported image format `{format}`, expected avif, webp, jpeg, png or gif")
+        })?;
+        if format != image_type && !formats.contains(&format) {
+            formats.push(format);
+        }
+    }
+    Ok(formats)
+}
+
+/// Parse the quality of the images, from 1 to 100.
+fn parse_quality(value: &str) -> Result<u8> {
+    match value.trim().parse::<u8>() {
+        Ok(quality) if (1..=100).contains(&quality) => Ok(quality),
+        _ => bail!("invalid image quality `{value}`, expected a number from 1 to 100"),
+    }
+}
+
+/// The widths of the responsive variants of an image of the given width.
+///
+/// Images are never upscaled: the widths larger than the image are replaced by its own width.
+fn variant_widths(widths: &[u32], width: u32) -> Vec<u32> {
+    let mut variants = widths
+        .iter()
+        .map(|&variant| variant.min(width))
+        .collect::<Vec<_>>();
+    variants.dedup();
+    variants
+}
+
+/// An image file, with its width for a `srcset` attribute.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Candidate {
+    /// The name of the file.
+    pub file: String,
+    /// The width of the image, if it has responsive variants.
+    pub width: Option<u32>,
+}
+
+/// The variants of an image in another format, for a `<source>` of a `<picture>`.
+#[derive(Clone, Debug)]
+pub struct ImageSource {
+    /// The MIME type of the format.
+    pub mime_type: &'static str,
+    /// The image files.
+    pub candidates: Vec<Candidate>,
+}
+
+/// The output of an Image build pipeline.
+#[derive(Clone)]
+pub struct ImageOutput {
+    /// The runtime build config.
+    pub cfg: Arc<RtcBuild>,
+    /// The ID of this pipeline.
+    pub id: usize,
+    /// Name of the finalized fallback image file.
+    pub file: String,
+    /// The digest for the integrity attribute
+    pub integrity: OutputDigest,
+    /// The responsive variants of the image in its own format.
+    pub srcset: Vec<Candidate>,
+    /// The variants of the image in other formats.
+    pub sources: Vec<ImageSource>,
+    /// The attributes of the source element.
+    pub attrs: Attrs,
+}
+
+impl ImageOutput {
+    pub async fn finalize(self, dom: &mut Document) -> Result<()> {
+        let base = self.cfg.public_url.to_string();
+        let mut attrs = self.attrs.clone();
+        if self.srcset.is_empty() && self.sources.is_empty() {
+            // the integrity of an image only holds for a single file
+            self.integrity.insert_into(&mut attrs);
+        } else if !self.srcset.is_empty() {
+            attrs.insert("srcset".to_string(), srcset(&base, &self.srcset));
+        }
+
+        let img = format!(
+            r#"<img src="{base}{file}"{attrs}>"#,
+            file = self.file,
+            attrs = AttrWriter::new(&attrs, EXCLUDE_IMAGE),
+        );
+        let html = match self.sources.is_empty() {
+            true => img,
+            false => {
+                let mut html = "<picture>".to_string();
+                for source in &self.sources {
+                    let mut attrs = Attrs::new();
+                    attrs.insert(
+                        "type".to_string(),
+                        Attr {
+                            value: source.mime_type.to_string(),
+                            need_escape: false,
+                        },
+                    );
+                    attrs.insert("srcset".to_string(), srcset(&base, &source.candidates));
+                    if let Some(sizes) = self.attrs.get(ATTR_SIZES) {
+                        attrs.insert(ATTR_SIZES.to_string(), sizes.clone());
+                    }
+                    let _ = write!(html, "<source{}>", AttrWriter::new(&attrs, &[]));
+                }
+                html.push_str(&img);
+                html.push_str("</picture>");
+                html
+            }
+        };
+
+        dom.replace_with_html(&prank_id_selector(self.id), &html)?;
+        Ok(())
+    }
+}
+
+/// The `srcset` attribute of the given image files.
+fn srcset(base: &str, candidates: &[Candidate]) -> Attr {
+    let value = candidates
+        .iter()
+        .map(|candidate| match candidate.width {
+            Some(width) => format!("{base}{} {width}w", candidate.file),
+            None => format!("{base}{}", candidate.file),
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    Attr {
+        value: htmlescape::encode_minimal(&value),
+        need_escape: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_attrs() -> Result<()> {
+        assert_eq!(parse_widths("1280, 320,640,320")?, vec![320, 640, 1280]);
+        assert!(parse_widths("320,wide").is_err());
+        assert!(parse_widths("0").is_err());
+        assert_eq!(
+            parse_formats("avif,WebP,jpeg", ImageType::Jpeg)?,
+            vec![ImageType::Avif, ImageType::Webp]
+        );
+        assert!(parse_formats("bmp", ImageType::Jpeg).is_err());
+        assert_eq!(parse_quality("80")?, 80);
+        assert!(parse_quality("0").is_err());
+        assert!(parse_quality("101").is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn accepts_unprefixed_widths() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        std::fs::write(tmp.path().join("photo.jpg"), [0xFF, 0xD8, 0xFF, 0xD9])?;
+        let cfg = Arc::new(RtcBuild::new_test(tmp.path()).await?);
+        let mut attrs = Attrs::new();
+        attrs.insert(ATTR_HREF.into(), "photo.jpg".into());
+        attrs.insert(ATTR_WIDTHS_SHORT.into(), "640,320".into());
+
+        let image = Image::new(cfg, Arc::new(tmp.path().to_path_buf()), attrs, 0).await?;
+        assert_eq!(image.widths, vec![320, 640]);
+        Ok(())
+    }
+
+    #[test]
+    fn never_upscales() {
+        assert_eq!(variant_widths(&[320, 640, 1280], 800), vec![320, 640, 800]);
+        assert_eq!(variant_widths(&[1280, 1920], 800), vec![800]);
+    }
+
+    #[test]
+    fn writes_srcset() {
+        let candidates = [
+            Candidate {
+                file: "photo-320w.jpg".to_string(),
+                width: Some(320),
+            },
+            Candidate {
+                file: "photo-640w.jpg".to_string(),
+                width: Some(640),
+            },
+        ];
+        assert_eq!(
+            srcset("/", &candidates).value,
+            "/photo-320w.jpg 320w, /photo-640w.jpg 640w"
+        );
+    }
+}
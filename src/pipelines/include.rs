@@ -0,0 +1,268 @@
+//! Includes of HTML partials, and the templates of the pages.
+
+use super::{purescript::EnvModule, ATTR_HREF, ATTR_SRC};
+use crate::{
+    common::html_rewrite::{Document, DocumentOptions},
+    config::rt::RtcBuild,
+    processing::template::Template,
+};
+use anyhow::{bail, Context, Result};
+use serde_json::{Map, Value};
+use std::{
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+
+/// The selector of the links including a partial.
+const INCLUDE_SELECTOR: &str = r#"link[data-prank][rel="include"]"#;
+/// The attribute identifying the links including a partial, while expanding them.
+const ATTR_INCLUDE_ID: &str = "data-prank-include-id";
+/// The selector of the elements of assets, whose paths are relative to their HTML file.
+const ASSET_SELECTOR: &str = "link[data-prank][href], script[data-prank][src]";
+
+/// Read the source HTML of a page, expanding its includes and rendering it as a template, if
+/// enabled.
+pub async fn read_page(cfg: Arc<RtcBuild>, path: PathBuf) -> Result<Vec<u8>> {
+    tokio::task::spawn_blocking(move || {
+        let html = std::fs::read(&path)
+            .with_context(|| format!("error reading the HTML file {}", path.display()))?;
+        let options = DocumentOptions {
+            allow_self_closing_script: cfg.allow_self_closing_script,
+        };
+        let html = expand_includes(html, &path, &options, &mut vec![])?;
+        match cfg.template {
+            true => render_page(&cfg, html, &path),
+            false => Ok(html),
+        }
+    })
+    .await
+    .context("error awaiting the source HTML")?
+}
+
+/// The partials included by an HTML file, recursively, as canonical paths.
+///
+/// Files which can't be read or parsed are skipped, building the page reports their errors.
+pub fn partials(file: &Path) -> Vec<PathBuf> {
+    let mut partials = Vec::new();
+    let mut queue = vec![file.to_path_buf()];
+    while let Some(file) = queue.pop() {
+        let (Some(dir), Ok(html)) = (file.parent(), std::fs::read(&file)) else {
+            continue;
+        };
+        let Ok(document) = Document::new(html, DocumentOptions::default()) else {
+            continue;
+        };
+        let mut hrefs = Vec::new();
+        let _ = document.select(INCLUDE_SELECTOR, |el| {
+            hrefs.extend(el.get_attribute(ATTR_HREF));
+            Ok(())
+        });
+        for href in hrefs {
+            let mut path = dir.to_path_buf();
+            path.extend(href.split('/'));
+            if let Ok(path) = path.canonicalize() {
+                if !partials.contains(&path) {
+                    partials.push(path.clone());
+                    queue.push(path);
+                }
+            }
+        }
+    }
+    partials
+}
+
+/// Replace the links including a partial with the partial, recursively.
+///
+/// The paths of the assets of a partial are rebased onto the directory of the including file, so
+/// that they stay relative to the partial.
+fn expand_includes(
+    html: Vec<u8>,
+    file: &Path,
+    options: &DocumentOptions,
+    stack: &mut Vec<PathBuf>,
+) -> Result<Vec<u8>> {
+    let dir = file.parent().context("HTML file has no parent directory")?;
+    let mut document = Document::new(html, options.clone())?;
+    let mut hrefs = Vec::new();
+    document.select_mut(INCLUDE_SELECTOR, |el| {
+        let href = el.get_attribute(ATTR_HREF).context(
+            r#"required attr `href` missing for <link data-prank rel="include" .../> element"#,
+        )?;
+        el.set_attribute(ATTR_INCLUDE_ID, &hrefs.len().to_string())?;
+        hrefs.push(href);
+        Ok(())
+    })?;
+    if hrefs.is_empty() {
+        return Ok(document.into_inner());
+    }
+
+    stack.push(file.to_path_buf());
+    for (id, href) in hrefs.iter().enumerate() {
+        let mut path = dir.to_path_buf();
+        path.extend(href.split('/'));
+        let path = path
+            .canonicalize()
+            .with_context(|| format!("error getting the canonical path of the include {href:?}"))?;
+        if stack.contains(&path) {
+            bail!("the include {href:?} of {} includes itself", file.display());
+        }
+        let partial = std::fs::read(&path)
+            .with_context(|| format!("error reading the include {}", path.display()))?;
+        let partial = expand_includes(partial, &path, options, stack)
+            .with_context(|| format!("error expanding the include {href:?}"))?;
+        let partial_dir = path.parent().context("include has no parent directory")?;
+        let partial = rebase_assets(partial, partial_dir, dir, options)?;
+        document.replace_with_html(
+            &format!(r#"link[{ATTR_INCLUDE_ID}="{id}"]"#),
+            &String::from_utf8_lossy(&partial),
+        )?;
+    }
+    stack.pop();
+
+    Ok(document.into_inner())
+}
+
+/// Rebase the relative paths of the assets of a partial, from its directory onto another one.
+fn rebase_assets(
+    html: Vec<u8>,
+    from: &Path,
+    to: &Path,
+    options: &DocumentOptions,
+) -> Result<Vec<u8>> {
+    let prefix = relative_dir(to, from);
+    if prefix.is_empty() {
+        return Ok(html);
+    }
+    let mut document = Document::new(html, options.clone())?;
+    document.select_mut(ASSET_SELECTOR, |el| {
+        let attr = match el.tag_name().as_str() {
+            "link" => ATTR_HREF,
+            _ => ATTR_SRC,
+        };
+        if let Some(value) = el.get_attribute(attr) {
+            if is_relative_path(&value) {
+                el.set_attribute(attr, &format!("{prefix}{value}"))?;
+            }
+        }
+        Ok(())
+    })?;
+    Ok(document.into_inner())
+}
+
+/// The path of a directory relative to another one, with a trailing slash unless empty.
+pub(crate) fn relative_dir(from: &Path, to: &Path) -> String {
+    let from = from.components().collect::<Vec<_>>();
+    let to = to.components().collect::<Vec<_>>();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let mut path = String::new();
+    for _ in common..from.len() {
+        path.push_str("../");
+    }
+    for component in &to[common..] {
+        if let Component::Normal(name) = component {
+            path.push_str(&name.to_string_lossy());
+            path.push('/');
+        }
+    }
+    path
+}
+
+/// Check if the path of an asset is relative to its HTML file.
+fn is_relative_path(path: &str) -> bool {
+    !(path.is_empty() || path.starts_with('/') || path.contains(':'))
+}
+
+/// Render the source HTML of a page as a template.
+fn render_page(cfg: &RtcBuild, html: Vec<u8>, path: &Path) -> Result<Vec<u8>> {
+    let source = String::from_utf8(html)
+        .with_context(|| format!("the template {} is not valid UTF-8", path.display()))?;
+    let template = Template::parse(&source)
+        .with_context(|| format!("error parsing the template {}", path.display()))?;
+    let rendered = template
+        .render(&template_vars(cfg), &mut |file| {
+            load_data(&cfg.core.working_directory.join(file))
+        })
+        .with_context(|| format!("error rendering the template {}", path.display()))?;
+    Ok(rendered.into_bytes())
+}
+
+/// The variables of the templates: the configured ones, and the built-in ones.
+fn template_vars(cfg: &RtcBuild) -> Value {
+    let mut vars = cfg.template_vars.clone().into_iter().collect::<Map<_, _>>();
+    vars.insert("release".to_string(), cfg.release.into());
+    let profile = if cfg.release { "release" } else { "debug" };
+    vars.insert("profile".to_string(), profile.into());
+    vars.insert("public_url".to_string(), cfg.public_url.to_string().into());
+    let env = EnvModule::public_env()
+        .into_iter()
+        .map(|(label, value)| (label, Value::String(value)))
+        .collect::<Map<_, _>>();
+    vars.insert("env".to_string(), env.into());
+    Value::Object(vars)
+}
+
+/// Load a data file of the templates, a JSON, YAML or TOML file.
+fn load_data(path: &Path) -> Result<Value> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("error reading the data file {}", path.display()))?;
+    let extension = path.extension().and_then(|ext| ext.to_str());
+    match extension {
+        Some("json") => serde_json::from_str(&content).map_err(anyhow::Error::from),
+        Some("yaml" | "yml") => serde_yaml::from_str(&content).map_err(anyhow::Error::from),
+        Some("toml") => toml::from_str(&content).map_err(anyhow::Error::from),
+        _ => bail!(
+            "unsupported data file {}, expected JSON, YAML or TOML",
+            path.display()
+        ),
+    }
+    .with_context(|| format!("error parsing the data file {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rebases_paths() {
+        let root = Path::new("/site");
+        assert_eq!(relative_dir(root, root), "");
+        assert_eq!(relative_dir(root, &root.join("partials")), "partials/");
+        assert_eq!(
+            relative_dir(&root.join("blog"), &root.join("partials/nav")),
+            "../partials/nav/"
+        );
+    }
+
+    #[test]
+    fn expands_includes() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let root = tmp.path().canonicalize()?;
+        std::fs::create_dir_all(root.join("partials"))?;
+        std::fs::create_dir_all(root.join("blog"))?;
+        std::fs::write(
+            root.join("partials/header.html"),
+            r#"<header><link data-prank rel="css" href="header.css"/><link data-prank rel="include" href="nav.html"/></header>"#,
+        )?;
+        std::fs::write(root.join("partials/nav.html"), "<nav>Home</nav>")?;
+        let page = root.join("blog/index.html");
+        std::fs::write(
+            &page,
+            r#"<body><link data-prank rel="include" href="../partials/header.html"/><main></main></body>"#,
+        )?;
+
+        let options = DocumentOptions::default();
+        let html = expand_includes(std::fs::read(&page)?, &page, &options, &mut vec![])?;
+        assert_eq!(
+            String::from_utf8(html)?,
+            r#"<body><header><link data-prank rel="css" href="../partials/header.css" /><nav>Home</nav></header><main></main></body>"#
+        );
+
+        // an include including itself fails
+        std::fs::write(
+            root.join("partials/nav.html"),
+            r#"<link data-prank rel="include" href="header.html"/>"#,
+        )?;
+        assert!(expand_includes(std::fs::read(&page)?, &page, &options, &mut vec![]).is_err());
+        Ok(())
+    }
+}
@@ -75,6 +75,7 @@ impl Inline {
 }
 
 /// The content type of a inlined file.
+#[derive(Clone)]
 pub enum ContentType {
     /// Html is just pasted into `index.html` as is.
     Html,
@@ -123,6 +124,7 @@ impl FromStr for ContentType {
 }
 
 /// The output of a Inline build pipeline.
+#[derive(Clone)]
 pub struct InlineOutput {
     /// The ID of this pipeline.
     pub id: usize,
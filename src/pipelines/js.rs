@@ -118,6 +118,7 @@ impl Js {
 }
 
 /// The output of a JS build pipeline.
+#[derive(Clone)]
 pub struct JsOutput {
     /// The runtime build config.
     pub cfg: Arc<RtcBuild>,
@@ -0,0 +1,392 @@
+//! The asset manifest of a build.
+//!
+//! The manifest maps the sources of the assets of the pages, the `href` or `src` of their
+//! `data-prank` elements, and the main modules of their PureScript applications to the files
+//! written to the dist dir. It allows e.g. a backend rendering the HTML itself to reference the hashed
+//! files of a build.
+
+use crate::{common, processing::integrity::OutputDigest};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::{
+    collections::{btree_map::Entry, BTreeMap, HashMap},
+    path::Path,
+};
+use tokio::fs;
+
+/// The name of the manifest file, in the dist dir.
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// The type of the entries of PureScript applications.
+const TYPE_PURESCRIPT: &str = "purescript";
+
+/// The output files of an asset pipeline.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutputFile {
+    /// The source of the files.
+    pub source: OutputSource,
+    /// The main file, referenced by the page.
+    pub file: AssetFile,
+    /// The other files of the asset, e.g. the shared modules and chunks of an application, or the
+    /// variants of an image.
+    pub files: Vec<AssetFile>,
+}
+
+/// The source of an output file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OutputSource {
+    /// The `data-prank` element of the given ID.
+    Element(usize),
+    /// The PureScript application of the given main module.
+    PureScript(String),
+}
+
+/// A file written to the dist dir for an asset.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AssetFile {
+    /// The path of the file, relative to the dist dir.
+    pub file: String,
+    /// The integrity value of the file, if enabled.
+    pub integrity: Option<String>,
+}
+
+impl AssetFile {
+    /// Create an asset file, with the integrity of the given digest.
+    fn new(file: &str, integrity: Option<&OutputDigest>) -> Self {
+        Self {
+            file: file.to_string(),
+            integrity: integrity
+                .and_then(OutputDigest::to_integrity_value)
+                .map(|value| value.to_string()),
+        }
+    }
+}
+
+impl OutputFile {
+    /// Create an output file, with the integrity of the given digest.
+    pub fn new(source: OutputSource, file: &str, integrity: Option<&OutputDigest>) -> Self {
+        Self {
+            source,
+            file: AssetFile::new(file, integrity),
+            files: vec![],
+        }
+    }
+
+    /// Add the other files of the asset, skipping the main file and duplicates.
+    pub fn with_files<'a>(
+        mut self,
+        files: impl IntoIterator<Item = (&'a str, Option<&'a OutputDigest>)>,
+    ) -> Self {
+        for (file, integrity) in files {
+            if file != self.file.file && !self.files.iter().any(|other| other.file == file) {
+                self.files.push(AssetFile::new(file, integrity));
+            }
+        }
+        self
+    }
+}
+
+/// The `data-prank` element of an asset.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AssetElement {
+    /// The source of the asset, the `href` or `src` of the element, relative to the common parent
+    /// directory of the pages.
+    pub source: String,
+    /// The type of the asset, the `rel` of a link, or `js` for a script.
+    pub r#type: String,
+}
+
+/// An entry of the manifest.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ManifestEntry {
+    /// The main file of the asset.
+    #[serde(flatten)]
+    pub file: EntryFile,
+    /// The type of the asset, e.g. `css` or `purescript`.
+    pub r#type: String,
+    /// The other files of the asset.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<EntryFile>,
+}
+
+/// A file of an entry of the manifest.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct EntryFile {
+    /// The URL of the file, including the public URL.
+    pub href: String,
+    /// The path of the file, relative to the dist dir.
+    pub file: String,
+    /// The integrity value of the file, if enabled.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub integrity: Option<String>,
+    /// The size of the file in bytes, missing for files which aren't served from the dist dir,
+    /// like the output modules in dev mode.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+}
+
+impl EntryFile {
+    /// Create the entry of an asset file of the dist dir.
+    async fn new(dist: &Path, public_url: &str, AssetFile { file, integrity }: AssetFile) -> Self {
+        let size = fs::metadata(dist.join(&file))
+            .await
+            .ok()
+            .filter(|metadata| metadata.is_file())
+            .map(|metadata| metadata.len());
+        Self {
+            href: format!("{public_url}{file}"),
+            file,
+            integrity,
+            size,
+        }
+    }
+}
+
+/// The asset manifest, by the sources of the assets.
+pub type Manifest = BTreeMap<String, ManifestEntry>;
+
+/// The output files of a page, with the source of their asset.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ManifestFile {
+    /// The source of the asset.
+    pub source: String,
+    /// The type of the asset.
+    pub r#type: String,
+    /// The main file of the asset.
+    pub file: AssetFile,
+    /// The other files of the asset.
+    pub files: Vec<AssetFile>,
+}
+
+/// The source of an asset referenced by a page, relative to the common parent directory of the
+/// pages.
+///
+/// The page directory is relative to the common parent directory as well, empty for a page in it.
+pub fn asset_source(page_dir: &str, source: &str) -> String {
+    let mut segments = Vec::new();
+    for segment in page_dir.split('/').chain(source.split('/')) {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    segments.join("/")
+}
+
+/// Assign the output files of a page to the sources of their assets.
+pub fn page_files(
+    elements: &HashMap<usize, AssetElement>,
+    files: Vec<OutputFile>,
+) -> Vec<ManifestFile> {
+    files
+        .into_iter()
+        .filter_map(
+            |OutputFile {
+                 source,
+                 file,
+                 files,
+             }| {
+                let (source, r#type) = match source {
+                    OutputSource::Element(id) => {
+                        let element = elements.get(&id)?;
+                        (element.source.clone(), element.r#type.clone())
+                    }
+                    OutputSource::PureScript(main_module) => {
+                        (main_module, TYPE_PURESCRIPT.to_string())
+                    }
+                };
+                Some(ManifestFile {
+                    source,
+                    r#type,
+                    file,
+                    files,
+                })
+            },
+        )
+        .collect()
+}
+
+/// Create the manifest of the output files of the pages, and write it to the dist dir.
+pub async fn write_manifest(
+    dist: &Path,
+    public_url: &str,
+    files: Vec<ManifestFile>,
+) -> Result<Manifest> {
+    let mut manifest = Manifest::new();
+    for ManifestFile {
+        source,
+        r#type,
+        file,
+        files,
+    } in files
+    {
+        let mut entry = ManifestEntry {
+            file: EntryFile::new(dist, public_url, file).await,
+            r#type,
+            files: Vec::with_capacity(files.len()),
+        };
+        for file in files {
+            entry
+                .files
+                .push(EntryFile::new(dist, public_url, file).await);
+        }
+        // pages sharing an asset list it more than once, possibly with other files (e.g. other
+        // image variants), which are kept along with the files of the first page
+        match manifest.entry(source) {
+            Entry::Vacant(vacant) => {
+                vacant.insert(entry);
+            }
+            Entry::Occupied(mut occupied) => {
+                let first = occupied.get_mut();
+                for file in std::iter::once(entry.file).chain(entry.files) {
+                    if first.file.file != file.file
+                        && !first.files.iter().any(|other| other.file == file.file)
+                    {
+                        first.files.push(file);
+                    }
+                }
+            }
+        }
+    }
+
+    let json = serde_json::to_string_pretty(&manifest).context("error serializing manifest")?;
+    let path = dist.join(MANIFEST_FILE);
+    if common::path_exists(&path).await? {
+        tracing::warn!("overwriting {MANIFEST_FILE} of the dist dir with the asset manifest");
+    }
+    fs::write(&path, json)
+        .await
+        .context("error writing asset manifest")?;
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processing::integrity::IntegrityType;
+
+    #[tokio::test]
+    async fn writes_manifest() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        std::fs::write(tmp.path().join("app-1234.css"), "body{}")?;
+        std::fs::write(tmp.path().join("index-5678.js"), "main();")?;
+        std::fs::write(tmp.path().join("Data.Maybe-9abc.js"), "export {};")?;
+
+        let elements = HashMap::from([
+            (
+                0,
+                AssetElement {
+                    source: "styles/app.css".to_string(),
+                    r#type: "css".to_string(),
+                },
+            ),
+            (
+                1,
+                AssetElement {
+                    source: "spago.yaml".to_string(),
+                    r#type: "purescript".to_string(),
+                },
+            ),
+        ]);
+        let digest = OutputDigest::generate_from(IntegrityType::Sha384, "body{}");
+        let main = OutputFile::new(
+            OutputSource::PureScript("Main".to_string()),
+            "index-5678.js",
+            Some(&OutputDigest::default()),
+        )
+        .with_files([
+            ("Data.Maybe-9abc.js", Some(&digest)),
+            ("index-5678.js", None),
+        ]);
+        let files = vec![
+            OutputFile::new(OutputSource::Element(0), "app-1234.css", Some(&digest)),
+            main.clone(),
+            // another page of the same application
+            main,
+            OutputFile::new(
+                OutputSource::PureScript("Worker".to_string()),
+                "output/Worker/index.js",
+                None,
+            ),
+        ];
+
+        let manifest = write_manifest(tmp.path(), "/app/", page_files(&elements, files)).await?;
+        assert_eq!(
+            manifest.keys().collect::<Vec<_>>(),
+            ["Main", "Worker", "styles/app.css"]
+        );
+        let css = &manifest["styles/app.css"];
+        assert_eq!(css.file.href, "/app/app-1234.css");
+        assert_eq!(css.file.size, Some(6));
+        assert_eq!(css.r#type, "css");
+        assert!(css
+            .file
+            .integrity
+            .as_deref()
+            .is_some_and(|integrity| integrity.starts_with("sha384-")));
+        assert!(css.files.is_empty());
+        assert_eq!(manifest["Main"].file.integrity, None);
+        assert_eq!(manifest["Main"].r#type, "purescript");
+        assert_eq!(manifest["Worker"].file.size, None);
+
+        let written: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(tmp.path().join(MANIFEST_FILE))?)?;
+        assert_eq!(written["Main"]["href"], "/app/index-5678.js");
+        assert_eq!(written["Main"]["size"], 7);
+        let shared = written["Main"]["files"]
+            .as_array()
+            .context("the shared modules must be listed")?;
+        assert_eq!(shared.len(), 1);
+        assert_eq!(shared[0]["href"], "/app/Data.Maybe-9abc.js");
+        assert_eq!(shared[0]["size"], 10);
+        assert!(written["styles/app.css"].get("files").is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn merges_the_files_of_pages() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let elements = HashMap::from([(
+            0,
+            AssetElement {
+                source: "hero.png".to_string(),
+                r#type: "image".to_string(),
+            },
+        )]);
+        let image = |files: &[&str]| {
+            OutputFile::new(OutputSource::Element(0), "hero-1234.png", None)
+                .with_files(files.iter().map(|file| (*file, None)))
+        };
+        let files = [
+            image(&["hero-1234-480w.png", "hero-1234.webp"]),
+            image(&["hero-1234-960w.png", "hero-1234.webp"]),
+        ];
+
+        let files = files
+            .into_iter()
+            .flat_map(|file| page_files(&elements, vec![file]))
+            .collect();
+        let manifest = write_manifest(tmp.path(), "/", files).await?;
+        let files = manifest["hero.png"]
+            .files
+            .iter()
+            .map(|file| file.file.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            files,
+            ["hero-1234-480w.png", "hero-1234.webp", "hero-1234-960w.png"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn resolves_asset_sources() {
+        assert_eq!(asset_source("", "styles/app.css"), "styles/app.css");
+        assert_eq!(asset_source("", "./app.css"), "app.css");
+        assert_eq!(asset_source("blog", "app.css"), "blog/app.css");
+        assert_eq!(asset_source("blog/posts", "../../app.css"), "app.css");
+    }
+}
@@ -5,16 +5,31 @@ mod copy_file;
 #[cfg(test)]
 mod copy_file_test;
 mod css;
+mod css_urls;
 mod html;
 mod icon;
+mod image;
+mod include;
 mod inline;
 mod js;
+mod manifest;
 mod purescript;
 mod sass;
+mod service_worker;
+mod shared;
 mod tailwind_css;
 mod tailwind_css_extra;
-
-pub use html::HtmlPipeline;
+mod webmanifest;
+
+pub use html::{HtmlPipeline, PageOutput};
+pub use include::partials;
+pub use manifest::{write_manifest, MANIFEST_FILE};
+pub use purescript::{
+    import_specifiers, imported_module, page_manifest, spago_args, write_reports, CompilerErrors,
+    DevBundler, DevSession, Diagnostic, OutputDir, PursIde, SpagoMetadata,
+};
+pub use service_worker::write_service_worker;
+pub use shared::SharedAssets;
 
 use crate::{
     common::{dist_relative, html_rewrite::Document, path_exists},
@@ -24,18 +39,23 @@ use crate::{
         copy_file::{CopyFile, CopyFileOutput},
         css::{Css, CssOutput},
         icon::{Icon, IconOutput},
+        image::{Image, ImageOutput},
         inline::{Inline, InlineOutput},
         js::{Js, JsOutput},
+        manifest::{OutputFile, OutputSource},
         purescript::{PureScriptApp, PureScriptAppOutput},
         sass::{Sass, SassOutput},
         tailwind_css::{TailwindCss, TailwindCssOutput},
-        tailwind_css_extra::{TailwindCssExtra, TailwindCssExtraOutput},
+        tailwind_css_extra::{CssExtraRef, TailwindCssExtra, TailwindCssExtraOutput},
+        webmanifest::{WebManifest, WebManifestOutput},
+    },
+    processing::{
+        image::{self as image_processing, ImageType},
+        minify::minify_js,
     },
-    processing::minify::{minify_css, minify_js},
 };
 use anyhow::{bail, ensure, Context, Result};
 use minify_js::TopLevelMode;
-use oxipng::Options;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::{
@@ -59,7 +79,6 @@ const ATTR_TARGET_PATH: &str = "data-target-path";
 
 const SNIPPETS_DIR: &str = "snippets";
 const PRANK_ID: &str = "data-prank-id";
-const PNG_OPTIMIZATION_LEVEL: u8 = 6;
 
 #[derive(Debug, Clone)]
 pub struct Attr {
@@ -90,9 +109,11 @@ pub enum PrankAsset {
     TailwindCssExtra(TailwindCssExtra),
     Js(Js),
     Icon(Icon),
+    Image(Image),
     Inline(Inline),
     CopyFile(CopyFile),
     CopyDir(CopyDir),
+    WebManifest(WebManifest),
     PureScriptApp(PureScriptApp),
 }
 
@@ -125,6 +146,7 @@ impl PrankAsset {
         cfg: Arc<RtcBuild>,
         html_dir: Arc<PathBuf>,
         ignore_chan: Option<mpsc::Sender<Vec<PathBuf>>>,
+        session: DevSession,
         reference: PrankAssetReference,
         id: usize,
         changed_paths: Vec<PathBuf>,
@@ -140,6 +162,7 @@ impl PrankAsset {
                         Self::Sass(Sass::new(cfg, html_dir, attrs, id).await?)
                     }
                     Icon::TYPE_ICON => Self::Icon(Icon::new(cfg, html_dir, attrs, id).await?),
+                    Image::TYPE_IMAGE => Self::Image(Image::new(cfg, html_dir, attrs, id).await?),
                     Inline::TYPE_INLINE => {
                         Self::Inline(Inline::new(cfg, html_dir, attrs, id).await?)
                     }
@@ -150,8 +173,20 @@ impl PrankAsset {
                     CopyDir::TYPE_COPY_DIR => {
                         Self::CopyDir(CopyDir::new(cfg, html_dir, attrs, id).await?)
                     }
+                    WebManifest::TYPE_WEBMANIFEST => {
+                        Self::WebManifest(WebManifest::new(cfg, html_dir, attrs, id).await?)
+                    }
                     PureScriptApp::TYPE_PURESCRIPT_APP => Self::PureScriptApp(
-                        PureScriptApp::new(cfg, html_dir, ignore_chan, attrs, id, changed_paths).await?,
+                        PureScriptApp::new(
+                            cfg,
+                            html_dir,
+                            ignore_chan,
+                            session,
+                            attrs,
+                            id,
+                            changed_paths,
+                        )
+                        .await?,
                     ),
                     TailwindCss::TYPE_TAILWIND_CSS => {
                         Self::TailwindCss(TailwindCss::new(cfg, html_dir, attrs, id).await?)
@@ -180,15 +215,19 @@ impl PrankAsset {
             Self::TailwindCssExtra(inner) => inner.spawn(),
             Self::Js(inner) => inner.spawn(),
             Self::Icon(inner) => inner.spawn(),
+            Self::Image(inner) => inner.spawn(),
             Self::Inline(inner) => inner.spawn(),
             Self::CopyFile(inner) => inner.spawn(),
             Self::CopyDir(inner) => inner.spawn(),
+            Self::WebManifest(inner) => inner.spawn(),
             Self::PureScriptApp(inner) => inner.spawn(),
         }
     }
 }
 
 /// The output of a `<prank-link/>` asset pipeline.
+#[derive(Clone)]
+#[allow(clippy::large_enum_variant)]
 pub enum PrankAssetPipelineOutput {
     Css(CssOutput),
     Sass(SassOutput),
@@ -196,9 +235,11 @@ pub enum PrankAssetPipelineOutput {
     TailwindCssExtra(TailwindCssExtraOutput),
     Js(JsOutput),
     Icon(IconOutput),
+    Image(ImageOutput),
     Inline(InlineOutput),
     CopyFile(CopyFileOutput),
     CopyDir(CopyDirOutput),
+    WebManifest(WebManifestOutput),
     PureScriptApp(PureScriptAppOutput),
     None,
 }
@@ -212,28 +253,112 @@ impl PrankAssetPipelineOutput {
             PrankAssetPipelineOutput::TailwindCssExtra(out) => out.finalize(dom).await,
             PrankAssetPipelineOutput::Js(out) => out.finalize(dom).await,
             PrankAssetPipelineOutput::Icon(out) => out.finalize(dom).await,
+            PrankAssetPipelineOutput::Image(out) => out.finalize(dom).await,
             PrankAssetPipelineOutput::Inline(out) => out.finalize(dom).await,
             PrankAssetPipelineOutput::CopyFile(out) => out.finalize(dom).await,
             PrankAssetPipelineOutput::CopyDir(out) => out.finalize(dom).await,
+            PrankAssetPipelineOutput::WebManifest(out) => out.finalize(dom).await,
             PrankAssetPipelineOutput::PureScriptApp(out) => out.finalize(dom).await,
             PrankAssetPipelineOutput::None => Ok(()),
         }
     }
+
+    /// Assign the output to the element of the given ID, for an asset shared by several pages.
+    pub fn set_id(&mut self, id: usize) {
+        match self {
+            Self::Css(out) => out.id = id,
+            Self::Sass(out) => out.id = id,
+            Self::TailwindCss(out) => out.id = id,
+            Self::TailwindCssExtra(out) => out.id = id,
+            Self::Js(out) => out.id = id,
+            Self::Icon(out) => out.id = id,
+            Self::Image(out) => out.id = id,
+            Self::Inline(out) => out.id = id,
+            Self::CopyFile(out) => out.id = id,
+            Self::CopyDir(out) => out.0 = id,
+            Self::WebManifest(out) => out.id = id,
+            Self::PureScriptApp(out) => out.id = Some(id),
+            Self::None => {}
+        }
+    }
+
+    /// The files written to the dist dir by the pipeline, for the asset manifest.
+    ///
+    /// Inlined assets and copied directories have no such files.
+    pub fn output_file(&self) -> Option<OutputFile> {
+        let element = OutputSource::Element;
+        match self {
+            Self::Css(out) => Some(OutputFile::new(
+                element(out.id),
+                &out.file,
+                Some(&out.integrity),
+            )),
+            Self::Sass(SassOutput {
+                id,
+                css_ref: sass::CssRef::File(file, integrity),
+                ..
+            })
+            | Self::TailwindCss(TailwindCssOutput {
+                id,
+                css_ref: tailwind_css::CssRef::File(file, integrity),
+                ..
+            })
+            | Self::TailwindCssExtra(TailwindCssExtraOutput {
+                id,
+                css_ref: CssExtraRef::File(file, integrity),
+                ..
+            }) => Some(OutputFile::new(element(*id), file, Some(integrity))),
+            Self::Js(out) => Some(OutputFile::new(
+                element(out.id),
+                &out.file,
+                Some(&out.integrity),
+            )),
+            Self::Icon(out) => Some(OutputFile::new(
+                element(out.id),
+                &out.file,
+                Some(&out.integrity),
+            )),
+            // the responsive variants and other formats of the image
+            Self::Image(out) => Some(
+                OutputFile::new(element(out.id), &out.file, Some(&out.integrity)).with_files(
+                    out.srcset
+                        .iter()
+                        .chain(out.sources.iter().flat_map(|source| &source.candidates))
+                        .map(|candidate| (candidate.file.as_str(), None)),
+                ),
+            ),
+            Self::CopyFile(out) => Some(OutputFile::new(element(out.id), &out.file, None)),
+            Self::WebManifest(out) => Some(OutputFile::new(element(out.id), &out.file, None)),
+            // the shared modules, chunks and initializer of the application
+            Self::PureScriptApp(out) => Some(
+                OutputFile::new(
+                    OutputSource::PureScript(out.main_module.clone()),
+                    &out.bundle_output,
+                    out.integrities.digest(&out.bundle_output),
+                )
+                .with_files(
+                    out.integrities
+                        .files()
+                        .map(|(file, digest)| (file, Some(digest))),
+                ),
+            ),
+            Self::Sass(_)
+            | Self::TailwindCss(_)
+            | Self::TailwindCssExtra(_)
+            | Self::Inline(_)
+            | Self::CopyDir(_)
+            | Self::None => None,
+        }
+    }
 }
 
 pub enum AssetFileType {
-    Css,
-    Icon(ImageType),
+    Image(ImageType),
     Js,
     Mjs,
     Other,
 }
 
-pub enum ImageType {
-    Png,
-    Other,
-}
-
 /// An asset file to be processed by some build pipeline.
 pub struct AssetFile {
     /// The canonicalized path to the target file.
@@ -310,15 +435,10 @@ impl AssetFile {
 
         bytes = if minify {
             match file_type {
-                AssetFileType::Css => minify_css(bytes),
-                AssetFileType::Icon(image_type) => match image_type {
-                    ImageType::Png => oxipng::optimize_from_memory(
-                        bytes.as_ref(),
-                        &Options::from_preset(PNG_OPTIMIZATION_LEVEL),
-                    )
-                    .with_context(|| format!("error optimizing PNG {:?}", &self.path))?,
-                    ImageType::Other => bytes,
-                },
+                AssetFileType::Image(image_type) => {
+                    image_processing::optimize(bytes, image_type)
+                        .with_context(|| format!("error optimizing image {:?}", &self.path))?
+                }
                 AssetFileType::Js => minify_js(bytes, TopLevelMode::Global),
                 AssetFileType::Mjs => minify_js(bytes, TopLevelMode::Module),
                 _ => bytes,
@@ -348,6 +468,41 @@ impl AssetFile {
         Ok(file_name)
     }
 
+    /// Write a variant of this asset to the target dir, e.g. a resized image, named after this
+    /// asset with a suffix and the given extension. If hashing is enabled, a hash of the content is
+    /// included in the file name.
+    ///
+    /// The base file name (stripped path, relative to the base dist dir) is returned if the operation
+    /// was successful.
+    pub async fn write_variant(
+        &self,
+        dist: &Path,
+        to_dir: &Path,
+        with_hash: bool,
+        suffix: &str,
+        ext: &str,
+        bytes: Vec<u8>,
+    ) -> Result<String> {
+        let stem = self.file_stem.to_string_lossy();
+        let file_name = if with_hash {
+            format!(
+                "{stem}{suffix}-{:0>16x}.{ext}",
+                seahash::hash(bytes.as_ref())
+            )
+        } else {
+            format!("{stem}{suffix}.{ext}")
+        };
+
+        let file_path = to_dir.join(&file_name);
+        let file_name = dist_relative(dist, &file_path)?;
+
+        fs::write(&file_path, bytes)
+            .await
+            .with_context(|| format!("error writing file {:?}", &file_path))?;
+
+        Ok(file_name)
+    }
+
     /// Read the content of this asset to a String.
     pub async fn read_to_string(&self) -> Result<String> {
         fs::read_to_string(&self.path)
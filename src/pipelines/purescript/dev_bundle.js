@@ -0,0 +1,27 @@
+// The module registry of a dev bundle, modules are evaluated once they are first imported.
+const __prank_factories = new Map();
+const __prank_cache = new Map();
+function __prank_define(id, factory) {
+    __prank_factories.set(id, factory);
+}
+function __prank_load(id) {
+    let exports = __prank_cache.get(id);
+    if (exports === undefined) {
+        exports = Object.create(null);
+        __prank_cache.set(id, exports);
+        __prank_factories.get(id)(exports);
+    }
+    return exports;
+}
+function __prank_export(exports, getters) {
+    for (const name in getters) {
+        Object.defineProperty(exports, name, { enumerable: true, get: getters[name] });
+    }
+}
+function __prank_export_all(exports, from) {
+    for (const name of Object.keys(from)) {
+        if (name !== "default" && !(name in exports)) {
+            Object.defineProperty(exports, name, { enumerable: true, get: () => from[name] });
+        }
+    }
+}
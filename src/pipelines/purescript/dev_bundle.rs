@@ -0,0 +1,1139 @@
+//! An in-process bundler for the PureScript output served in dev mode.
+//!
+//! In dev mode, the page imports the main module from `/output`, and the browser discovers the
+//! modules it imports one level at a time. For larger applications, that's hundreds of requests
+//! before the application starts. Instead, the dev bundler links the modules of the output
+//! directory into a single, unminified bundle. Each module becomes a function registered by its
+//! path, and its module syntax is rewritten to calls of a small runtime (see `dev_bundle.js`).
+//!
+//! The rewrite keeps the lines of each module, so the inline source map simply maps the lines of
+//! the bundle to the lines of the modules served from `/output`. Rewritten modules are cached
+//! along with the hash of their source, so a rebuild only rewrites the modules whose code changed.
+//!
+//! The rewrite only understands the module syntax emitted by the PureScript compilers and found in
+//! foreign modules. It is no minifier or general purpose bundler, the release builds are bundled by
+//! `purs-backend-es` instead.
+//!
+//! A bundle trades hot module replacement for fewer requests: the modules of the bundle can't be
+//! replaced one by one, so the page reloads on every change.
+
+use super::graph::resolve;
+use anyhow::{anyhow, bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// The module registry of a bundle.
+const RUNTIME: &str = include_str!("dev_bundle.js");
+
+/// Keywords after which a `/` starts a regular expression, instead of being a division.
+const REGEX_KEYWORDS: &[&str] = &[
+    "return",
+    "typeof",
+    "instanceof",
+    "in",
+    "of",
+    "new",
+    "delete",
+    "void",
+    "throw",
+    "case",
+    "do",
+    "else",
+    "yield",
+    "await",
+];
+
+/// Links the modules of an output directory into a single bundle, caching rewritten modules
+/// between builds.
+#[derive(Debug, Default)]
+pub struct DevBundler {
+    modules: Mutex<HashMap<PathBuf, CachedModule>>,
+}
+
+#[derive(Debug)]
+struct CachedModule {
+    /// The hash of the module's source.
+    hash: u64,
+    module: Arc<LinkedModule>,
+}
+
+/// A module with its module syntax rewritten to calls of the bundle's runtime.
+#[derive(Debug, Default)]
+struct LinkedModule {
+    /// The rewritten source, with the same lines as the original one.
+    code: String,
+    /// The exports, as the exported name and the expression evaluating to its value.
+    exports: Vec<(String, String)>,
+    /// The (statically or dynamically) imported modules of the output directory.
+    imports: Vec<PathBuf>,
+    /// The imported packages, which are imported by the bundle itself.
+    externals: Vec<String>,
+}
+
+impl DevBundler {
+    /// Create a new instance, with an empty cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bundle all modules reachable from `entry`, which is relative to `output_dir`.
+    ///
+    /// The bundle re-exports the exports of the entry module. `public_path` is the URL path the
+    /// output directory is served from, which is used for the source map.
+    pub fn bundle(&self, output_dir: &Path, entry: &Path, public_path: &str) -> Result<String> {
+        let mut cache = self
+            .modules
+            .lock()
+            .map_err(|_| anyhow!("dev bundle cache is poisoned"))?;
+
+        let mut modules = Vec::new();
+        let mut seen = HashSet::new();
+        let mut queue = vec![entry.to_path_buf()];
+        while let Some(path) = queue.pop() {
+            if !seen.insert(path.clone()) {
+                continue;
+            }
+            let module = load(&mut cache, output_dir, &path)?;
+            queue.extend(module.imports.iter().rev().cloned());
+            modules.push((path, module));
+        }
+
+        let externals = modules
+            .iter()
+            .flat_map(|(_, module)| module.externals.iter())
+            .collect::<BTreeSet<_>>();
+
+        let mut bundle = Bundle::default();
+        for specifier in externals {
+            bundle.push(&format!(
+                "import * as {} from {};\n",
+                external_name(specifier),
+                quote(specifier)
+            ));
+        }
+        bundle.push(RUNTIME);
+
+        let mut sources = Vec::with_capacity(modules.len());
+        for (path, module) in &modules {
+            let id = module_id(path);
+            let exports = module
+                .exports
+                .iter()
+                .map(|(name, value)| format!("{}: () => {value}", quote(name)))
+                .collect::<Vec<_>>()
+                .join(", ");
+            bundle.push(&format!(
+                "__prank_define({}, function (__prank_exports) {{ __prank_export(__prank_exports, {{ {exports} }});\n",
+                quote(&id)
+            ));
+            bundle.push_mapped(&module.code, sources.len());
+            bundle.push("});\n");
+            sources.push(format!("{public_path}{id}"));
+        }
+
+        let names = modules
+            .first()
+            .map(|(_, module)| {
+                module
+                    .exports
+                    .iter()
+                    .map(|(name, _)| name.as_str())
+                    .filter(|name| *name != "default" && is_identifier(name))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        bundle.push(&format!(
+            "const __prank_entry = __prank_load({});\nexport const {{ {} }} = __prank_entry;\n",
+            quote(&module_id(entry)),
+            names.join(", ")
+        ));
+
+        Ok(bundle.finish(&sources))
+    }
+}
+
+/// Load a module from the cache, or rewrite it if its source changed.
+fn load(
+    cache: &mut HashMap<PathBuf, CachedModule>,
+    output_dir: &Path,
+    path: &Path,
+) -> Result<Arc<LinkedModule>> {
+    let full_path = output_dir.join(path);
+    let source = std::fs::read_to_string(&full_path)
+        .with_context(|| format!("error reading module {}", full_path.display()))?;
+    let hash = seahash::hash(source.as_bytes());
+
+    if let Some(cached) = cache.get(path) {
+        if cached.hash == hash {
+            return Ok(cached.module.clone());
+        }
+    }
+
+    tracing::trace!("linking {}", path.display());
+    let module = Arc::new(
+        link(&source, path).with_context(|| format!("error linking module {}", path.display()))?,
+    );
+    cache.insert(
+        path.to_path_buf(),
+        CachedModule {
+            hash,
+            module: module.clone(),
+        },
+    );
+    Ok(module)
+}
+
+/// A bundle being written, along with the original line of each line.
+#[derive(Default)]
+struct Bundle {
+    code: String,
+    /// The source index and line of each line of the bundle, if any.
+    lines: Vec<Option<(usize, usize)>>,
+}
+
+impl Bundle {
+    /// Append unmapped code, which must end with a newline.
+    fn push(&mut self, code: &str) {
+        self.code.push_str(code);
+        self.lines.extend(code.lines().map(|_| None));
+    }
+
+    /// Append the code of a source, mapping each line to the line of the source.
+    fn push_mapped(&mut self, code: &str, source: usize) {
+        self.code.push_str(code);
+        if !code.ends_with('\n') {
+            self.code.push('\n');
+        }
+        self.lines
+            .extend((0..code.lines().count()).map(|line| Some((source, line))));
+    }
+
+    /// Append the inline source map, and return the code.
+    fn finish(mut self, sources: &[String]) -> String {
+        let map = serde_json::json!({
+            "version": 3,
+            "sources": sources,
+            "names": [],
+            "mappings": mappings(&self.lines),
+        });
+        self.code
+            .push_str("//# sourceMappingURL=data:application/json;base64,");
+        self.code.push_str(&STANDARD.encode(map.to_string()));
+        self.code.push('\n');
+        self.code
+    }
+}
+
+/// Encode the mappings of a source map, mapping the start of each line.
+fn mappings(lines: &[Option<(usize, usize)>]) -> String {
+    let (mut prev_source, mut prev_line) = (0i64, 0i64);
+    let mut result = String::new();
+    for (index, line) in lines.iter().enumerate() {
+        if index > 0 {
+            result.push(';');
+        }
+        if let Some((source, line)) = line {
+            let (source, line) = (*source as i64, *line as i64);
+            vlq(&mut result, 0);
+            vlq(&mut result, source - prev_source);
+            vlq(&mut result, line - prev_line);
+            vlq(&mut result, 0);
+            (prev_source, prev_line) = (source, line);
+        }
+    }
+    result
+}
+
+/// Append a value as base64 VLQ.
+fn vlq(out: &mut String, value: i64) {
+    const DIGITS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut value = match value < 0 {
+        true => ((-value as u64) << 1) | 1,
+        false => (value as u64) << 1,
+    };
+    loop {
+        let mut digit = value & 31;
+        value >>= 5;
+        if value > 0 {
+            digit |= 32;
+        }
+        out.push(DIGITS[digit as usize] as char);
+        if value == 0 {
+            break;
+        }
+    }
+}
+
+/// The ID of a module in the bundle, its path relative to the output directory.
+fn module_id(path: &Path) -> String {
+    path.iter()
+        .map(|c| c.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// The name of the namespace of an imported package.
+fn external_name(specifier: &str) -> String {
+    format!("__prank_ext_{:x}", seahash::hash(specifier.as_bytes()))
+}
+
+/// Create a JS string literal.
+fn quote(value: &str) -> String {
+    serde_json::Value::String(value.to_string()).to_string()
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+}
+
+/// Rewrite the module syntax of a module.
+fn link(source: &str, path: &Path) -> Result<LinkedModule> {
+    let tokens = lex(source)?;
+    let mut linker = Linker {
+        source,
+        path,
+        tokens: &tokens,
+        pos: 0,
+        module: LinkedModule::default(),
+        edits: Vec::new(),
+    };
+    linker.run()?;
+
+    let mut code = source.to_string();
+    for (range, replacement) in linker.edits.into_iter().rev() {
+        // keep the lines of the original source
+        let newlines = source[range.clone()].matches('\n').count();
+        code.replace_range(range, &format!("{replacement}{}", "\n".repeat(newlines)));
+    }
+    linker.module.code = code;
+    Ok(linker.module)
+}
+
+/// The source of an imported module.
+enum Source {
+    /// A module of the output directory.
+    Module(PathBuf),
+    /// A package, or anything else the browser needs to resolve.
+    External(String),
+}
+
+impl Source {
+    /// The expression evaluating to the namespace of the module.
+    fn namespace(&self) -> String {
+        match self {
+            Self::Module(path) => format!("__prank_load({})", quote(&module_id(path))),
+            Self::External(specifier) => external_name(specifier),
+        }
+    }
+}
+
+struct Linker<'a> {
+    source: &'a str,
+    path: &'a Path,
+    tokens: &'a [Token],
+    pos: usize,
+    module: LinkedModule,
+    edits: Vec<(Range<usize>, String)>,
+}
+
+impl Linker<'_> {
+    fn run(&mut self) -> Result<()> {
+        let mut depth = 0usize;
+        // the previous token, and if a newline followed it
+        let mut prev: Option<Token> = None;
+        let mut newline = true;
+
+        while let Some(token) = self.tokens.get(self.pos).copied() {
+            self.pos += 1;
+            match token.kind {
+                Kind::Newline => {
+                    newline = true;
+                    continue;
+                }
+                Kind::Punct(b'(' | b'[' | b'{') => depth += 1,
+                Kind::Punct(b')' | b']' | b'}') => depth = depth.saturating_sub(1),
+                Kind::Ident => {
+                    let statement_start = newline
+                        || prev.map_or(true, |prev| matches!(prev.kind, Kind::Punct(b';' | b'}')));
+                    let property = prev.is_some_and(|prev| prev.kind == Kind::Punct(b'.'));
+                    match self.text(token) {
+                        "import" if !property => match self.peek().map(|t| t.kind) {
+                            Some(Kind::Punct(b'(')) => self.dynamic_import(token)?,
+                            Some(Kind::Punct(b'.')) => {}
+                            _ if depth == 0 && statement_start => self.import(token)?,
+                            _ => {}
+                        },
+                        "export" if depth == 0 && statement_start => self.export(token)?,
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+            prev = self.tokens.get(self.pos - 1).copied();
+            newline = false;
+        }
+        Ok(())
+    }
+
+    fn text(&self, token: Token) -> &str {
+        &self.source[token.start..token.end]
+    }
+
+    /// The next token, skipping newlines.
+    fn peek(&self) -> Option<Token> {
+        self.tokens[self.pos..]
+            .iter()
+            .find(|t| t.kind != Kind::Newline)
+            .copied()
+    }
+
+    /// Consume the next token, skipping newlines.
+    fn next(&mut self) -> Result<Token> {
+        while let Some(token) = self.tokens.get(self.pos).copied() {
+            self.pos += 1;
+            if token.kind != Kind::Newline {
+                return Ok(token);
+            }
+        }
+        bail!("unexpected end of module")
+    }
+
+    fn eat_punct(&mut self, punct: u8) -> bool {
+        let found = self.peek().is_some_and(|t| t.kind == Kind::Punct(punct));
+        if found {
+            self.pos = self.next_index();
+        }
+        found
+    }
+
+    fn eat_ident(&mut self, ident: &str) -> bool {
+        let found = self
+            .peek()
+            .is_some_and(|t| t.kind == Kind::Ident && self.text(t) == ident);
+        if found {
+            self.pos = self.next_index();
+        }
+        found
+    }
+
+    /// The position after the next token, skipping newlines.
+    fn next_index(&self) -> usize {
+        let mut pos = self.pos;
+        while self
+            .tokens
+            .get(pos)
+            .is_some_and(|t| t.kind == Kind::Newline)
+        {
+            pos += 1;
+        }
+        pos + 1
+    }
+
+    fn expect_ident(&mut self) -> Result<String> {
+        let token = self.next()?;
+        match token.kind {
+            Kind::Ident => Ok(self.text(token).to_string()),
+            _ => bail!("expected an identifier, found `{}`", self.text(token)),
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<()> {
+        match self.eat_ident(keyword) {
+            true => Ok(()),
+            false => bail!("expected `{keyword}`"),
+        }
+    }
+
+    /// A string literal, unquoted.
+    fn string(&mut self) -> Result<Option<String>> {
+        match self.peek() {
+            Some(token) if token.kind == Kind::Str => {
+                self.next()?;
+                Ok(Some(unquote(self.text(token))?))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// An identifier or string, as used by import and export specifiers.
+    fn name(&mut self) -> Result<String> {
+        match self.string()? {
+            Some(name) => Ok(name),
+            None => self.expect_ident(),
+        }
+    }
+
+    /// The specifiers of an import or export list, after the opening brace.
+    fn specifiers(&mut self) -> Result<Vec<(String, String)>> {
+        let mut specifiers = Vec::new();
+        while !self.eat_punct(b'}') {
+            let name = self.name()?;
+            let alias = match self.eat_ident("as") {
+                true => self.name()?,
+                false => name.clone(),
+            };
+            specifiers.push((name, alias));
+            if !self.eat_punct(b',') {
+                ensure_punct(self.eat_punct(b'}'), '}')?;
+                break;
+            }
+        }
+        Ok(specifiers)
+    }
+
+    /// The `from "..."` clause of a statement.
+    fn from(&mut self) -> Result<Source> {
+        self.expect_keyword("from")?;
+        let specifier = self
+            .string()?
+            .ok_or_else(|| anyhow!("expected a module specifier"))?;
+        self.source_of(&specifier)
+    }
+
+    /// The end of a statement, skipping import attributes and the semicolon.
+    fn end(&mut self) -> Result<usize> {
+        if self
+            .peek()
+            .is_some_and(|t| t.kind == Kind::Ident && matches!(self.text(t), "with" | "assert"))
+        {
+            self.next()?;
+            ensure_punct(self.eat_punct(b'{'), '{')?;
+            while self.next()?.kind != Kind::Punct(b'}') {}
+        }
+        self.eat_punct(b';');
+        Ok(self.tokens[self.pos - 1].end)
+    }
+
+    fn source_of(&mut self, specifier: &str) -> Result<Source> {
+        Ok(match is_relative(specifier) {
+            true => {
+                let path = resolve(self.path, specifier)
+                    .with_context(|| format!("error resolving import '{specifier}'"))?;
+                self.module.imports.push(path.clone());
+                Source::Module(path)
+            }
+            false => {
+                self.module.externals.push(specifier.to_string());
+                Source::External(specifier.to_string())
+            }
+        })
+    }
+
+    /// `import("./module.js")`, only string literals are resolved.
+    fn dynamic_import(&mut self, import: Token) -> Result<()> {
+        let start = self.pos;
+        self.eat_punct(b'(');
+        let specifier = self.string()?;
+        let specifier = match specifier {
+            Some(specifier) if self.eat_punct(b')') => specifier,
+            _ => {
+                self.pos = start;
+                return Ok(());
+            }
+        };
+        // packages are imported by the browser, using the same specifier
+        if is_relative(&specifier) {
+            let source = self.source_of(&specifier)?;
+            let end = self.tokens[self.pos - 1].end;
+            self.edits.push((
+                import.start..end,
+                format!("Promise.resolve().then(() => {})", source.namespace()),
+            ));
+        }
+        Ok(())
+    }
+
+    fn import(&mut self, import: Token) -> Result<()> {
+        if let Some(specifier) = self.string()? {
+            let source = self.source_of(&specifier)?;
+            let end = self.end()?;
+            let code = match source {
+                Source::Module(_) => format!("{};", source.namespace()),
+                // imported by the bundle
+                Source::External(_) => String::new(),
+            };
+            self.edits.push((import.start..end, code));
+            return Ok(());
+        }
+
+        let mut default = None;
+        if self.peek().is_some_and(|t| t.kind == Kind::Ident) {
+            default = Some(self.expect_ident()?);
+            self.eat_punct(b',');
+        }
+        let mut namespace = None;
+        let mut named = None;
+        if self.eat_punct(b'*') {
+            self.expect_keyword("as")?;
+            namespace = Some(self.expect_ident()?);
+        } else if self.eat_punct(b'{') {
+            named = Some(self.specifiers()?);
+        }
+
+        let source = self.from()?.namespace();
+        let end = self.end()?;
+
+        let mut bindings = Vec::new();
+        if let Some(default) = default {
+            bindings.push(format!("const {default} = {source}.default;"));
+        }
+        if let Some(namespace) = namespace {
+            bindings.push(format!("const {namespace} = {source};"));
+        }
+        if let Some(named) = named {
+            let named = named
+                .into_iter()
+                .map(|(name, alias)| format!("{}: {alias}", quote(&name)))
+                .collect::<Vec<_>>();
+            bindings.push(format!("const {{ {} }} = {source};", named.join(", ")));
+        }
+        self.edits.push((import.start..end, bindings.join(" ")));
+        Ok(())
+    }
+
+    fn export(&mut self, export: Token) -> Result<()> {
+        // `export * from "..."` and `export * as name from "..."`
+        if self.eat_punct(b'*') {
+            let name = match self.eat_ident("as") {
+                true => Some(self.name()?),
+                false => None,
+            };
+            let source = self.from()?;
+            let end = self.end()?;
+            let code = match name {
+                Some(name) => {
+                    self.module.exports.push((name, source.namespace()));
+                    String::new()
+                }
+                None => format!(
+                    "__prank_export_all(__prank_exports, {});",
+                    source.namespace()
+                ),
+            };
+            self.edits.push((export.start..end, code));
+            return Ok(());
+        }
+
+        // `export { a, b as c }` and `export { a } from "..."`
+        if self.eat_punct(b'{') {
+            let specifiers = self.specifiers()?;
+            let source = match self.peek() {
+                Some(t) if t.kind == Kind::Ident && self.text(t) == "from" => Some(self.from()?),
+                _ => None,
+            };
+            let end = self.end()?;
+            for (name, alias) in specifiers {
+                let value = match &source {
+                    Some(source) => format!("{}[{}]", source.namespace(), quote(&name)),
+                    None => name,
+                };
+                self.module.exports.push((alias, value));
+            }
+            self.edits.push((export.start..end, String::new()));
+            return Ok(());
+        }
+
+        // `export default ...`
+        if self.eat_ident("default") {
+            let end = self.tokens[self.pos - 1].end;
+            match self.declaration()? {
+                Some(name) => {
+                    self.module.exports.push(("default".to_string(), name));
+                    self.edits.push((export.start..end, String::new()));
+                }
+                None => {
+                    self.module
+                        .exports
+                        .push(("default".to_string(), "__prank_default".to_string()));
+                    self.edits
+                        .push((export.start..end, "const __prank_default =".to_string()));
+                }
+            }
+            return Ok(());
+        }
+
+        // `export const a = ...`, `export function a() ...`, ...
+        self.edits.push((export.start..export.end, String::new()));
+        let token = self.next()?;
+        match self.text(token) {
+            "const" | "let" | "var" => {
+                for name in self.declarators()? {
+                    self.module.exports.push((name.clone(), name));
+                }
+            }
+            _ => {
+                self.pos -= 1;
+                let name = self
+                    .declaration()?
+                    .ok_or_else(|| anyhow!("unsupported export"))?;
+                self.module.exports.push((name.clone(), name));
+            }
+        }
+        Ok(())
+    }
+
+    /// The name of a function or class declaration, if it has one.
+    fn declaration(&mut self) -> Result<Option<String>> {
+        let start = self.pos;
+        self.eat_ident("async");
+        if self.eat_ident("function") {
+            self.eat_punct(b'*');
+        } else if !self.eat_ident("class") {
+            self.pos = start;
+            return Ok(None);
+        }
+        match self.peek() {
+            Some(t) if t.kind == Kind::Ident && self.text(t) != "extends" => {
+                Ok(Some(self.expect_ident()?))
+            }
+            _ => {
+                self.pos = start;
+                Ok(None)
+            }
+        }
+    }
+
+    /// The names declared by a variable declaration.
+    ///
+    /// The initializers are linked as any other code, so this continues after the first name.
+    fn declarators(&mut self) -> Result<Vec<String>> {
+        let start = self.pos;
+        let names = self.scan_declarators()?;
+        self.pos = start;
+        self.next()?;
+        Ok(names)
+    }
+
+    fn scan_declarators(&mut self) -> Result<Vec<String>> {
+        let mut names = Vec::new();
+        loop {
+            let token = self.next()?;
+            if token.kind != Kind::Ident {
+                bail!("exporting destructured variables is not supported");
+            }
+            names.push(self.text(token).to_string());
+
+            // skip the initializer, up to the next declarator or the end of the statement
+            let mut depth = 0usize;
+            let mut prev = token;
+            loop {
+                let Some(token) = self.tokens.get(self.pos).copied() else {
+                    return Ok(names);
+                };
+                match token.kind {
+                    Kind::Punct(b'(' | b'[' | b'{') => depth += 1,
+                    Kind::Punct(b')' | b']' | b'}') if depth > 0 => depth -= 1,
+                    Kind::Punct(b')' | b']' | b'}') => return Ok(names),
+                    Kind::Punct(b',') if depth == 0 => {
+                        self.pos += 1;
+                        break;
+                    }
+                    Kind::Punct(b';') if depth == 0 => return Ok(names),
+                    Kind::Newline if depth == 0 && self.ends_statement(prev) => return Ok(names),
+                    _ => {}
+                }
+                if token.kind != Kind::Newline {
+                    prev = token;
+                }
+                self.pos += 1;
+            }
+        }
+    }
+
+    /// Whether a newline after `prev` ends the statement, by automatic semicolon insertion.
+    fn ends_statement(&self, prev: Token) -> bool {
+        let ends_expression = match prev.kind {
+            Kind::Ident | Kind::Str | Kind::Literal => true,
+            Kind::Punct(punct) => matches!(punct, b')' | b']' | b'}'),
+            Kind::Newline => false,
+        };
+        ends_expression
+            && self.peek().map_or(true, |t| {
+                matches!(t.kind, Kind::Ident | Kind::Str | Kind::Literal)
+            })
+    }
+}
+
+fn is_relative(specifier: &str) -> bool {
+    specifier.starts_with("./") || specifier.starts_with("../")
+}
+
+fn ensure_punct(found: bool, punct: char) -> Result<()> {
+    match found {
+        true => Ok(()),
+        false => bail!("expected `{punct}`"),
+    }
+}
+
+/// The value of a (simple) string literal.
+fn unquote(literal: &str) -> Result<String> {
+    let inner = literal
+        .get(1..literal.len().saturating_sub(1))
+        .unwrap_or_default();
+    if inner.contains('\\') {
+        bail!("escape sequences in module specifiers are not supported");
+    }
+    Ok(inner.to_string())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Ident,
+    Punct(u8),
+    /// A string literal.
+    Str,
+    /// Any other literal: numbers, template literals, regular expressions.
+    Literal,
+    Newline,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Token {
+    kind: Kind,
+    start: usize,
+    end: usize,
+}
+
+/// Split JS source into tokens, just detailed enough to find its module syntax.
+fn lex(source: &str) -> Result<Vec<Token>> {
+    let bytes = source.as_bytes();
+    let mut tokens: Vec<Token> = Vec::new();
+    // the brace depths at which template literals continue
+    let mut templates = Vec::new();
+    let mut braces = 0usize;
+    let mut pos = 0;
+
+    while let Some(&c) = bytes.get(pos) {
+        let start = pos;
+        let kind = match c {
+            b'\n' => {
+                pos += 1;
+                Kind::Newline
+            }
+            c if c.is_ascii_whitespace() => {
+                pos += 1;
+                continue;
+            }
+            b'/' if bytes.get(pos + 1) == Some(&b'/') => {
+                pos = source[pos..]
+                    .find('\n')
+                    .map_or(bytes.len(), |end| pos + end);
+                continue;
+            }
+            b'/' if bytes.get(pos + 1) == Some(&b'*') => {
+                let end = source[pos + 2..]
+                    .find("*/")
+                    .map(|end| pos + 2 + end + 2)
+                    .ok_or_else(|| anyhow!("unterminated comment"))?;
+                let newline = source[pos..end].contains('\n');
+                pos = end;
+                match newline {
+                    true => Kind::Newline,
+                    false => continue,
+                }
+            }
+            b'"' | b'\'' => {
+                pos = skip_string(bytes, pos)?;
+                Kind::Str
+            }
+            b'`' => {
+                pos = skip_template(bytes, pos + 1, &mut templates, braces)?;
+                Kind::Literal
+            }
+            b'}' if templates.last() == Some(&braces) => {
+                templates.pop();
+                pos = skip_template(bytes, pos + 1, &mut templates, braces)?;
+                Kind::Literal
+            }
+            b'/' if regex_allowed(source, &tokens) => {
+                pos = skip_regex(bytes, pos)?;
+                Kind::Literal
+            }
+            c if c.is_ascii_digit() => {
+                while bytes
+                    .get(pos)
+                    .is_some_and(|c| c.is_ascii_alphanumeric() || matches!(c, b'.' | b'_'))
+                {
+                    pos += 1;
+                }
+                Kind::Literal
+            }
+            c if is_ident_byte(c) => {
+                while bytes.get(pos).is_some_and(|c| is_ident_byte(*c)) {
+                    pos += 1;
+                }
+                Kind::Ident
+            }
+            c => {
+                match c {
+                    b'{' => braces += 1,
+                    b'}' => braces = braces.saturating_sub(1),
+                    _ => {}
+                }
+                pos += 1;
+                Kind::Punct(c)
+            }
+        };
+        tokens.push(Token {
+            kind,
+            start,
+            end: pos,
+        });
+    }
+
+    Ok(tokens)
+}
+
+fn is_ident_byte(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, b'_' | b'$' | b'\\') || c >= 0x80
+}
+
+/// Skip a string literal, returning the position after it.
+fn skip_string(bytes: &[u8], start: usize) -> Result<usize> {
+    let quote = bytes[start];
+    let mut pos = start + 1;
+    while let Some(&c) = bytes.get(pos) {
+        match c {
+            b'\\' => pos += 2,
+            b'\n' => break,
+            c if c == quote => return Ok(pos + 1),
+            _ => pos += 1,
+        }
+    }
+    bail!("unterminated string literal")
+}
+
+/// Skip the rest of a template literal, up to its end or the start of an embedded expression.
+fn skip_template(
+    bytes: &[u8],
+    mut pos: usize,
+    templates: &mut Vec<usize>,
+    braces: usize,
+) -> Result<usize> {
+    while let Some(&c) = bytes.get(pos) {
+        match c {
+            b'\\' => pos += 2,
+            b'`' => return Ok(pos + 1),
+            b'$' if bytes.get(pos + 1) == Some(&b'{') => {
+                templates.push(braces);
+                return Ok(pos + 2);
+            }
+            _ => pos += 1,
+        }
+    }
+    bail!("unterminated template literal")
+}
+
+/// Skip a regular expression literal, returning the position after its flags.
+fn skip_regex(bytes: &[u8], start: usize) -> Result<usize> {
+    let mut pos = start + 1;
+    let mut class = false;
+    while let Some(&c) = bytes.get(pos) {
+        match c {
+            b'\\' => pos += 2,
+            b'\n' => break,
+            b'[' => {
+                class = true;
+                pos += 1
+            }
+            b']' => {
+                class = false;
+                pos += 1
+            }
+            b'/' if !class => {
+                pos += 1;
+                while bytes.get(pos).is_some_and(|c| c.is_ascii_alphabetic()) {
+                    pos += 1;
+                }
+                return Ok(pos);
+            }
+            _ => pos += 1,
+        }
+    }
+    bail!("unterminated regular expression")
+}
+
+/// Whether a `/` starts a regular expression, judging by the previous token.
+fn regex_allowed(source: &str, tokens: &[Token]) -> bool {
+    match tokens.iter().rev().find(|t| t.kind != Kind::Newline) {
+        None => true,
+        Some(token) => match token.kind {
+            Kind::Punct(punct) => !matches!(punct, b')' | b']'),
+            Kind::Ident => REGEX_KEYWORDS.contains(&&source[token.start..token.end]),
+            Kind::Str | Kind::Literal | Kind::Newline => false,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::test_util::write;
+
+    #[test]
+    fn links_module_syntax() -> Result<()> {
+        let source = r#"// Generated by purs
+import * as $foreign from "./foreign.js";
+import * as Data$dMaybe from "../Data.Maybe/index.js";
+import {
+  h,
+  default as render
+} from "preact";
+var greeting = "import * as nope from 'nope';";
+var main = /* export */ function () {
+    return $foreign.log(`${greeting} ${ { a: 1 }.a }`.replace(/["/]/g, ""));
+};
+export {
+    main
+};
+export {
+    log
+} from "./foreign.js";
+"#;
+        let module = link(source, Path::new("Main/index.js"))?;
+        assert_eq!(
+            module.code,
+            r#"// Generated by purs
+const $foreign = __prank_load("Main/foreign.js");
+const Data$dMaybe = __prank_load("Data.Maybe/index.js");
+const { "h": h, "default": render } = __prank_ext_"#
+                .to_string()
+                + &format!("{:x}", seahash::hash(b"preact"))
+                + r#";
+
+
+
+var greeting = "import * as nope from 'nope';";
+var main = /* export */ function () {
+    return $foreign.log(`${greeting} ${ { a: 1 }.a }`.replace(/["/]/g, ""));
+};
+
+
+
+
+
+
+"#
+        );
+        assert_eq!(module.code.lines().count(), source.lines().count());
+        assert_eq!(
+            module.exports,
+            [
+                ("main".to_string(), "main".to_string()),
+                (
+                    "log".to_string(),
+                    r#"__prank_load("Main/foreign.js")["log"]"#.to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            module.imports,
+            [
+                PathBuf::from("Main/foreign.js"),
+                PathBuf::from("Data.Maybe/index.js"),
+                PathBuf::from("Main/foreign.js")
+            ]
+        );
+        assert_eq!(module.externals, ["preact"]);
+        Ok(())
+    }
+
+    #[test]
+    fn links_foreign_exports() -> Result<()> {
+        let source = r#"export const a = 1, b = [1, 2]
+export let c = () => {
+  return 1;
+}
+export function d() {}
+export async function* e() {}
+export class F {}
+export default function () {}
+export const load = () => import("../Page/index.js");
+"#;
+        let module = link(source, Path::new("Main/foreign.js"))?;
+        let names = module
+            .exports
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["a", "b", "c", "d", "e", "F", "default", "load"]);
+        assert!(module
+            .code
+            .contains("const __prank_default = function () {}"));
+        assert!(module
+            .code
+            .contains(r#"Promise.resolve().then(() => __prank_load("Page/index.js"))"#));
+        assert_eq!(module.imports, [PathBuf::from("Page/index.js")]);
+        Ok(())
+    }
+
+    #[test]
+    fn bundles_and_caches_modules() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let root = tmp.path();
+        write(root, "Util/index.js", "export const util = 1;\n")?;
+        write(
+            root,
+            "Main/index.js",
+            "import * as Util from \"../Util/index.js\";\nvar main = () => Util.util;\nexport { main };\n",
+        )?;
+
+        let bundler = DevBundler::new();
+        let bundle = bundler.bundle(root, Path::new("Main/index.js"), "/output/")?;
+        assert!(bundle.contains("__prank_define(\"Main/index.js\""));
+        assert!(bundle.contains("__prank_define(\"Util/index.js\""));
+        assert!(bundle.contains("export const { main } = __prank_entry;"));
+        assert!(bundle.contains("//# sourceMappingURL=data:application/json;base64,"));
+        assert_eq!(
+            bundler.modules.lock().map(|m| m.len()).unwrap_or_default(),
+            2
+        );
+
+        // the cached module is used, as long as the source didn't change, even if rewritten
+        let cached = bundler
+            .modules
+            .lock()
+            .ok()
+            .and_then(|m| m.get(Path::new("Util/index.js")).map(|m| m.module.clone()));
+        write(root, "Util/index.js", "export const util = 1;\n")?;
+        bundler.bundle(root, Path::new("Main/index.js"), "/output/")?;
+        let again = bundler
+            .modules
+            .lock()
+            .ok()
+            .and_then(|m| m.get(Path::new("Util/index.js")).map(|m| m.module.clone()));
+        assert!(matches!((&cached, &again), (Some(a), Some(b)) if Arc::ptr_eq(a, b)));
+
+        write(root, "Util/index.js", "export const util = 2;\n")?;
+        bundler.bundle(root, Path::new("Main/index.js"), "/output/")?;
+        let changed = bundler
+            .modules
+            .lock()
+            .ok()
+            .and_then(|m| m.get(Path::new("Util/index.js")).map(|m| m.module.clone()));
+        assert!(matches!((&again, &changed), (Some(a), Some(b)) if !Arc::ptr_eq(a, b)));
+        Ok(())
+    }
+
+    #[test]
+    fn encodes_mappings() {
+        let mut out = String::new();
+        for value in [0, 1, -1, 16] {
+            vlq(&mut out, value);
+        }
+        assert_eq!(out, "ACDgB");
+        assert_eq!(
+            mappings(&[None, Some((0, 0)), Some((0, 1)), Some((1, 0))]),
+            ";AAAA;AACA;ACDA"
+        );
+    }
+}
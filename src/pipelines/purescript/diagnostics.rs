@@ -0,0 +1,377 @@
+//! Diagnostics of the PureScript compiler.
+//!
+//! `purs` reports its errors and warnings as JSON when run with `--json-errors` (which spago passes
+//! on), and `purs ide` always does. The errors are enriched with an excerpt of the source, so that the
+//! browser overlay can show it, and rendered as annotated snippets for the terminal.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{fmt::Write, path::Path};
+
+/// The number of source lines shown before the span of a diagnostic.
+const CONTEXT_LINES: usize = 1;
+/// The maximum number of source lines shown of a span.
+const MAX_SPAN_LINES: usize = 5;
+
+/// A diagnostic reported by the PureScript compiler.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostic {
+    /// Whether this is an error or a warning.
+    pub severity: Severity,
+    /// The error code, e.g. `UnknownName`.
+    pub code: String,
+    /// The message, as formatted by the compiler.
+    pub message: String,
+    /// The source file, relative to the workspace root.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// The location in the source file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub span: Option<Span>,
+    /// A replacement for the span, suggested by the compiler.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suggestion: Option<String>,
+    /// The source lines around the span.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub excerpt: Vec<SourceLine>,
+}
+
+/// The severity of a diagnostic.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+        }
+    }
+}
+
+/// The diagnostics of a compiler run.
+#[derive(Clone, Debug, Default)]
+pub struct CompilerOutput {
+    pub errors: Vec<Diagnostic>,
+    pub warnings: Vec<Diagnostic>,
+}
+
+/// A location in a source file, using one-based lines and columns. The end column is exclusive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Span {
+    pub start_line: usize,
+    pub start_column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+/// A line of a source file.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceLine {
+    pub number: usize,
+    pub text: String,
+}
+
+/// The compiler errors which made a build fail, or the warnings when those are denied.
+#[derive(Clone, Debug, thiserror::Error)]
+#[error("{}", render_all(.0))]
+pub struct CompilerErrors(pub Vec<Diagnostic>);
+
+impl CompilerErrors {
+    /// Find the compiler errors which caused an error, if any.
+    pub fn find(err: &anyhow::Error) -> Option<&Self> {
+        err.chain().find_map(|err| err.downcast_ref::<Self>())
+    }
+}
+
+/// An error as reported by `purs` in JSON.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PursError {
+    error_code: String,
+    message: String,
+    filename: Option<String>,
+    position: Option<Span>,
+    suggestion: Option<PursSuggestion>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PursSuggestion {
+    replacement: String,
+}
+
+/// The JSON output of a `purs compile --json-errors` run.
+#[derive(Debug, Deserialize)]
+struct PursOutput {
+    #[serde(default)]
+    errors: Vec<PursError>,
+    #[serde(default)]
+    warnings: Vec<PursError>,
+}
+
+impl Diagnostic {
+    /// Create the diagnostics from a list of errors or warnings, as reported by `purs` in JSON.
+    ///
+    /// File names are resolved relative to `root`, to read the source excerpts.
+    pub fn from_purs(errors: &Value, severity: Severity, root: &Path) -> Option<Vec<Self>> {
+        let errors = Vec::<PursError>::deserialize(errors).ok()?;
+        Some(
+            errors
+                .into_iter()
+                .map(|error| Self::from_purs_error(error, severity, root))
+                .collect(),
+        )
+    }
+
+    fn from_purs_error(error: PursError, severity: Severity, root: &Path) -> Self {
+        let excerpt = match (&error.filename, &error.position) {
+            (Some(file), Some(span)) => read_excerpt(&root.join(file), span),
+            _ => vec![],
+        };
+        Self {
+            severity,
+            code: error.error_code,
+            message: error.message,
+            file: error.filename,
+            span: error.position,
+            suggestion: error.suggestion.map(|s| s.replacement),
+            excerpt,
+        }
+    }
+
+    /// Render the diagnostic as an annotated snippet, the way rustc does.
+    pub fn render(&self) -> String {
+        let mut lines = message_lines(&self.message).into_iter();
+        let summary = lines.next().unwrap_or_default().trim_start();
+        let mut out = format!("{}[{}]: {summary}\n", self.severity.label(), self.code);
+
+        let gutter = self
+            .excerpt
+            .last()
+            .map(|line| line.number.to_string().len())
+            .unwrap_or(1);
+        let pad = " ".repeat(gutter);
+
+        if let Some(file) = &self.file {
+            match &self.span {
+                Some(span) => {
+                    let _ = writeln!(
+                        out,
+                        "{pad}--> {file}:{}:{}",
+                        span.start_line, span.start_column
+                    );
+                }
+                None => {
+                    let _ = writeln!(out, "{pad}--> {file}");
+                }
+            }
+        }
+
+        if let (Some(span), false) = (&self.span, self.excerpt.is_empty()) {
+            let _ = writeln!(out, "{pad} |");
+            for line in &self.excerpt {
+                let source = format!("{:>gutter$} | {}", line.number, line.text);
+                let _ = writeln!(out, "{}", source.trim_end());
+                if let Some((start, end)) = underline(span, line) {
+                    let _ = writeln!(
+                        out,
+                        "{pad} | {}{}",
+                        " ".repeat(start),
+                        "^".repeat(end.saturating_sub(start).max(1))
+                    );
+                }
+            }
+            let _ = writeln!(out, "{pad} |");
+        }
+
+        let mut label = "note";
+        for line in lines {
+            match label {
+                "" => {
+                    let _ = writeln!(out, "{pad}         {line}");
+                }
+                _ => {
+                    let _ = writeln!(out, "{pad} = {label}: {line}");
+                    label = "";
+                }
+            }
+        }
+        match self.suggestion.as_deref().map(str::trim) {
+            Some("") => {
+                let _ = writeln!(out, "{pad} = help: remove it");
+            }
+            Some(suggestion) => {
+                let _ = writeln!(out, "{pad} = help: replace with `{suggestion}`");
+            }
+            None => {}
+        }
+
+        out
+    }
+}
+
+/// Parse the diagnostics of a `spago build --json-errors` run from its standard output.
+///
+/// Spago runs the compiler once, but each line with a JSON object is considered, ignoring
+/// anything else.
+pub fn parse_json_output(stdout: &str, root: &Path) -> CompilerOutput {
+    let mut result = CompilerOutput::default();
+    let outputs = stdout
+        .lines()
+        .filter(|line| line.trim_start().starts_with('{'))
+        .filter_map(|line| serde_json::from_str::<PursOutput>(line).ok());
+    for output in outputs {
+        result.errors.extend(
+            output
+                .errors
+                .into_iter()
+                .map(|error| Diagnostic::from_purs_error(error, Severity::Error, root)),
+        );
+        result.warnings.extend(
+            output
+                .warnings
+                .into_iter()
+                .map(|warning| Diagnostic::from_purs_error(warning, Severity::Warning, root)),
+        );
+    }
+    result
+}
+
+/// Render diagnostics as annotated snippets, followed by a summary line.
+pub fn render_all(diagnostics: &[Diagnostic]) -> String {
+    let errors = diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.severity == Severity::Error)
+        .count();
+    let warnings = diagnostics.len() - errors;
+    let plural = |count: usize| if count == 1 { "" } else { "s" };
+
+    let mut out = diagnostics
+        .iter()
+        .map(Diagnostic::render)
+        .collect::<Vec<_>>()
+        .join("\n");
+    let _ = match errors {
+        0 => write!(
+            out,
+            "\nerror: could not compile due to {warnings} denied warning{}",
+            plural(warnings)
+        ),
+        _ => write!(
+            out,
+            "\nerror: could not compile due to {errors} previous error{}",
+            plural(errors)
+        ),
+    };
+    out
+}
+
+/// The lines of a message, dropping blank lines and the indentation common to all lines.
+fn message_lines(message: &str) -> Vec<&str> {
+    let indent = message
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    message
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line[indent..].trim_end())
+        .collect()
+}
+
+/// The zero-based columns to underline on a line of the excerpt.
+fn underline(span: &Span, line: &SourceLine) -> Option<(usize, usize)> {
+    if line.number < span.start_line || line.number > span.end_line {
+        return None;
+    }
+    let width = line.text.chars().count();
+    let start = match line.number == span.start_line {
+        true => span.start_column.saturating_sub(1),
+        false => line.text.len() - line.text.trim_start().len(),
+    };
+    let end = match line.number == span.end_line {
+        true => span.end_column.saturating_sub(1),
+        false => width,
+    };
+    Some((start, end))
+}
+
+/// Read the lines of a span, along with some context, from a source file.
+fn read_excerpt(path: &Path, span: &Span) -> Vec<SourceLine> {
+    let Ok(source) = std::fs::read_to_string(path) else {
+        return vec![];
+    };
+    let first = span.start_line.saturating_sub(CONTEXT_LINES).max(1);
+    let last = span.end_line.min(span.start_line + MAX_SPAN_LINES - 1);
+    source
+        .lines()
+        .enumerate()
+        .map(|(index, text)| SourceLine {
+            number: index + 1,
+            text: text.to_string(),
+        })
+        .skip(first - 1)
+        .take(last + 1 - first)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn parses_and_renders_errors() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        std::fs::create_dir_all(tmp.path().join("src"))?;
+        std::fs::write(
+            tmp.path().join("src/Main.purs"),
+            "module Main where\n\nmain = foo\n",
+        )?;
+
+        let stdout = r#"{"warnings":[],"errors":[{"position":{"startLine":3,"startColumn":8,"endLine":3,"endColumn":11},"message":"  Unknown value foo.\n\n\nSee https://github.com/purescript/documentation/blob/master/errors/UnknownName.md for more information,\nor to contribute content related to this error.\n","errorCode":"UnknownName","errorLink":"","filename":"src/Main.purs","moduleName":"Main","suggestion":null,"allSpans":[]}]}"#;
+        let output = parse_json_output(&format!("Compiling Main\n{stdout}\n"), tmp.path());
+        assert!(output.warnings.is_empty());
+        let diagnostics = output.errors;
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].excerpt.len(), 2);
+
+        assert_eq!(
+            diagnostics[0].render(),
+            r#"error[UnknownName]: Unknown value foo.
+ --> src/Main.purs:3:8
+  |
+2 |
+3 | main = foo
+  |        ^^^
+  |
+  = note: See https://github.com/purescript/documentation/blob/master/errors/UnknownName.md for more information,
+          or to contribute content related to this error.
+"#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn parses_and_renders_warnings() {
+        let stdout = r#"{"warnings":[{"position":{"startLine":3,"startColumn":1,"endLine":3,"endColumn":19},"message":"  The import of Data.Maybe is redundant\n","errorCode":"UnusedImport","errorLink":"","filename":"src/Main.purs","moduleName":"Main","suggestion":{"replacement":"","replaceRange":null},"allSpans":[]}],"errors":[]}"#;
+        let output = parse_json_output(stdout, Path::new("/nonexistent"));
+        assert!(output.errors.is_empty());
+        assert_eq!(output.warnings.len(), 1);
+        assert_eq!(output.warnings[0].severity, Severity::Warning);
+        assert_eq!(
+            output.warnings[0].render(),
+            "warning[UnusedImport]: The import of Data.Maybe is redundant\n --> src/Main.purs:3:1\n  = help: remove it\n"
+        );
+        assert!(render_all(&output.warnings)
+            .ends_with("error: could not compile due to 1 denied warning"));
+    }
+}
@@ -0,0 +1,247 @@
+//! The generated `Prank.Env` module.
+//!
+//! The module exposes the build configuration to the application as constants: the activated
+//! features, the release flag, the public URL and the `PRANK_PUBLIC_*` environment variables. It
+//! is written as a PureScript module and its foreign JS module into a directory of the workspace
+//! owned by Prank, which is passed to the compiler as an additional source, so that spago compiles
+//! it along with the application without touching the sources of the package.
+
+use anyhow::{Context, Result};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+use tokio::fs;
+
+/// The prefix of environment variables exposed to the application.
+const PUBLIC_ENV_PREFIX: &str = "PRANK_PUBLIC_";
+
+/// The PureScript module, relative to the workspace root, as passed to the compiler.
+pub const SOURCE: &str = ".prank/env/Prank/Env.purs";
+
+/// The header of the generated files.
+const HEADER: &str = "Generated by prank from the build configuration, do not edit.";
+
+/// The values of the `Prank.Env` module.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EnvModule {
+    /// The activated features.
+    pub features: Vec<String>,
+    /// Whether this is a release build.
+    pub release: bool,
+    /// The public URL from which assets are served.
+    pub public_url: String,
+    /// The public environment variables, keyed by their record label.
+    pub env: BTreeMap<String, String>,
+}
+
+impl EnvModule {
+    /// Collect the public environment variables of the process.
+    pub fn public_env() -> BTreeMap<String, String> {
+        public_env(std::env::vars())
+    }
+
+    /// The paths of the PureScript module and its foreign module, in a workspace.
+    pub fn paths(workspace_root: &Path) -> (PathBuf, PathBuf) {
+        let purs_path = workspace_root.join(SOURCE);
+        let js_path = purs_path.with_extension("js");
+        (purs_path, js_path)
+    }
+
+    /// Write the module into a workspace.
+    ///
+    /// The files are only written if their content changed, so that they don't trigger another
+    /// build. Returns `true` if any file was written.
+    pub async fn write(&self, workspace_root: &Path) -> Result<bool> {
+        let (purs_path, js_path) = Self::paths(workspace_root);
+        let mut written = false;
+        for (path, content) in [(purs_path, self.purescript()), (js_path, self.foreign())] {
+            if fs::read_to_string(&path).await.ok().as_deref() == Some(content.as_str()) {
+                continue;
+            }
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)
+                    .await
+                    .with_context(|| format!("error creating directory {}", dir.display()))?;
+            }
+            fs::write(&path, content)
+                .await
+                .with_context(|| format!("error writing {}", path.display()))?;
+            written = true;
+        }
+        Ok(written)
+    }
+
+    /// The PureScript module, declaring the foreign values.
+    fn purescript(&self) -> String {
+        let env = match self.env.is_empty() {
+            true => "{}".to_string(),
+            false => {
+                let fields = self
+                    .env
+                    .keys()
+                    .map(|label| format!("{} :: String", quote(label)))
+                    .collect::<Vec<_>>();
+                format!("{{ {} }}", fields.join(", "))
+            }
+        };
+        format!(
+            r#"-- {HEADER}
+module Prank.Env
+  ( features
+  , hasFeature
+  , release
+  , publicUrl
+  , env
+  ) where
+
+-- | The activated features of the build.
+foreign import features :: Array String
+
+-- | Check if a feature is activated.
+foreign import hasFeature :: String -> Boolean
+
+-- | Whether this is a release build.
+foreign import release :: Boolean
+
+-- | The public URL from which the assets are served.
+foreign import publicUrl :: String
+
+-- | The `PRANK_PUBLIC_*` environment variables of the build.
+foreign import env :: {env}
+"#
+        )
+    }
+
+    /// The foreign JS module, defining the values.
+    fn foreign(&self) -> String {
+        let env = self
+            .env
+            .iter()
+            .map(|(label, value)| format!("\n  {}: {},", quote(label), quote(value)))
+            .collect::<String>();
+        format!(
+            r#"// {HEADER}
+export const features = [{features}];
+export const hasFeature = (feature) => features.includes(feature);
+export const release = {release};
+export const publicUrl = {public_url};
+export const env = {{{env}{newline}}};
+"#,
+            features = self
+                .features
+                .iter()
+                .map(|feature| quote(feature))
+                .collect::<Vec<_>>()
+                .join(", "),
+            release = self.release,
+            public_url = quote(&self.public_url),
+            newline = if env.is_empty() { "" } else { "\n" },
+        )
+    }
+}
+
+/// Filter the public environment variables, turning their names into record labels.
+fn public_env(vars: impl IntoIterator<Item = (String, String)>) -> BTreeMap<String, String> {
+    vars.into_iter()
+        .filter_map(|(name, value)| {
+            let name = name.strip_prefix(PUBLIC_ENV_PREFIX)?;
+            (!name.is_empty()).then(|| (label(name), value))
+        })
+        .collect()
+}
+
+/// Turn the name of an environment variable into a camel case label, e.g. `API_URL` into `apiUrl`.
+fn label(name: &str) -> String {
+    let mut label = String::with_capacity(name.len());
+    for (index, word) in name.split('_').filter(|word| !word.is_empty()).enumerate() {
+        let word = word.to_lowercase();
+        let mut chars = word.chars();
+        match (index, chars.next()) {
+            (0, Some(first)) => label.push(first),
+            (_, Some(first)) => label.extend(first.to_uppercase()),
+            (_, None) => {}
+        }
+        label.extend(chars);
+    }
+    label
+}
+
+/// Quote a string, valid as a JS string literal as well as a PureScript string or record label.
+fn quote(value: &str) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn module() -> EnvModule {
+        EnvModule {
+            features: vec!["analytics".into(), "dark-mode".into()],
+            release: true,
+            public_url: "/app/".into(),
+            env: public_env([
+                (
+                    "PRANK_PUBLIC_API_URL".to_string(),
+                    "https://api".to_string(),
+                ),
+                ("PRANK_PUBLIC_".to_string(), "ignored".to_string()),
+                ("SECRET_TOKEN".to_string(), "ignored".to_string()),
+            ]),
+        }
+    }
+
+    #[test]
+    fn labels() {
+        assert_eq!(label("API_URL"), "apiUrl");
+        assert_eq!(label("VERSION"), "version");
+        assert_eq!(label("__SENTRY__DSN"), "sentryDsn");
+    }
+
+    #[test]
+    fn generates_module() {
+        let module = module();
+        assert_eq!(
+            module.env,
+            BTreeMap::from([("apiUrl".to_string(), "https://api".to_string())])
+        );
+
+        let purs = module.purescript();
+        assert!(purs.starts_with(&format!("-- {HEADER}\n")));
+        assert!(purs.contains("foreign import env :: { \"apiUrl\" :: String }\n"));
+
+        assert_eq!(
+            module.foreign(),
+            format!(
+                r#"// {HEADER}
+export const features = ["analytics", "dark-mode"];
+export const hasFeature = (feature) => features.includes(feature);
+export const release = true;
+export const publicUrl = "/app/";
+export const env = {{
+  "apiUrl": "https://api",
+}};
+"#
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn writes_changes_only() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let mut module = module();
+
+        assert!(module.write(tmp.path()).await?);
+        assert!(!module.write(tmp.path()).await?);
+
+        module.release = false;
+        assert!(module.write(tmp.path()).await?);
+        let (purs_path, js_path) = EnvModule::paths(tmp.path());
+        assert!(purs_path.ends_with(".prank/env/Prank/Env.purs"));
+        assert!(fs::read_to_string(js_path)
+            .await?
+            .contains("export const release = false;"));
+        Ok(())
+    }
+}
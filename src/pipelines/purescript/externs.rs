@@ -0,0 +1,485 @@
+//! Checks of the main module, using the metadata the compiler writes next to compiled modules.
+//!
+//! Besides the JS code, `purs` writes an `externs.cbor` file for every module, describing its
+//! exported declarations along with their types, and a `corefn.json` file if a backend needs it.
+//! A main module must be compiled, and export a `main` value, which must be an `Effect`.
+//!
+//! The externs are encoded as CBOR, which is decoded into a generic tree here. Its layout is an
+//! implementation detail of the compiler, the decoder was written against the compilers of
+//! [`SUPPORTED_COMPILERS`]. The externs of other compilers, or externs which can't be decoded, are
+//! skipped with a warning: the exports are taken from the `corefn.json` file or the JS module
+//! instead, without checking the type of `main`.
+
+use super::graph;
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use semver::{Version, VersionReq};
+use std::path::Path;
+
+/// The versions of `purs` whose externs layout is known to the decoder.
+const SUPPORTED_COMPILERS: &str = ">=0.15.0, <0.16.0";
+
+/// The maximum number of similarly named modules suggested for a missing main module.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// The maximum nesting of CBOR values, to bound the recursion of the decoder.
+const MAX_DEPTH: usize = 512;
+
+/// The constructor tag of a value declaration (`EDValue`) in the externs.
+const EXTERN_VALUE: u64 = 3;
+
+/// Matches an export list of a JS module, which exports `main`.
+#[allow(clippy::expect_used)]
+static EXPORTS_MAIN: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"export\s*\{[^}]*\bmain\b[^}]*\}").expect("exports regex must be valid")
+});
+
+/// Check that a module of the output directory is a main module.
+///
+/// Fails if the module was not compiled, suggesting the closest module names, or if it does not
+/// export `main`. If `check_type` is set, it also fails if `main` is not an `Effect`. As only the
+/// type constructors are known here, a `main` declared using a type synonym needs to skip that
+/// check.
+pub fn verify_main(output_dir: &Path, module: &str, check_type: bool) -> Result<()> {
+    let dir = output_dir.join(module);
+    if !dir.join("externs.cbor").is_file() && !dir.join("index.js").is_file() {
+        let suggestions = closest_modules(output_dir, module)?;
+        match suggestions.is_empty() {
+            true => bail!(
+                "main module `{module}` was not found in {}, is `data-main` correct?",
+                output_dir.display()
+            ),
+            false => bail!(
+                "main module `{module}` was not found in {}, is `data-main` correct? Similar modules: {}",
+                output_dir.display(),
+                suggestions.join(", ")
+            ),
+        }
+    }
+
+    let main_type = match std::fs::read(dir.join("externs.cbor")) {
+        Ok(externs) => match decode_supported(&externs) {
+            Ok(externs) => main_type(&externs).map(texts),
+            Err(err) => {
+                tracing::warn!(
+                    "error reading the externs of module `{module}`, so the type of `main` is not checked: {err:#}"
+                );
+                None
+            }
+        },
+        Err(_) => None,
+    };
+
+    match main_type {
+        Some(names) => {
+            if check_type
+                && (!names.iter().any(|name| name == "Effect")
+                    || names.iter().any(|name| name == "Function"))
+            {
+                bail!("`main` of module `{module}` is not an `Effect`. If its type is a synonym of an `Effect`, skip this check using `data-no-main-type-check`");
+            }
+        }
+        None => {
+            if !exports_main(&dir)? {
+                bail!("main module `{module}` does not export a `main` value");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Check the exports of a module's `corefn.json` or JS module for `main`.
+fn exports_main(dir: &Path) -> Result<bool> {
+    if let Ok(corefn) = std::fs::read(dir.join("corefn.json")) {
+        let corefn: serde_json::Value =
+            serde_json::from_slice(&corefn).context("error parsing corefn.json")?;
+        if let Some(exports) = corefn["exports"].as_array() {
+            return Ok(exports.iter().any(|export| export == "main"));
+        }
+    }
+
+    let path = dir.join("index.js");
+    let source = std::fs::read_to_string(&path)
+        .with_context(|| format!("error reading module {}", path.display()))?;
+    Ok(EXPORTS_MAIN.is_match(&source))
+}
+
+/// The modules of the output directory with names closest to `module`.
+fn closest_modules(output_dir: &Path, module: &str) -> Result<Vec<String>> {
+    let mut modules = graph::module_dirs(output_dir)?
+        .into_iter()
+        .map(|(name, _)| (distance(&name, module), name))
+        .filter(|(distance, name)| *distance <= name.len().max(module.len()) / 2)
+        .collect::<Vec<_>>();
+    modules.sort();
+    Ok(modules
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, name)| name)
+        .collect())
+}
+
+/// The edit distance between two strings.
+fn distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// A decoded CBOR value, reduced to what is needed to find declarations.
+#[derive(Debug, PartialEq)]
+enum Value {
+    Int(u64),
+    Text(String),
+    Array(Vec<Value>),
+    Map(Vec<(Value, Value)>),
+    Other,
+}
+
+/// Find the type of the `main` value declaration: `[EDValue, Ident "main", type]`.
+fn main_type(value: &Value) -> Option<&Value> {
+    match value {
+        Value::Array(items) => {
+            if let [Value::Int(EXTERN_VALUE), Value::Array(ident), ty] = items.as_slice() {
+                if let [Value::Int(0), Value::Text(name)] = ident.as_slice() {
+                    if name == "main" {
+                        return Some(ty);
+                    }
+                }
+            }
+            items.iter().find_map(main_type)
+        }
+        Value::Map(entries) => entries
+            .iter()
+            .find_map(|(key, value)| main_type(key).or_else(|| main_type(value))),
+        _ => None,
+    }
+}
+
+/// All texts of a value, e.g. the names of the type constructors of a type.
+fn texts(value: &Value) -> Vec<String> {
+    fn collect(value: &Value, texts: &mut Vec<String>) {
+        match value {
+            Value::Text(text) => texts.push(text.clone()),
+            Value::Array(items) => items.iter().for_each(|item| collect(item, texts)),
+            Value::Map(entries) => entries.iter().for_each(|(key, value)| {
+                collect(key, texts);
+                collect(value, texts);
+            }),
+            Value::Int(_) | Value::Other => {}
+        }
+    }
+    let mut texts = vec![];
+    collect(value, &mut texts);
+    texts
+}
+
+/// Decode externs written by one of the [`SUPPORTED_COMPILERS`].
+fn decode_supported(bytes: &[u8]) -> Result<Value> {
+    let externs = decode(bytes)?;
+    let version = compiler_version(&externs)
+        .ok_or_else(|| anyhow!("the compiler version of the externs is unknown"))?;
+    let supported = VersionReq::parse(SUPPORTED_COMPILERS)?;
+    ensure!(
+        Version::parse(version).is_ok_and(|version| supported.matches(&version)),
+        "the externs were written by purs {version}, only purs {SUPPORTED_COMPILERS} is supported"
+    );
+    Ok(externs)
+}
+
+/// The version of the compiler which wrote the externs, their first field.
+fn compiler_version(externs: &Value) -> Option<&str> {
+    match externs {
+        Value::Array(items) => items.iter().find_map(|item| match item {
+            Value::Text(version) => Some(version.as_str()),
+            _ => None,
+        }),
+        _ => None,
+    }
+}
+
+/// Decode a CBOR document.
+fn decode(bytes: &[u8]) -> Result<Value> {
+    let mut decoder = Decoder { bytes, pos: 0 };
+    match decoder.value(0)? {
+        Item::Value(value) => Ok(value),
+        Item::Break => bail!("unexpected break"),
+    }
+}
+
+/// A decoded item, which is a value or the end of an indefinite length item.
+enum Item {
+    Value(Value),
+    Break,
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Decoder<'_> {
+    fn take(&mut self, len: usize) -> Result<&[u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .context("unexpected end of CBOR data")?;
+        let bytes = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    /// The argument of an item, `None` for indefinite lengths.
+    fn argument(&mut self, info: u8) -> Result<Option<u64>> {
+        let len = match info {
+            0..=23 => return Ok(Some(info.into())),
+            24 => 1,
+            25 => 2,
+            26 => 4,
+            27 => 8,
+            31 => return Ok(None),
+            _ => bail!("invalid CBOR argument {info}"),
+        };
+        Ok(Some(
+            self.take(len)?
+                .iter()
+                .fold(0, |value, byte| (value << 8) | u64::from(*byte)),
+        ))
+    }
+
+    fn length(&mut self, argument: u64) -> Result<usize> {
+        usize::try_from(argument)
+            .ok()
+            .filter(|len| *len <= self.bytes.len())
+            .context("invalid CBOR length")
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Item> {
+        if depth > MAX_DEPTH {
+            bail!("CBOR data nested too deeply");
+        }
+        let initial = self.take(1)?[0];
+        let (major, info) = (initial >> 5, initial & 0x1f);
+        if initial == 0xff {
+            return Ok(Item::Break);
+        }
+        let argument = self.argument(info)?;
+
+        let value = match (major, argument) {
+            (0, Some(value)) => Value::Int(value),
+            (1, Some(_)) => Value::Other,
+            (2 | 3, Some(len)) => {
+                let len = self.length(len)?;
+                let bytes = self.take(len)?;
+                match major {
+                    3 => Value::Text(String::from_utf8_lossy(bytes).into_owned()),
+                    _ => Value::Other,
+                }
+            }
+            (2 | 3, None) => {
+                let mut text = String::new();
+                loop {
+                    match self.value(depth + 1)? {
+                        Item::Value(Value::Text(chunk)) => text.push_str(&chunk),
+                        Item::Value(_) => {}
+                        Item::Break => break,
+                    }
+                }
+                match major {
+                    3 => Value::Text(text),
+                    _ => Value::Other,
+                }
+            }
+            (4, len) => {
+                let mut items = vec![];
+                while len.map_or(true, |len| (items.len() as u64) < len) {
+                    match self.value(depth + 1)? {
+                        Item::Value(value) => items.push(value),
+                        Item::Break if len.is_none() => break,
+                        Item::Break => bail!("unexpected break"),
+                    }
+                }
+                Value::Array(items)
+            }
+            (5, len) => {
+                let mut entries = vec![];
+                while len.map_or(true, |len| (entries.len() as u64) < len) {
+                    let key = match self.value(depth + 1)? {
+                        Item::Value(key) => key,
+                        Item::Break if len.is_none() => break,
+                        Item::Break => bail!("unexpected break"),
+                    };
+                    match self.value(depth + 1)? {
+                        Item::Value(value) => entries.push((key, value)),
+                        Item::Break => bail!("unexpected break"),
+                    }
+                }
+                Value::Map(entries)
+            }
+            // tags annotate the following value, which is all we need
+            (6, Some(_)) => return self.value(depth + 1),
+            // simple values and floats, their argument is the value itself
+            (7, Some(_)) => Value::Other,
+            _ => bail!("invalid CBOR item {initial:#x}"),
+        };
+        Ok(Item::Value(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::test_util::write;
+
+    /// Encode the head of a CBOR item.
+    fn head(major: u8, value: usize, out: &mut Vec<u8>) {
+        match value {
+            0..=23 => out.push(major << 5 | value as u8),
+            _ => {
+                out.push(major << 5 | 25);
+                out.extend((value as u16).to_be_bytes());
+            }
+        }
+    }
+
+    fn text(text: &str, out: &mut Vec<u8>) {
+        head(3, text.len(), out);
+        out.extend(text.as_bytes());
+    }
+
+    /// Encode the externs of a module, declaring `main` with a type naming the given constructors.
+    fn externs(constructors: &[&str]) -> Vec<u8> {
+        versioned_externs("0.15.15", constructors)
+    }
+
+    fn versioned_externs(version: &str, constructors: &[&str]) -> Vec<u8> {
+        let mut out = vec![];
+        // [ExternsFile, version, declarations]
+        head(4, 3, &mut out);
+        head(0, 0, &mut out);
+        text(version, &mut out);
+        // an indefinite length list of declarations, as written by the compiler
+        out.push(0x9f);
+        // [EDValue, Ident "main", type]
+        head(4, 3, &mut out);
+        head(0, EXTERN_VALUE as usize, &mut out);
+        head(4, 2, &mut out);
+        head(0, 0, &mut out);
+        text("main", &mut out);
+        head(4, constructors.len(), &mut out);
+        for constructor in constructors {
+            text(constructor, &mut out);
+        }
+        out.push(0xff);
+        out
+    }
+
+    #[test]
+    fn finds_main_type() -> Result<()> {
+        let externs = decode(&externs(&["Effect", "Effect", "Data.Unit", "Unit"]))?;
+        assert_eq!(
+            main_type(&externs).map(texts),
+            Some(vec![
+                "Effect".to_string(),
+                "Effect".to_string(),
+                "Data.Unit".to_string(),
+                "Unit".to_string()
+            ])
+        );
+        assert_eq!(main_type(&decode(&[0x80])?), None);
+        assert!(decode(&[0x9f]).is_err());
+
+        assert!(decode_supported(&versioned_externs("0.15.0", &["Effect"])).is_ok());
+        let err = decode_supported(&versioned_externs("0.16.0", &["Effect"]))
+            .err()
+            .map(|err| err.to_string());
+        assert_eq!(
+            err.as_deref(),
+            Some(
+                "the externs were written by purs 0.16.0, only purs >=0.15.0, <0.16.0 is supported"
+            )
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn verifies_main_modules() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        write(tmp.path(), "App.Main/externs.cbor", externs(&["Effect"]))?;
+        write(
+            tmp.path(),
+            "App.Worker/index.js",
+            b"const main = 1;\nexport {\n    main\n};\n",
+        )?;
+        write(tmp.path(), "App.Util/index.js", b"export {\n    util\n};\n")?;
+
+        verify_main(tmp.path(), "App.Main", true)?;
+        verify_main(tmp.path(), "App.Worker", true)?;
+
+        let err = verify_main(tmp.path(), "App.Util", true)
+            .err()
+            .map(|err| err.to_string());
+        assert_eq!(
+            err.as_deref(),
+            Some("main module `App.Util` does not export a `main` value")
+        );
+
+        let err = verify_main(tmp.path(), "App.Mian", true)
+            .err()
+            .map(|err| err.to_string())
+            .unwrap_or_default();
+        assert!(
+            err.ends_with("Similar modules: App.Main, App.Util"),
+            "{err}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn checks_main_type() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        write(
+            tmp.path(),
+            "App.Main/externs.cbor",
+            externs(&["App", "App"]),
+        )?;
+        let err = verify_main(tmp.path(), "App.Main", true)
+            .err()
+            .map(|err| err.to_string())
+            .unwrap_or_default();
+        assert!(
+            err.starts_with("`main` of module `App.Main` is not an `Effect`"),
+            "{err}"
+        );
+        verify_main(tmp.path(), "App.Main", false)?;
+
+        // the externs of other compilers fall back to the exports
+        write(
+            tmp.path(),
+            "App.Next/externs.cbor",
+            versioned_externs("0.16.0", &["App"]),
+        )?;
+        write(tmp.path(), "App.Next/index.js", b"export {\n    main\n};\n")?;
+        verify_main(tmp.path(), "App.Next", true)?;
+        Ok(())
+    }
+
+    #[test]
+    fn distances() {
+        assert_eq!(distance("Main", "Main"), 0);
+        assert_eq!(distance("App.Mian", "App.Main"), 2);
+        assert_eq!(distance("", "Main"), 4);
+    }
+}
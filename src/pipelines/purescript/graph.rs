@@ -0,0 +1,785 @@
+//! The ES module graph of compiled PureScript modules.
+//!
+//! The PureScript compilers emit one ES module per PureScript module (plus foreign modules),
+//! importing each other through relative paths. This module loads such a graph, starting from an
+//! entry module, and is able to write it out as a flat set of content addressed files. As the
+//! file name of a module depends on its content, which includes the (rewritten) file names of
+//! its imports, identical modules of different entry points end up in the same file.
+//!
+//! Modules loaded through a dynamic `import()` of a relative path are part of the graph as well.
+//! Those chosen as split points are the entry points of lazily loaded chunks: the modules only
+//! reachable through them are not needed before the application imports them. Such a chunk may
+//! import the modules loading it, so dynamic imports don't count as cycles. The file names of
+//! dynamically imported modules are filled in once all modules are named, the name of a module
+//! covering the sources of the modules it imports dynamically instead.
+//!
+//! For hot module replacement in dev mode, it also tracks which modules of an output directory
+//! changed during a build, and which modules depend on them.
+
+use crate::processing::minify::minify_js;
+use anyhow::{bail, Context, Result};
+use minify_js::TopLevelMode;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use seahash::SeaHasher;
+use std::{
+    collections::{BTreeSet, HashMap},
+    hash::Hasher,
+    ops::Range,
+    path::{Component, Path, PathBuf},
+};
+
+/// Matches static `import`/`export ... from` statements, capturing the module specifier.
+#[allow(clippy::expect_used)]
+static IMPORT: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"(?m)^[ \t]*(?:import|export)(?:[^;"'(]*?\bfrom)?[ \t]*["']([^"'\n]+)["']"#)
+        .expect("import regex must be valid")
+});
+
+/// Matches dynamic `import()` expressions of a string literal, capturing the module specifier.
+#[allow(clippy::expect_used)]
+static DYNAMIC_IMPORT: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"\bimport\(\s*["']([^"'\n]+)["']\s*\)"#)
+        .expect("dynamic import regex must be valid")
+});
+
+/// A module of the graph.
+#[derive(Debug)]
+pub struct Module {
+    /// The path of the module, relative to the root of the graph.
+    pub path: PathBuf,
+    /// The source code of the module.
+    pub source: String,
+    /// The imports of this module.
+    pub imports: Vec<Import>,
+}
+
+/// An import of a module.
+#[derive(Debug)]
+pub struct Import {
+    /// The byte range of the specifier in the module's source.
+    pub range: Range<usize>,
+    /// The index of the imported module, `None` for bare (package) specifiers.
+    pub module: Option<usize>,
+    /// Whether the module is imported dynamically, using `import()`.
+    pub dynamic: bool,
+}
+
+/// A graph of ES modules.
+#[derive(Debug)]
+pub struct ModuleGraph {
+    /// The modules, in dependency order: a module is always preceded by the modules it imports
+    /// statically.
+    pub modules: Vec<Module>,
+    /// The index of the entry module.
+    pub entry: usize,
+}
+
+/// The modules of a graph, grouped by when they are loaded.
+///
+/// All values are indices into [`ModuleGraph::modules`].
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Chunks {
+    /// The modules statically reachable from the entry module, in dependency order. The entry
+    /// module is the last one.
+    pub main: Vec<usize>,
+    /// The lazily loaded chunks, in dependency order.
+    pub lazy: Vec<LazyChunk>,
+}
+
+/// A chunk of modules loaded by a dynamic import.
+#[derive(Debug, PartialEq, Eq)]
+pub struct LazyChunk {
+    /// The dynamically imported module.
+    pub entry: usize,
+    /// The modules statically reachable from the entry module, which are not part of the main
+    /// chunk, in dependency order. The entry module is the last one.
+    pub modules: Vec<usize>,
+}
+
+/// A module written by [`ModuleGraph::write`].
+#[derive(Debug, Clone)]
+pub struct WrittenModule {
+    /// The name of the module, e.g. `Data.Maybe`.
+    pub name: String,
+    /// The path of the module, relative to the root of the graph.
+    pub source_path: PathBuf,
+    /// The file name of the written module.
+    pub file_name: String,
+    /// The full path of the written module.
+    pub path: PathBuf,
+}
+
+impl ModuleGraph {
+    /// Load the graph of all modules reachable from `entry`, which is relative to `root`.
+    pub fn load(root: &Path, entry: &Path) -> Result<Self> {
+        let mut loader = Loader {
+            root,
+            modules: Vec::new(),
+            index: HashMap::new(),
+            visiting: Vec::new(),
+            dynamic: Vec::new(),
+        };
+        let entry = loader.visit(entry.to_path_buf())?;
+        // dynamically imported modules are loaded on their own, so they may import their importer
+        while let Some((importer, import, path)) = loader.dynamic.pop() {
+            let module = loader.visit(path)?;
+            loader.modules[importer].imports[import].module = Some(module);
+        }
+        Ok(Self {
+            modules: loader.modules,
+            entry,
+        })
+    }
+
+    /// Group the modules into the main chunk, and the chunks loaded by dynamic imports of the
+    /// `split` modules (paths relative to the root of the graph).
+    ///
+    /// Modules reachable from the entry module as well as from a split point are part of the main
+    /// chunk only. A split point which is part of the main chunk does not start a chunk of its
+    /// own, and other dynamically imported modules are part of the chunk importing them.
+    pub fn chunks(&self, split: &[PathBuf]) -> Chunks {
+        if self.modules.is_empty() {
+            return Chunks::default();
+        }
+        let entry = self.entry;
+        let split = self
+            .modules
+            .iter()
+            .enumerate()
+            .filter(|(_, module)| split.contains(&module.path))
+            .map(|(index, _)| index)
+            .collect::<BTreeSet<_>>();
+
+        let main = self.closure(entry, &split, &BTreeSet::new());
+        let excluded = main.iter().copied().collect::<BTreeSet<_>>();
+
+        let lazy = split
+            .iter()
+            .filter(|index| !excluded.contains(index))
+            .map(|&index| LazyChunk {
+                entry: index,
+                modules: self.closure(index, &split, &excluded),
+            })
+            .collect();
+
+        Chunks { main, lazy }
+    }
+
+    /// The modules reachable from a module without passing a split point, skipping the excluded
+    /// ones, in dependency order. The start module is the last one.
+    fn closure(
+        &self,
+        start: usize,
+        split: &BTreeSet<usize>,
+        excluded: &BTreeSet<usize>,
+    ) -> Vec<usize> {
+        let mut found = BTreeSet::from([start]);
+        let mut queue = vec![start];
+        while let Some(index) = queue.pop() {
+            for import in &self.modules[index].imports {
+                let Some(module) = import.module else {
+                    continue;
+                };
+                if import.dynamic && split.contains(&module) {
+                    continue;
+                }
+                if !excluded.contains(&module) && found.insert(module) {
+                    queue.push(module);
+                }
+            }
+        }
+        // the modules are ordered by dependencies already, but dynamically imported ones may
+        // follow the start module
+        found.remove(&start);
+        found.into_iter().chain([start]).collect()
+    }
+
+    /// Write all modules of the graph as a flat directory, rewriting the imports accordingly.
+    ///
+    /// If `hash` is enabled, the file names carry the hash of their content. If `minify` is
+    /// enabled, the modules get minified. The result is in the order of [`ModuleGraph::modules`].
+    pub fn write(&self, dir: &Path, hash: bool, minify: bool) -> Result<Vec<WrittenModule>> {
+        let mut written: Vec<WrittenModule> = Vec::with_capacity(self.modules.len());
+        let mut contents = Vec::with_capacity(self.modules.len());
+        let mut reachable = HashMap::new();
+        let mut bare = false;
+
+        // Name the modules along their static imports, with placeholders for dynamic imports.
+        for module in &self.modules {
+            let mut source = module.source.clone();
+            let mut dynamic = vec![];
+            for import in module.imports.iter().rev() {
+                match import.module {
+                    Some(index) if import.dynamic => {
+                        source.replace_range(import.range.clone(), &dynamic_placeholder(index));
+                        dynamic.push(index);
+                    }
+                    Some(index) => {
+                        let specifier = format!("./{}", written[index].file_name);
+                        source.replace_range(import.range.clone(), &specifier);
+                    }
+                    None => bare = true,
+                }
+            }
+
+            let bytes = match minify {
+                true => minify_js(source.into_bytes(), TopLevelMode::Module),
+                false => source.into_bytes(),
+            };
+
+            let name = flat_name(&module.path);
+            let module_name = name.trim_end_matches(".js").to_string();
+            let file_name = match hash {
+                true => {
+                    let mut hasher = SeaHasher::new();
+                    hasher.write(&bytes);
+                    for index in &dynamic {
+                        let hash = *reachable
+                            .entry(*index)
+                            .or_insert_with(|| self.reachable_hash(*index));
+                        hasher.write_u64(hash);
+                    }
+                    format!("{:x}-{name}", hasher.finish())
+                }
+                false => name,
+            };
+
+            written.push(WrittenModule {
+                name: module_name,
+                source_path: module.path.clone(),
+                path: dir.join(&file_name),
+                file_name,
+            });
+            contents.push((bytes, dynamic));
+        }
+
+        // Fill in the names of the dynamically imported modules, and write the modules.
+        for (module, (mut bytes, dynamic)) in written.iter().zip(contents) {
+            if !dynamic.is_empty() {
+                let mut source = String::from_utf8(bytes).with_context(|| {
+                    format!("module {} is not valid UTF-8", module.source_path.display())
+                })?;
+                for index in dynamic {
+                    let specifier = format!("./{}", written[index].file_name);
+                    source = source.replace(&dynamic_placeholder(index), &specifier);
+                }
+                bytes = source.into_bytes();
+            }
+
+            // the same name means the same content, so it may already be written by another app
+            if !module.path.exists() {
+                std::fs::write(&module.path, &bytes)
+                    .with_context(|| format!("error writing module {}", module.path.display()))?;
+            }
+        }
+
+        if bare {
+            tracing::warn!("the module graph contains package imports, which are kept as they are and must be resolvable by the browser (e.g. through an import map)");
+        }
+
+        Ok(written)
+    }
+
+    /// The hash of the sources of all modules reachable from a module, also through dynamic
+    /// imports.
+    fn reachable_hash(&self, start: usize) -> u64 {
+        let mut found = BTreeSet::from([start]);
+        let mut queue = vec![start];
+        while let Some(index) = queue.pop() {
+            for module in self.modules[index].imports.iter().filter_map(|i| i.module) {
+                if found.insert(module) {
+                    queue.push(module);
+                }
+            }
+        }
+        let mut hasher = SeaHasher::new();
+        for index in found {
+            hasher.write(self.modules[index].source.as_bytes());
+        }
+        hasher.finish()
+    }
+}
+
+/// The specifier standing in for a dynamically imported module until all modules are named.
+fn dynamic_placeholder(index: usize) -> String {
+    format!("./__prank_dynamic_import_{index}__.js")
+}
+
+struct Loader<'a> {
+    root: &'a Path,
+    modules: Vec<Module>,
+    index: HashMap<PathBuf, usize>,
+    visiting: Vec<PathBuf>,
+    /// The dynamic imports to load after the current module graph: the importing module, the
+    /// index of the import, and the imported path.
+    dynamic: Vec<(usize, usize, PathBuf)>,
+}
+
+impl Loader<'_> {
+    fn visit(&mut self, path: PathBuf) -> Result<usize> {
+        if let Some(index) = self.index.get(&path) {
+            return Ok(*index);
+        }
+        if self.visiting.contains(&path) {
+            bail!(
+                "cyclic import of module {} (via {})",
+                path.display(),
+                self.visiting
+                    .iter()
+                    .map(|p| p.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(" -> ")
+            );
+        }
+
+        let full_path = self.root.join(&path);
+        let source = std::fs::read_to_string(&full_path)
+            .with_context(|| format!("error reading module {}", full_path.display()))?;
+
+        self.visiting.push(path.clone());
+        let mut specifiers = import_specifiers(&source)
+            .into_iter()
+            .map(|(range, specifier)| (range, specifier, false))
+            .chain(
+                dynamic_import_specifiers(&source)
+                    .into_iter()
+                    .map(|(range, specifier)| (range, specifier, true)),
+            )
+            .collect::<Vec<_>>();
+        // imports get rewritten back to front, so keep them in the order of the source
+        specifiers.sort_by_key(|(range, ..)| range.start);
+
+        let mut imports = Vec::new();
+        let mut dynamic_imports = Vec::new();
+        for (range, specifier, dynamic) in specifiers {
+            let module = match specifier.starts_with("./") || specifier.starts_with("../") {
+                true => {
+                    let target = resolve(&path, &specifier).with_context(|| {
+                        format!("error resolving import '{specifier}' of {}", path.display())
+                    })?;
+                    match dynamic {
+                        // loaded after the modules loading it, so importing them is no cycle
+                        true => {
+                            dynamic_imports.push((imports.len(), target));
+                            None
+                        }
+                        false => Some(self.visit(target)?),
+                    }
+                }
+                false => None,
+            };
+            imports.push(Import {
+                range,
+                module,
+                dynamic,
+            });
+        }
+        self.visiting.pop();
+
+        let index = self.modules.len();
+        self.dynamic.extend(
+            dynamic_imports
+                .into_iter()
+                .map(|(import, target)| (index, import, target)),
+        );
+        self.modules.push(Module {
+            path: path.clone(),
+            source,
+            imports,
+        });
+        self.index.insert(path, index);
+        Ok(index)
+    }
+}
+
+/// Find the specifiers of all static imports and re-exports of a module.
+pub fn import_specifiers(source: &str) -> Vec<(Range<usize>, String)> {
+    IMPORT
+        .captures_iter(source)
+        .filter_map(|captures| captures.get(1))
+        .map(|m| (m.range(), m.as_str().to_string()))
+        .collect()
+}
+
+/// Find the specifiers of all dynamic imports of a module, which use a string literal.
+fn dynamic_import_specifiers(source: &str) -> Vec<(Range<usize>, String)> {
+    DYNAMIC_IMPORT
+        .captures_iter(source)
+        .filter_map(|captures| captures.get(1))
+        .map(|m| (m.range(), m.as_str().to_string()))
+        .collect()
+}
+
+/// Resolve a relative specifier against the path of the importing module.
+pub(super) fn resolve(importer: &Path, specifier: &str) -> Result<PathBuf> {
+    let mut result = importer.parent().map(Path::to_path_buf).unwrap_or_default();
+    for component in Path::new(specifier).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !result.pop() {
+                    bail!("import points outside of the output directory");
+                }
+            }
+            Component::Normal(name) => result.push(name),
+            _ => bail!("unsupported import path"),
+        }
+    }
+    Ok(result)
+}
+
+/// Create a file name for a module path, e.g. `Data.Maybe/index.js` becomes `Data.Maybe.js`.
+fn flat_name(path: &Path) -> String {
+    let mut parts = path
+        .with_extension("")
+        .iter()
+        .map(|c| c.to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    if parts.len() > 1 && parts.last().is_some_and(|last| last == "index") {
+        parts.pop();
+    }
+    format!("{}.js", parts.join("."))
+}
+
+/// The content hashes of all modules of an output directory, by module name.
+///
+/// The hash of a module covers its `index.js`, as well as its optional `foreign.js`.
+pub fn module_hashes(output_dir: &Path) -> Result<HashMap<String, u64>> {
+    let mut hashes = HashMap::new();
+    for (name, dir) in module_dirs(output_dir)? {
+        let mut hasher = SeaHasher::new();
+        for file in ["index.js", "foreign.js"] {
+            match std::fs::read(dir.join(file)) {
+                Ok(content) => hasher.write(&content),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                Err(err) => {
+                    return Err(err).with_context(|| {
+                        format!("error reading module {}", dir.join(file).display())
+                    })
+                }
+            }
+        }
+        hashes.insert(name, hasher.finish());
+    }
+    Ok(hashes)
+}
+
+/// The modules of an output directory which changed (or were added) between two snapshots
+/// created by [`module_hashes`].
+pub fn changed_modules(
+    before: &HashMap<String, u64>,
+    after: &HashMap<String, u64>,
+) -> BTreeSet<String> {
+    after
+        .iter()
+        .filter(|(name, hash)| before.get(*name) != Some(*hash))
+        .map(|(name, _)| name.clone())
+        .collect()
+}
+
+/// Extend a set of modules of an output directory with all modules (transitively) importing them.
+pub fn with_dependents(output_dir: &Path, modules: BTreeSet<String>) -> Result<BTreeSet<String>> {
+    let mut importers: HashMap<String, Vec<String>> = HashMap::new();
+    for (name, dir) in module_dirs(output_dir)? {
+        let path = dir.join("index.js");
+        let source = match std::fs::read_to_string(&path) {
+            Ok(source) => source,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(err) => {
+                return Err(err).with_context(|| format!("error reading module {}", path.display()))
+            }
+        };
+        for (_, specifier) in import_specifiers(&source) {
+            if let Some(imported) = imported_module(&name, &specifier) {
+                if imported != name {
+                    importers
+                        .entry(imported.to_string())
+                        .or_default()
+                        .push(name.clone());
+                }
+            }
+        }
+    }
+
+    let mut result = modules;
+    let mut queue = result.iter().cloned().collect::<Vec<_>>();
+    while let Some(module) = queue.pop() {
+        for importer in importers.get(&module).into_iter().flatten() {
+            if result.insert(importer.clone()) {
+                queue.push(importer.clone());
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// The name of the module a specifier of an output module points to, `None` for anything but
+/// a module of the same output directory.
+///
+/// Modules import each other using `../Other.Module/index.js`, and their own foreign module
+/// using `./foreign.js`.
+pub fn imported_module<'a>(importer: &'a str, specifier: &'a str) -> Option<&'a str> {
+    if specifier.starts_with("./") {
+        Some(importer)
+    } else {
+        let rest = specifier.strip_prefix("../")?;
+        rest.split_once('/').map(|(module, _)| module)
+    }
+}
+
+/// The directories of all modules of an output directory, along with the module names.
+pub(super) fn module_dirs(output_dir: &Path) -> Result<Vec<(String, PathBuf)>> {
+    let entries = match std::fs::read_dir(output_dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => {
+            return Err(err).with_context(|| {
+                format!("error reading output directory {}", output_dir.display())
+            })
+        }
+    };
+
+    let mut dirs = vec![];
+    for entry in entries {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            dirs.push((
+                entry.file_name().to_string_lossy().to_string(),
+                entry.path(),
+            ));
+        }
+    }
+    Ok(dirs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::test_util::write;
+
+    #[test]
+    fn finds_imports() {
+        let source = r#"import * as $runtime from "../runtime.js";
+import * as Data$dMaybe from "../Data.Maybe/index.js";
+import {
+  a,
+  b
+} from './foreign.js';
+import "./side-effect.js";
+export {log} from "./foreign.js";
+const from = "not/an/import.js";
+export const value = from;
+"#;
+        let specifiers = import_specifiers(source)
+            .into_iter()
+            .map(|(_, s)| s)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            specifiers,
+            [
+                "../runtime.js",
+                "../Data.Maybe/index.js",
+                "./foreign.js",
+                "./side-effect.js",
+                "./foreign.js"
+            ]
+        );
+    }
+
+    #[test]
+    fn shared_modules_are_written_once() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let (root, dist) = (tmp.path().join("output"), tmp.path().join("dist"));
+        std::fs::create_dir_all(&dist)?;
+        write(&root, "runtime.js", "export const fail = () => {};\n")?;
+        write(
+            &root,
+            "Shared/index.js",
+            "import * as $runtime from \"../runtime.js\";\nexport const shared = 1;\n",
+        )?;
+        write(
+            &root,
+            "A/index.js",
+            "import * as Shared from \"../Shared/index.js\";\nexport const main = () => Shared.shared;\n",
+        )?;
+        write(
+            &root,
+            "B/index.js",
+            "import * as Shared from \"../Shared/index.js\";\nexport const main = () => Shared.shared + 1;\n",
+        )?;
+
+        let a = ModuleGraph::load(&root, Path::new("A/index.js"))?;
+        let b = ModuleGraph::load(&root, Path::new("B/index.js"))?;
+        assert_eq!(a.modules.len(), 3);
+        assert_eq!(
+            a.modules.last().map(|m| m.path.as_path()),
+            Some(Path::new("A/index.js"))
+        );
+
+        let a = a.write(&dist, true, false)?;
+        let b = b.write(&dist, true, false)?;
+        assert_eq!(a[0].file_name, b[0].file_name);
+        assert_eq!(a[1].file_name, b[1].file_name);
+        assert_ne!(a[2].file_name, b[2].file_name);
+        assert!(a[1].file_name.ends_with("-Shared.js"));
+        assert_eq!(std::fs::read_dir(&dist)?.count(), 4);
+
+        let entry = std::fs::read_to_string(&a[2].path)?;
+        assert!(entry.contains(&format!("from \"./{}\"", a[1].file_name)));
+        Ok(())
+    }
+
+    #[test]
+    fn tracks_changed_modules_and_dependents() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let root = tmp.path();
+        write(root, "Data.Util/index.js", "export const util = 1;\n")?;
+        write(
+            root,
+            "App.View/index.js",
+            "import * as Data$dUtil from \"../Data.Util/index.js\";\nimport * as $foreign from \"./foreign.js\";\n",
+        )?;
+        write(root, "App.View/foreign.js", "export const view = 1;\n")?;
+        write(
+            root,
+            "Main/index.js",
+            "import * as App$dView from \"../App.View/index.js\";\n",
+        )?;
+        write(root, "Other/index.js", "export const other = 1;\n")?;
+
+        let before = module_hashes(root)?;
+        assert_eq!(before.len(), 4);
+        write(root, "App.View/foreign.js", "export const view = 2;\n")?;
+        let after = module_hashes(root)?;
+
+        let changed = changed_modules(&before, &after);
+        assert_eq!(changed, BTreeSet::from(["App.View".to_string()]));
+        assert_eq!(
+            with_dependents(root, changed)?,
+            BTreeSet::from(["App.View".to_string(), "Main".to_string()])
+        );
+
+        assert_eq!(
+            imported_module("Main", "../Data.Util/index.js"),
+            Some("Data.Util")
+        );
+        assert_eq!(imported_module("Main", "./foreign.js"), Some("Main"));
+        assert_eq!(imported_module("Main", "react"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn splits_dynamic_imports_into_chunks() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let (root, dist) = (tmp.path().join("output"), tmp.path().join("dist"));
+        std::fs::create_dir_all(&dist)?;
+        write(&root, "Shared/index.js", "export const shared = 1;\n")?;
+        write(&root, "Admin.View/index.js", "export const view = 1;\n")?;
+        write(
+            &root,
+            "Admin/index.js",
+            "import * as Shared from \"../Shared/index.js\";\nimport * as Admin$dView from \"../Admin.View/index.js\";\n",
+        )?;
+        write(&root, "Help/index.js", "export const help = 1;\n")?;
+        write(
+            &root,
+            "Main/foreign.js",
+            "export const loadAdmin = () => import(\"../Admin/index.js\");\nexport const loadHelp = () => import(\"../Help/index.js\");\n",
+        )?;
+        write(
+            &root,
+            "Main/index.js",
+            "import * as Shared from \"../Shared/index.js\";\nimport * as $foreign from \"./foreign.js\";\n",
+        )?;
+
+        let graph = ModuleGraph::load(&root, Path::new("Main/index.js"))?;
+        let index = |path: &str| graph.modules.iter().position(|m| m.path == Path::new(path));
+        assert_eq!(graph.chunks(&[]).lazy, []);
+
+        // Only split points start a chunk, other dynamic imports are part of the main chunk.
+        let chunks = graph.chunks(&[PathBuf::from("Admin/index.js")]);
+        assert_eq!(chunks.main.len(), 4);
+        assert!(index("Help/index.js").is_some_and(|help| chunks.main.contains(&help)));
+        assert_eq!(chunks.main.last().copied(), index("Main/index.js"));
+        assert_eq!(chunks.lazy.len(), 1);
+        assert_eq!(Some(chunks.lazy[0].entry), index("Admin/index.js"));
+        assert_eq!(
+            chunks.lazy[0].modules,
+            [index("Admin.View/index.js"), index("Admin/index.js")]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>()
+        );
+
+        let written = graph.write(&dist, true, false)?;
+        let admin = &written[chunks.lazy[0].entry];
+        assert_eq!(admin.name, "Admin");
+        let foreign = written
+            .iter()
+            .find(|m| m.name == "Main.foreign")
+            .map(|m| std::fs::read_to_string(&m.path))
+            .transpose()?
+            .unwrap_or_default();
+        assert!(foreign.contains(&format!("import(\"./{}\")", admin.file_name)));
+        Ok(())
+    }
+
+    #[test]
+    fn splits_chunks_importing_their_importer() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let (root, dist) = (tmp.path().join("output"), tmp.path().join("dist"));
+        std::fs::create_dir_all(&dist)?;
+        write(
+            &root,
+            "App.Router/foreign.js",
+            "export const loadAdmin = () => import(\"../Route.Admin/index.js\");\n",
+        )?;
+        write(
+            &root,
+            "App.Router/index.js",
+            "import * as $foreign from \"./foreign.js\";\nexport const route = 1;\n",
+        )?;
+        write(
+            &root,
+            "Route.Admin/index.js",
+            "import * as App$dRouter from \"../App.Router/index.js\";\n",
+        )?;
+        write(
+            &root,
+            "Main/index.js",
+            "import * as App$dRouter from \"../App.Router/index.js\";\n",
+        )?;
+
+        let graph = ModuleGraph::load(&root, Path::new("Main/index.js"))?;
+        let index = |path: &str| graph.modules.iter().position(|m| m.path == Path::new(path));
+        let chunks = graph.chunks(&[PathBuf::from("Route.Admin/index.js")]);
+        assert_eq!(chunks.main.len(), 3);
+        assert_eq!(chunks.main.last().copied(), index("Main/index.js"));
+        assert_eq!(chunks.lazy.len(), 1);
+        assert_eq!(chunks.lazy[0].modules, [chunks.lazy[0].entry]);
+
+        let written = graph.write(&dist, true, false)?;
+        let file = |name: &str| {
+            written
+                .iter()
+                .find(|m| m.name == name)
+                .context("module must be written")
+        };
+        let (router, admin) = (file("App.Router")?, file("Route.Admin")?);
+        let foreign = std::fs::read_to_string(&file("App.Router.foreign")?.path)?;
+        assert!(foreign.contains(&format!("import(\"./{}\")", admin.file_name)));
+        let admin = std::fs::read_to_string(&admin.path)?;
+        assert!(admin.contains(&format!("\"./{}\"", router.file_name)));
+        Ok(())
+    }
+
+    #[test]
+    fn detects_cycles() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        write(tmp.path(), "A/index.js", "import \"../B/index.js\";\n")?;
+        write(tmp.path(), "B/index.js", "import \"../A/index.js\";\n")?;
+        assert!(ModuleGraph::load(tmp.path(), Path::new("A/index.js")).is_err());
+        Ok(())
+    }
+}
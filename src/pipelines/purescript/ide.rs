@@ -0,0 +1,302 @@
+//! Incremental compiles through a long-lived `purs ide server`.
+//!
+//! A `spago build` checks all modules of the workspace, which takes a while for larger
+//! applications. In watch mode, a `purs ide server` can be kept running instead, rebuilding only
+//! the changed modules. The server reads the externs of the other modules from the output
+//! directory, so a full build must have been run before. One server is started per workspace, on
+//! its first incremental build, and stopped once the watch system is dropped.
+
+use super::diagnostics::{CompilerErrors, Diagnostic, Severity};
+use crate::{
+    common::check_target_not_found_err,
+    config::rt::RtcBuild,
+    tools::{self, Application},
+};
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    net::Ipv4Addr,
+    path::{Path, PathBuf},
+    process::{ExitStatus, Stdio},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+    process::{Child, Command},
+    sync::Mutex,
+    time::Instant,
+};
+
+/// The time to wait for a `purs ide server` to accept connections.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+/// The number of times to start a `purs ide server`, each time on another port.
+///
+/// The free port picked for the server might be taken by another process before the server
+/// listens on it, the server then fails to start.
+const START_ATTEMPTS: usize = 3;
+
+/// The `purs ide server` processes of the spago workspaces of a page, by workspace root.
+#[derive(Debug, Default)]
+pub struct PursIde {
+    servers: Mutex<HashMap<PathBuf, IdeServer>>,
+}
+
+impl PursIde {
+    /// Create a new instance, servers are started when needed.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mark the externs known to the server of a workspace as outdated, after a full build
+    /// changed its output.
+    pub async fn invalidate(&self, workspace_root: &Path) {
+        if let Some(server) = self.servers.lock().await.get_mut(workspace_root) {
+            server.stale = true;
+        }
+    }
+
+    /// Rebuild the given PureScript modules of a workspace, writing their JS output to the output
+    /// directory of the workspace.
+    ///
+    /// The server is started using the `purs` of the build's tools, if not running yet. The
+    /// warnings of the rebuilt modules are returned.
+    pub async fn rebuild(
+        &self,
+        cfg: &RtcBuild,
+        workspace_root: &Path,
+        output_dir: &Path,
+        files: &[PathBuf],
+    ) -> Result<Vec<Diagnostic>> {
+        let mut servers = self.servers.lock().await;
+        let server = match servers.remove(workspace_root) {
+            Some(server) => server,
+            None => {
+                let purs = tools::get(
+                    Application::Purs,
+                    cfg.tools.purs.as_deref(),
+                    cfg.offline,
+                    &cfg.client_options(),
+                )
+                .await?;
+                IdeServer::start(&purs, workspace_root, output_dir).await?
+            }
+        };
+
+        // Keep the server for the next build, unless we lost the connection to it.
+        let res = server.rebuild(workspace_root, files).await;
+        match res {
+            Ok((server, warnings)) => {
+                servers.insert(workspace_root.to_path_buf(), server);
+                Ok(warnings)
+            }
+            Err(RebuildError::Compiler(server, err)) => {
+                servers.insert(workspace_root.to_path_buf(), server);
+                Err(err)
+            }
+            Err(RebuildError::Server(err)) => Err(err),
+        }
+    }
+}
+
+/// A running `purs ide server`.
+#[derive(Debug)]
+struct IdeServer {
+    /// The server process, killed when dropped.
+    child: Child,
+    /// The port the server listens on.
+    port: u16,
+    /// Whether the externs need to be (re)loaded from the output directory.
+    stale: bool,
+}
+
+/// The reasons a rebuild can fail.
+enum RebuildError {
+    /// The modules failed to compile.
+    Compiler(IdeServer, anyhow::Error),
+    /// The server could not be used.
+    Server(anyhow::Error),
+}
+
+impl IdeServer {
+    /// Start a server for a workspace, and wait for it to accept connections.
+    async fn start(purs: &Path, workspace_root: &Path, output_dir: &Path) -> Result<Self> {
+        for attempt in 1..=START_ATTEMPTS {
+            match Self::spawn(purs, workspace_root, output_dir).await? {
+                Ok(server) => return Ok(server),
+                Err(status) if attempt < START_ATTEMPTS => tracing::debug!(
+                    "purs ide server exited on startup ({status}), retrying on another port"
+                ),
+                Err(status) => bail!("purs ide server exited unexpectedly: {status}"),
+            }
+        }
+        unreachable!("the last attempt either succeeds or fails")
+    }
+
+    /// Spawn a server on a free port, and wait for it to answer.
+    ///
+    /// Returns the exit status of the server if it exited before answering, e.g. because the port
+    /// was taken meanwhile.
+    async fn spawn(
+        purs: &Path,
+        workspace_root: &Path,
+        output_dir: &Path,
+    ) -> Result<Result<Self, ExitStatus>> {
+        let port = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .and_then(|listener| listener.local_addr())
+            .context("error finding a free port for purs ide server")?
+            .port();
+        let port_arg = port.to_string();
+        let args = [
+            "ide",
+            "server",
+            "--port",
+            &port_arg,
+            "--output-directory",
+            &output_dir.to_string_lossy(),
+            "--log-level",
+            "none",
+        ];
+        tracing::debug!(?args, "starting purs ide server");
+
+        let child = Command::new(purs)
+            .current_dir(workspace_root)
+            .args(args)
+            .stdout(Stdio::null())
+            .stderr(Stdio::inherit())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|err| check_target_not_found_err(err.into(), "purs"))?;
+
+        let mut server = Self {
+            child,
+            port,
+            stale: true,
+        };
+        let deadline = Instant::now() + STARTUP_TIMEOUT;
+        loop {
+            if let Some(status) = server.child.try_wait()? {
+                return Ok(Err(status));
+            }
+            // Anything else listening on the port wouldn't answer like a purs ide server.
+            let cwd = server.command(&json!({ "command": "cwd" })).await;
+            if let Ok(Response::Success(_)) = cwd {
+                return Ok(Ok(server));
+            }
+            if Instant::now() > deadline {
+                bail!("timeout waiting for purs ide server to start");
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+
+    /// Rebuild the modules, loading the externs first if needed.
+    async fn rebuild(
+        mut self,
+        workspace_root: &Path,
+        files: &[PathBuf],
+    ) -> Result<(Self, Vec<Diagnostic>), RebuildError> {
+        if self.stale {
+            let res = self.command(&json!({ "command": "load" })).await;
+            match res {
+                Ok(Response::Success(_)) => self.stale = false,
+                Ok(Response::Error(err)) => {
+                    let err = command_error(err, workspace_root)
+                        .context("error loading externs into purs ide server");
+                    return Err(RebuildError::Compiler(self, err));
+                }
+                Err(err) => return Err(RebuildError::Server(err)),
+            }
+        }
+
+        let mut warnings = Vec::new();
+        for file in files {
+            tracing::debug!("rebuilding {}", file.display());
+            let command = json!({
+                "command": "rebuild",
+                "params": { "file": file, "codegen": ["js"] },
+            });
+            let res = self.command(&command).await;
+            match res {
+                Ok(Response::Success(result)) => warnings.extend(
+                    Diagnostic::from_purs(&result, Severity::Warning, workspace_root)
+                        .unwrap_or_default(),
+                ),
+                Ok(Response::Error(err)) => {
+                    let err = command_error(err, workspace_root)
+                        .context(format!("error compiling {}", file.display()));
+                    return Err(RebuildError::Compiler(self, err));
+                }
+                Err(err) => return Err(RebuildError::Server(err)),
+            }
+        }
+
+        Ok((self, warnings))
+    }
+
+    /// Send a command to the server, which answers with a single line of JSON.
+    async fn command(&self, command: &Value) -> Result<Response> {
+        let mut stream = TcpStream::connect((Ipv4Addr::LOCALHOST, self.port))
+            .await
+            .context("error connecting to purs ide server")?;
+        let mut request = serde_json::to_vec(command)?;
+        request.push(b'\n');
+        stream
+            .write_all(&request)
+            .await
+            .context("error sending command to purs ide server")?;
+
+        let mut response = String::new();
+        BufReader::new(stream)
+            .read_line(&mut response)
+            .await
+            .context("error reading response of purs ide server")?;
+        serde_json::from_str(&response).context("error parsing response of purs ide server")
+    }
+}
+
+/// A response of the server.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "resultType", content = "result", rename_all = "lowercase")]
+enum Response {
+    Success(Value),
+    Error(Value),
+}
+
+/// The error of a failed command, the diagnostics of the compiler or a plain message.
+fn command_error(result: Value, root: &Path) -> anyhow::Error {
+    match Diagnostic::from_purs(&result, Severity::Error, root) {
+        Some(diagnostics) => CompilerErrors(diagnostics).into(),
+        None => match result {
+            Value::String(message) => anyhow!(message),
+            other => anyhow!(other.to_string()),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_rebuild_errors() -> Result<()> {
+        let response: Response = serde_json::from_str(
+            r#"{"resultType":"error","result":[{"suggestion":null,"moduleName":"Main","errorCode":"UnknownName","message":"  Unknown value foo\n","filename":"src/Main.purs","position":{"startLine":12,"startColumn":5,"endLine":12,"endColumn":8},"errorLink":"","allSpans":[]}]}"#,
+        )?;
+        let Response::Error(result) = response else {
+            panic!("expected an error response");
+        };
+        let err = command_error(result, Path::new("/nonexistent"));
+        let errors = err
+            .downcast_ref::<CompilerErrors>()
+            .ok_or_else(|| anyhow!("expected compiler errors"))?;
+        assert_eq!(errors.0[0].code, "UnknownName");
+        assert_eq!(errors.0[0].file.as_deref(), Some("src/Main.purs"));
+
+        let err = command_error(json!("Module not found"), Path::new("/nonexistent"));
+        assert_eq!(err.to_string(), "Module not found");
+        Ok(())
+    }
+}
@@ -1,14 +1,39 @@
+mod dev_bundle;
+mod diagnostics;
+mod env;
+mod externs;
+mod graph;
+mod ide;
 mod output;
+mod report;
+mod source_map;
+mod spago;
 mod sri;
-
-pub use output::PureScriptAppOutput;
-
-use super::{data_target_path, Attrs, PrankAssetPipelineOutput, ATTR_HREF};
+mod warnings;
+
+pub use dev_bundle::DevBundler;
+pub use diagnostics::{CompilerErrors, Diagnostic};
+pub use env::EnvModule;
+pub use graph::{import_specifiers, imported_module};
+pub use ide::PursIde;
+pub use output::{Chunk, PureScriptAppOutput};
+pub use report::{write_reports, BundleReport};
+pub use spago::SpagoMetadata;
+
+use report::ModuleBytes;
+use spago::SpagoBundleType;
+use warnings::Warnings;
+
+use super::{data_target_path, include, Attrs, PrankAssetPipelineOutput, ATTR_HREF};
 use crate::{
-    common::{self, path_exists},
+    common::{
+        self,
+        html_rewrite::{Document, DocumentOptions},
+        path_exists, OutputLine,
+    },
     config::{
         rt::{Features, RtcBuild},
-        types::CrossOrigin,
+        types::{CrossOrigin, PureScriptWarnings},
     },
     pipelines::purescript::sri::{SriBuilder, SriOptions, SriType},
     processing::{integrity::IntegrityType, minify::minify_js},
@@ -16,58 +41,121 @@ use crate::{
 };
 use anyhow::{anyhow, bail, ensure, Context, Result};
 use minify_js::TopLevelMode;
+use once_cell::sync::Lazy;
 use seahash::SeaHasher;
-use serde::Deserialize;
 use std::{
-    fs::File,
+    collections::{BTreeSet, HashMap},
     hash::Hasher,
-    io::BufReader,
     path::{Path, PathBuf},
     str::FromStr,
     sync::Arc,
 };
-use tokio::{fs, sync::mpsc, task::JoinHandle};
+use tokio::{
+    fs,
+    sync::{mpsc, Mutex},
+    task::JoinHandle,
+};
 use tracing::log;
 
-#[derive(Debug, Clone, Deserialize)]
-pub struct SpagoMetadata {
-    #[serde(skip)]
-    /// The path to the spago.yaml manifest
-    pub manifest_path: PathBuf,
-    #[serde(skip)]
-    /// The directory containing the manifest
-    pub workspace_root: PathBuf,
-    /// The `package` section of the spago.yaml
-    pub package: SpagoPackage,
+/// The script run before the application, resolved relative to the page.
+pub const ATTR_INITIALIZER: &str = "data-initializer";
+
+/// Serializes compiler runs of concurrently running PureScript pipelines.
+///
+/// All PureScript pipelines of a page (e.g. the main application and its workers) share the same
+/// spago workspace, and thus the same output directories. Running `spago build` or
+/// `purs-backend-es` concurrently on them would corrupt the output.
+static COMPILER_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// The arguments of a spago command, applying the network and lock file settings of the build,
+/// selecting the package of the workspace, and adding the generated `Prank.Env` module.
+pub fn spago_args<'a>(
+    command: &'a str,
+    cfg: &RtcBuild,
+    manifest: &'a SpagoMetadata,
+) -> Vec<&'a str> {
+    let mut args = vec![command];
+
+    if cfg.offline {
+        args.push("--offline");
+    }
+    if cfg.frozen {
+        // Frozen is the default
+        // args.push("--frozen");
+    }
+    if cfg.locked {
+        args.push("--pure");
+    }
+    if let Some(package) = &manifest.selected_package {
+        args.extend(["-p", package]);
+    }
+    if cfg.env_module {
+        args.extend(["--purs-args", env::SOURCE]);
+    }
+
+    args
 }
 
-#[derive(Debug, Clone, Deserialize)]
-pub struct SpagoPackage {
-    pub name: String,
+/// The path of the spago manifest of an application, given by the `href` of its link, relative to
+/// the directory of the HTML file.
+fn manifest_path(html_dir: &Path, attrs: &Attrs) -> PathBuf {
+    attrs
+        .get(ATTR_HREF)
+        .map(|attr| {
+            let mut path = PathBuf::new();
+            path.extend(attr.split('/'));
+            if !path.is_absolute() {
+                path = html_dir.join(path);
+            }
+            if !path.ends_with("spago.yaml") {
+                path = path.join("spago.yaml");
+            }
+            path
+        })
+        .unwrap_or_else(|| html_dir.join("spago.yaml"))
 }
 
-impl SpagoMetadata {
-    /// Create a new instance by parsing a spago.yaml file.
-    pub async fn new(manifest_path: &Path) -> Result<Self> {
-        let file = File::open(manifest_path)
-            .with_context(|| format!("error opening spago manifest {}", manifest_path.display()))?;
-        let reader = BufReader::new(file);
-        let mut metadata: SpagoMetadata = serde_yaml::from_reader(reader)
-            .with_context(|| format!("error parsing spago manifest {}", manifest_path.display()))?;
+/// Find the spago manifest of the main application of the build target, along with the package
+/// selected by its link.
+///
+/// Falls back to the `spago.yaml` next to the build target, like builds do if the page has no
+/// PureScript link.
+pub async fn page_manifest(cfg: Arc<RtcBuild>) -> Result<(PathBuf, Option<String>)> {
+    let html_dir = cfg.target_parent.clone();
+    if !path_exists(&cfg.target).await? {
+        return Ok((html_dir.join("spago.yaml"), None));
+    }
 
-        metadata.manifest_path = manifest_path.to_path_buf();
-        metadata.workspace_root = manifest_path
-            .parent()
-            .ok_or_else(|| anyhow!("manifest path has no parent directory"))?
-            .to_path_buf();
+    let html = include::read_page(cfg.clone(), cfg.target.clone()).await?;
+    let document = Document::new(
+        html,
+        DocumentOptions {
+            allow_self_closing_script: cfg.allow_self_closing_script,
+        },
+    )?;
+    let mut attrs = None;
+    document.select(
+        r#"link[data-prank][rel="purescript"]:not([data-type="worker"])"#,
+        |el| {
+            if attrs.is_none() {
+                attrs = Some(el.attributes().iter().fold(Attrs::new(), |mut acc, attr| {
+                    acc.insert(attr.name(), attr.value().into());
+                    acc
+                }));
+            }
+            Ok(())
+        },
+    )?;
 
-        Ok(metadata)
-    }
+    let attrs = attrs.unwrap_or_default();
+    let package = attrs.get("data-package").map(|attr| attr.to_string());
+    Ok((manifest_path(&html_dir, &attrs), package))
+}
 
-    /// Spago's default output directory, relative to the workspace root.
-    pub fn target_directory(&self) -> PathBuf {
-        self.workspace_root.join("output")
-    }
+/// Check if a path is a spago manifest or lock file, which define the dependencies.
+fn is_manifest(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name == "spago.yaml" || name == "spago.lock")
 }
 
 /// A PureScript application pipeline.
@@ -100,6 +188,59 @@ pub struct PureScriptApp {
     initializer: Option<PathBuf>,
     /// Paths that have changed in the current build cycle.
     changed_paths: Vec<PathBuf>,
+    /// Write the release build as a graph of shared modules, instead of a self-contained bundle.
+    shared_modules: bool,
+    /// The helpers kept between the builds of the watch system.
+    session: DevSession,
+    /// The modules to emit as lazily loaded chunks in release builds.
+    split: Vec<String>,
+    /// Check that `main` is an `Effect`, which fails for type synonyms.
+    check_main_type: bool,
+}
+
+/// The helpers kept between the builds of the watch system, to speed up dev builds.
+#[derive(Clone, Debug, Default)]
+pub struct DevSession {
+    /// The `purs ide server` to rebuild changed modules with, instead of running `spago build`.
+    pub purs_ide: Option<Arc<PursIde>>,
+    /// The bundler linking the modules of the output directory into a single dev bundle.
+    pub dev_bundler: Option<Arc<DevBundler>>,
+    /// The output directory to serve in dev mode, as found by the builds.
+    pub output_dir: Option<Arc<OutputDir>>,
+}
+
+/// The PureScript output directory of the applications, shared with the dev server.
+///
+/// The output directory is configured in the `spago.yaml` of the application's workspace, which
+/// is only known once the HTML file was processed by a build.
+#[derive(Debug, Default)]
+pub struct OutputDir(std::sync::RwLock<Option<PathBuf>>);
+
+impl OutputDir {
+    /// Create a new instance, the directory is unknown until the first build.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The output directory found by the last build.
+    pub fn get(&self) -> Option<PathBuf> {
+        self.0.read().ok().and_then(|dir| dir.clone())
+    }
+
+    fn set(&self, dir: PathBuf) {
+        if let Ok(mut current) = self.0.write() {
+            *current = Some(dir);
+        }
+    }
+}
+
+/// The outcome of compiling the PureScript sources of an application.
+#[derive(Debug, Default)]
+struct Compilation {
+    /// The modules to hot replace, `None` if the page needs to be reloaded.
+    hot_modules: Option<BTreeSet<String>>,
+    /// The warnings of the compiler.
+    warnings: Vec<Diagnostic>,
 }
 
 /// Describes how the purescript application is used.
@@ -107,8 +248,8 @@ pub struct PureScriptApp {
 pub enum PureScriptAppType {
     /// Used as the main application.
     Main,
-    // /// Used as a web worker.
-    // Worker,
+    /// Used as a web worker.
+    Worker,
 }
 
 impl FromStr for PureScriptAppType {
@@ -117,6 +258,7 @@ impl FromStr for PureScriptAppType {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "main" => Ok(PureScriptAppType::Main),
+            "worker" => Ok(PureScriptAppType::Worker),
             _ => bail!(
                 r#"unknown `data-type="{}"` value for <link data-prank rel="purescript" .../> attr; please ensure the value is lowercase and is a supported type"#,
                 s
@@ -132,24 +274,12 @@ impl PureScriptApp {
         cfg: Arc<RtcBuild>,
         html_dir: Arc<PathBuf>,
         ignore_chan: Option<mpsc::Sender<Vec<PathBuf>>>,
+        session: DevSession,
         attrs: Attrs,
         id: usize,
         changed_paths: Vec<PathBuf>,
     ) -> Result<Self> {
-        let manifest_href = attrs
-            .get(ATTR_HREF)
-            .map(|attr| {
-                let mut path = PathBuf::new();
-                path.extend(attr.split('/'));
-                if !path.is_absolute() {
-                    path = html_dir.join(path);
-                }
-                if !path.ends_with("spago.yaml") {
-                    path = path.join("spago.yaml");
-                }
-                path
-            })
-            .unwrap_or_else(|| html_dir.join("spago.yaml"));
+        let manifest_href = manifest_path(&html_dir, &attrs);
 
         let main_module = attrs.get("data-main").map(|attr| attr.to_string());
 
@@ -158,6 +288,10 @@ impl PureScriptApp {
             .map(|attr| attr.parse())
             .transpose()?
             .unwrap_or(PureScriptAppType::Main);
+        ensure!(
+            app_type != PureScriptAppType::Worker || main_module.is_some(),
+            r#"<link data-prank rel="purescript" data-type="worker" .../> requires a `data-main` attribute naming the worker's main module"#
+        );
         let cross_origin = attrs
             .get("data-cross-origin")
             .map(|attr| CrossOrigin::from_str(attr))
@@ -165,7 +299,8 @@ impl PureScriptApp {
             .unwrap_or_default();
         let integrity = IntegrityType::from_attrs(&attrs, &cfg)?;
 
-        let manifest = SpagoMetadata::new(&manifest_href).await?;
+        let package = attrs.get("data-package").map(|attr| attr.to_string());
+        let manifest = SpagoMetadata::new(&manifest_href, package.as_deref()).await?;
         let id = Some(id);
 
         // spago profile
@@ -211,10 +346,11 @@ impl PureScriptApp {
 
         // skip
         let skip_build = attrs.contains_key("data-prank-skip");
+        let check_main_type = !attrs.contains_key("data-no-main-type-check");
 
         // progress function
         let initializer = attrs
-            .get("data-initializer")
+            .get(ATTR_INITIALIZER)
             .map(|path| PathBuf::from_str(path))
             .transpose()?
             .map(|path| {
@@ -227,6 +363,16 @@ impl PureScriptApp {
 
         let target_path = data_target_path(&attrs)?;
 
+        // split points
+        let split = match attrs.get("data-split") {
+            Some(split) => split
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|module| !module.is_empty())
+                .map(str::to_string)
+                .collect(),
+            None => cfg.split.clone(),
+        };
+
         // done
         Ok(Self {
             id,
@@ -243,6 +389,10 @@ impl PureScriptApp {
             sri: SriBuilder::new(integrity),
             initializer,
             changed_paths,
+            shared_modules: false,
+            session,
+            split,
+            check_main_type,
         })
     }
 
@@ -254,6 +404,7 @@ impl PureScriptApp {
         cfg: Arc<RtcBuild>,
         html_dir: Arc<PathBuf>,
         ignore_chan: Option<mpsc::Sender<Vec<PathBuf>>>,
+        session: DevSession,
     ) -> Result<Option<Self>> {
         let path = html_dir.join("spago.yaml");
 
@@ -262,7 +413,7 @@ impl PureScriptApp {
             return Ok(None);
         }
 
-        let manifest = SpagoMetadata::new(&path).await?;
+        let manifest = SpagoMetadata::new(&path, None).await?;
         let integrity = IntegrityType::default_unless(cfg.no_sri);
 
         Ok(Some(Self {
@@ -270,6 +421,7 @@ impl PureScriptApp {
             skip_build: false,
             spago_features: cfg.spago_features.clone(),
             spago_profile: cfg.spago_profile.clone(),
+            split: cfg.split.clone(),
             cfg,
             manifest,
             ignore_chan,
@@ -280,9 +432,73 @@ impl PureScriptApp {
             sri: SriBuilder::new(integrity),
             initializer: None,
             changed_paths: vec![],
+            shared_modules: false,
+            session,
+            check_main_type: true,
         }))
     }
 
+    /// Is this the main application (and not a worker)?
+    pub fn is_main(&self) -> bool {
+        self.app_type == PureScriptAppType::Main
+    }
+
+    /// Share code with other applications of the same page.
+    ///
+    /// Release builds will then emit a graph of content addressed modules, instead of a single
+    /// bundle. Modules used by several applications end up in the same file, and so are only
+    /// loaded once.
+    pub fn share_modules(&mut self) {
+        self.shared_modules = true;
+    }
+
+    /// Whether the release build is split into lazily loaded chunks.
+    ///
+    /// Only the main application is split, workers are always bundled.
+    fn splits_chunks(&self) -> bool {
+        self.is_main() && !self.split.is_empty()
+    }
+
+    /// Warn about split points which did not end up as chunks of their own.
+    fn check_split_points(&self, chunks: &[Chunk]) {
+        for module in &self.split {
+            if !chunks.iter().any(|chunk| &chunk.module == module) {
+                tracing::warn!("split point {module} is not a module only imported dynamically by {}, so it is not a chunk of its own", self.main_module());
+            }
+        }
+    }
+
+    /// The main module of the application.
+    fn main_module(&self) -> &str {
+        let bundle = self.manifest.package.bundle.as_ref();
+        self.main_module
+            .as_deref()
+            .or_else(|| bundle.and_then(|bundle| bundle.module.as_deref()))
+            .unwrap_or("Main")
+    }
+
+    /// Check that the main module was compiled and exports `main`, before bundling or serving it.
+    async fn verify_main(&self) -> Result<()> {
+        let output_dir = self.manifest.target_directory();
+        let module = self.main_module().to_string();
+        let check_type = self.check_main_type;
+        tokio::task::spawn_blocking(move || externs::verify_main(&output_dir, &module, check_type))
+            .await
+            .context("error awaiting main module check")?
+    }
+
+    /// Check if the release bundle is a module exporting `main`, as configured in the `bundle`
+    /// section of the package.
+    fn bundles_module(&self) -> bool {
+        self.app_type == PureScriptAppType::Main
+            && self
+                .manifest
+                .package
+                .bundle
+                .as_ref()
+                .is_some_and(|bundle| bundle.r#type == SpagoBundleType::Module)
+    }
+
     /// Spawn a new pipeline.
     #[tracing::instrument(level = "trace", skip(self))]
     pub fn spawn(self) -> JoinHandle<Result<PrankAssetPipelineOutput>> {
@@ -296,16 +512,100 @@ impl PureScriptApp {
         }
 
         // 1. Conditionally run `spago build`
+        let mut compilation = Compilation::default();
         if self.should_run_spago_build() {
-            self.spago_build().await.context("running spago build")?;
+            compilation = self.spago_build().await.context("running spago build")?;
         } else {
             tracing::debug!("Skipping spago build as no relevant PureScript or FFI files changed.");
         }
+        self.verify_main()
+            .await
+            .context("checking the main module")?;
 
         let (bundle_name, bundle_dest_path): (String, PathBuf);
         let mut dev_mode_run_script_option: Option<String> = None;
+        let mut call_main = false;
+
+        let mut chunks = Vec::new();
+        let mut report = None;
+
+        if self.cfg.release && (self.shared_modules || self.splits_chunks()) {
+            // RELEASE MODE, sharing code with other applications or splitting it into chunks
+            // 2. Run `purs-backend-es build`, and write the module graph of the main module
+            if self.cfg.source_maps.enabled() {
+                tracing::warn!(
+                    "source maps are only created for single bundles, not for the shared modules or chunks of {}",
+                    self.main_module()
+                );
+            }
 
-        if self.cfg.release {
+            let (modules, graph_chunks) = self
+                .purs_link()
+                .await
+                .context("linking purs-backend-es modules")?;
+            let (entry, shared) = graph_chunks
+                .main
+                .split_last()
+                .ok_or_else(|| anyhow!("module graph has no entry module"))?;
+
+            // 3. Preload every module of the main chunk, not just the entry point
+            for index in shared {
+                let module = &modules[*index];
+                let (name, _) = self.dist_target(&module.file_name).await?;
+                self.sri
+                    .record_file(
+                        SriType::ModulePreload,
+                        name,
+                        SriOptions::default(),
+                        &module.path,
+                    )
+                    .await?;
+            }
+
+            // 4. Record the lazily loaded chunks, their integrity is declared by an import map, so
+            // they are not loaded before they get imported
+            for lazy in &graph_chunks.lazy {
+                let mut files = Vec::with_capacity(lazy.modules.len());
+                for index in &lazy.modules {
+                    let module = &modules[*index];
+                    let (name, _) = self.dist_target(&module.file_name).await?;
+                    self.sri
+                        .record_file(
+                            SriType::ImportMap,
+                            &name,
+                            SriOptions::default(),
+                            &module.path,
+                        )
+                        .await?;
+                    files.push(name);
+                }
+                let (file, _) = self.dist_target(&modules[lazy.entry].file_name).await?;
+                chunks.push(Chunk {
+                    module: modules[lazy.entry].name.clone(),
+                    file,
+                    files,
+                });
+            }
+            self.check_split_points(&chunks);
+
+            if self.cfg.report {
+                let mut files = Vec::with_capacity(modules.len());
+                for module in &modules {
+                    files.push(self.dist_target(&module.file_name).await?);
+                }
+                let written = modules
+                    .iter()
+                    .map(|module| (module.source_path.clone(), module.path.clone()))
+                    .collect();
+                report = Some(self.report(files, ModuleBytes::Files(written)).await?);
+            }
+
+            let entry = &modules[*entry];
+            let (entry_href, _) = self.dist_target(&entry.file_name).await?;
+            bundle_name = entry_href;
+            bundle_dest_path = entry.path.clone();
+            call_main = true;
+        } else if self.cfg.release {
             // RELEASE MODE
             // 2. Run `purs-backend-es bundle`
 
@@ -316,65 +616,133 @@ impl PureScriptApp {
 
             // 3. Hash, copy, and minify the single bundle
             let hashed_bundle_name = self.hashed_name(&bundle_path).await?;
-            let dest_path = self.cfg.staging_dist.join(&hashed_bundle_name);
+            let (bundle_href, dest_path) = self.dist_target(&hashed_bundle_name).await?;
+
+            // The bundler already minified the bundle, when creating a source map of it.
+            match self.cfg.source_maps.enabled() {
+                true => {
+                    // The map is served next to the bundle, once the staging dist is published.
+                    let final_dir = self.cfg.final_dist.join(&bundle_href);
+                    let final_dir = final_dir.parent().unwrap_or(&self.cfg.final_dist);
+                    source_map::copy_with_source_map(
+                        &bundle_path,
+                        &dest_path,
+                        final_dir,
+                        self.cfg.source_maps,
+                    )
+                    .await
+                    .context("error copying JS bundle and source map")?
+                }
+                // The source map of the report is not published.
+                false if self.cfg.report => {
+                    source_map::copy_without_source_map(&bundle_path, &dest_path)
+                        .await
+                        .context("error copying JS bundle")?
+                }
+                false => self
+                    .copy_or_minify_js(&bundle_path, &dest_path, TopLevelMode::Module)
+                    .await
+                    .context("error minifying or copying JS bundle")?,
+            }
 
-            self.copy_or_minify_js(&bundle_path, &dest_path, TopLevelMode::Module)
-                .await
-                .context("error minifying or copying JS bundle")?;
+            if self.cfg.report {
+                let files = vec![(bundle_href.clone(), dest_path.clone())];
+                report = Some(self.report(files, ModuleBytes::Bundle(bundle_path)).await?);
+            }
 
-            bundle_name = hashed_bundle_name;
+            bundle_name = bundle_href;
             bundle_dest_path = dest_path;
+            call_main = self.bundles_module();
         } else {
             // DEV MODE
-            // 2. We assume the server is configured to serve `manifest.target_directory()`
-            // at the URL path `/output`.
+            // 2. The server serves `manifest.target_directory()` at the output mount path,
+            // below the public URL.
 
             // 3. Determine the entry point path.
-            let main_module = self.main_module.as_deref().unwrap_or("Main");
-            // The relative path to the entry file (e.g., "output/Main/index.js")
-            let entry_path = PathBuf::from("output").join(main_module).join("index.js");
+            let main_module = self.main_module();
 
             // The full path to the *original file* in the project's output dir
-            bundle_dest_path = self
+            let module_path = self
                 .manifest
                 .target_directory()
                 .join(main_module)
                 .join("index.js");
 
-            if !path_exists(&bundle_dest_path).await? {
-                bail!("spago build succeeded, but main module entry point was not found at {}. Is `data-main` attribute correct?", bundle_dest_path.display());
+            if !path_exists(&module_path).await? {
+                bail!("spago build succeeded, but main module entry point was not found at {}. Is `data-main` attribute correct?", module_path.display());
             }
 
-            // The "name" to pass to the HTML is the relative path, using URL separators.
-            bundle_name = entry_path.to_string_lossy().replace('\\', "/");
+            // The "name" to pass to the HTML is the relative path (e.g., "output/Main/index.js")
+            let mut entry_href = format!("{}/{main_module}/index.js", self.cfg.output_mount);
+            let mut entry_url = format!("{}{entry_href}", self.cfg.public_url);
+            let mut entry_dest_path = module_path;
+
+            // 4. Optionally link the modules into a single bundle, served from the dist dir.
+            if let Some(bundler) = self.session.dev_bundler.clone() {
+                match self.dev_bundle(bundler).await {
+                    Ok((href, path)) => {
+                        entry_url = format!("{}{href}", self.cfg.public_url);
+                        entry_href = href;
+                        entry_dest_path = path;
+                        // modules of the bundle can't be replaced, so reload the page instead
+                        compilation.hot_modules = None;
+                    }
+                    Err(err) => tracing::warn!(
+                        "error creating the dev bundle, serving the output modules instead: {err:#}"
+                    ),
+                }
+            }
 
             // The script to run the main module in dev mode.
-            let dev_mode_run_script = format!(
-                r#"import {{ main }} from '/{bundle_name}';
-main();"#
-            );
-
-            dev_mode_run_script_option = Some(dev_mode_run_script);
+            let dev_mode_run_script = dev_run_script(&entry_url);
+
+            match self.app_type {
+                PureScriptAppType::Main => {
+                    // Register the entry point, to be re-imported on hot module replacement.
+                    dev_mode_run_script_option = Some(format!(
+                        "{dev_mode_run_script}\n(window.prankEntries ??= []).push('{entry_url}');"
+                    ));
+                    bundle_name = entry_href;
+                    bundle_dest_path = entry_dest_path;
+                }
+                PureScriptAppType::Worker => {
+                    (bundle_name, bundle_dest_path) =
+                        self.write_worker_entry(&dev_mode_run_script).await?;
+                }
+            }
         }
 
-        // 4. Build SRI for the entry point (bundle or main.js) and create output
-        let output = self
-            .build_sri_and_output(&bundle_name, &bundle_dest_path, dev_mode_run_script_option)
+        // 5. Build SRI for the entry point (bundle or main.js) and create output
+        let mut output = self
+            .build_sri_and_output(
+                &bundle_name,
+                &bundle_dest_path,
+                dev_mode_run_script_option,
+                call_main,
+            )
             .await
             .context("processing final JS")?;
+        output.hot_modules = compilation.hot_modules;
+        output.warnings = compilation.warnings;
+        output.chunks = chunks;
+        output.report = report;
 
         tracing::debug!("purescript build complete");
         Ok(PrankAssetPipelineOutput::PureScriptApp(output))
     }
 
     /// Run `spago build` to compile .purs to .js
+    ///
+    /// If the changes of this build cycle can be hot replaced, the modules to replace are returned.
     #[tracing::instrument(level = "trace", skip(self))]
-    async fn spago_build(&mut self) -> Result<()> {
-        tracing::debug!("building {}", &self.manifest.package.name);
+    async fn spago_build(&mut self) -> Result<Compilation> {
+        tracing::debug!(
+            "building {} ({})",
+            &self.manifest.package.name,
+            self.manifest.manifest_path.display()
+        );
 
         // Spawn the spago build process.
-        let mut args = vec!["build"];
-
         if let Some(_profile) = &self.spago_profile {
             // No profiles
             // args.push("--profile");
@@ -383,46 +751,268 @@ main();"#
             // We build release bundles via purs-backend-es
             // args.push("--release");
         }
-        if self.cfg.offline {
-            args.push("--offline");
+        let mut args = spago_args("build", &self.cfg, &self.manifest);
+        args.push("--json-errors");
+
+        let hot = self.is_hot_update();
+        let output_dir = self.manifest.target_directory();
+        let build_res = {
+            let _lock = COMPILER_LOCK.lock().await;
+            // A changed environment module must be compiled along with the changed sources.
+            let env_changed = self.write_env_module().await?;
+            // The warnings of the modules which won't be compiled again.
+            let mut kept_warnings = match self.cfg.purescript_warnings {
+                PureScriptWarnings::Allow => None,
+                _ => Some(Warnings::load(&output_dir)),
+            };
+            // Compare the output before and after the build, to find the compiled modules.
+            let before = match hot || kept_warnings.as_ref().is_some_and(|kept| !kept.is_empty()) {
+                true => Some(self.output_hashes().await?),
+                false => None,
+            };
+            let incremental_files = self.incremental_files().filter(|_| !env_changed);
+            let res = match (&self.session.purs_ide, incremental_files) {
+                (Some(purs_ide), Some(files)) => purs_ide
+                    .rebuild(
+                        &self.cfg,
+                        &self.manifest.workspace_root,
+                        &output_dir,
+                        &files,
+                    )
+                    .await
+                    .context("error during incremental build"),
+                (purs_ide, _) => {
+                    let res = self
+                        .run_spago_build(&args)
+                        .await
+                        .context("error during spago build execution");
+                    if let Some(purs_ide) = purs_ide {
+                        purs_ide.invalidate(&self.manifest.workspace_root).await;
+                    }
+                    res
+                }
+            };
+            match res {
+                Ok(warnings) => {
+                    let compiled = match before {
+                        Some(before) => self.compiled_modules(before).await?,
+                        None => BTreeSet::new(),
+                    };
+                    let warnings = match &mut kept_warnings {
+                        Some(kept) => {
+                            let warnings =
+                                kept.update(&self.manifest.workspace_root, warnings, &compiled);
+                            kept.save(&output_dir)?;
+                            warnings
+                        }
+                        None => warnings,
+                    };
+                    let hot_modules = match hot {
+                        true => Some(self.hot_modules(compiled).await?),
+                        false => None,
+                    };
+                    Ok(Compilation {
+                        hot_modules,
+                        warnings,
+                    })
+                }
+                Err(err) => Err(err),
+            }
+        };
+
+        // Share spago's target dir with the dev server.
+        if let Some(output_dir) = &self.session.output_dir {
+            output_dir.set(self.manifest.target_directory());
         }
-        if self.cfg.frozen {
-            // Frozen is the default
-            // args.push("--frozen");
+
+        // Send spago's target dir over to the watcher to be ignored.
+        if let Some(chan) = &self.ignore_chan {
+            if let Ok(target_dir) = self.manifest.target_directory().canonicalize() {
+                let target_dir_recursive = target_dir.join("**");
+                let _ = chan.send(vec![target_dir, target_dir_recursive]).await;
+            }
         }
-        if self.cfg.locked {
-            args.push("--pure");
+
+        let mut compilation = build_res?;
+        compilation.warnings = self.check_warnings(compilation.warnings)?;
+        Ok(compilation)
+    }
+
+    /// Write the `Prank.Env` module into the sources of the package, if enabled.
+    ///
+    /// Returns `true` if the module changed since the last build.
+    async fn write_env_module(&self) -> Result<bool> {
+        if !self.cfg.env_module {
+            return Ok(false);
         }
 
-        let build_res = common::run_command("spago", "spago", &args, &self.cfg.working_directory)
+        let features = self
+            .spago_features
+            .enabled(&self.cfg.default_features, &self.cfg.available_features);
+        if !self.cfg.available_features.is_empty() {
+            for feature in features
+                .iter()
+                .filter(|feature| !self.cfg.available_features.contains(feature))
+            {
+                log::warn!("feature '{feature}' is not one of the available features");
+            }
+        }
+
+        let module = EnvModule {
+            features,
+            release: self.cfg.release,
+            public_url: self.cfg.public_url.to_string(),
+            env: EnvModule::public_env(),
+        };
+        let workspace_root = &self.manifest.workspace_root;
+        let changed = module
+            .write(workspace_root)
             .await
-            .context("error during spago build execution");
+            .context("error writing the Prank.Env module")?;
+
+        // Send the generated files over to the watcher to be ignored.
+        if let Some(chan) = &self.ignore_chan {
+            let (purs_path, js_path) = EnvModule::paths(workspace_root);
+            let paths = [purs_path, js_path]
+                .iter()
+                .filter_map(|path| path.canonicalize().ok())
+                .collect();
+            let _ = chan.send(paths).await;
+        }
 
-        // Send spago's target dir over to the watcher to be ignored.
-        if let Some(chan) = &mut self.ignore_chan {
-            if let Ok(target_dir) = self.manifest.target_directory().canonicalize() {
-                let target_dir_recursive = target_dir.join("**");
-                let _ = chan.try_send(vec![target_dir, target_dir_recursive]);
+        Ok(changed)
+    }
+
+    /// Apply the warnings settings of the build to the warnings of the compiler.
+    ///
+    /// Censored warnings are dropped, and denied warnings fail the build.
+    fn check_warnings(&self, warnings: Vec<Diagnostic>) -> Result<Vec<Diagnostic>> {
+        let warnings: Vec<_> = warnings
+            .into_iter()
+            .filter(|warning| !self.cfg.purescript_censor_codes.contains(&warning.code))
+            .collect();
+        match self.cfg.purescript_warnings {
+            PureScriptWarnings::Allow => Ok(vec![]),
+            PureScriptWarnings::Deny if !warnings.is_empty() => {
+                Err(CompilerErrors(warnings).into())
+            }
+            PureScriptWarnings::Deny | PureScriptWarnings::Warn => Ok(warnings),
+        }
+    }
+
+    /// Run `spago build`, turning the errors reported by the compiler into [`CompilerErrors`].
+    ///
+    /// The warnings of the compiler are returned.
+    async fn run_spago_build(&self, args: &[&str]) -> Result<Vec<Diagnostic>> {
+        let root = &self.manifest.workspace_root;
+        // Log anything but the compiler's JSON output, while spago is running.
+        let mut json = String::new();
+        let status = common::run_command_with_output("spago", "spago", args, root, |line| {
+            let (OutputLine::Stdout(line) | OutputLine::Stderr(line)) = line;
+            match line.trim_start().starts_with('{') {
+                true => {
+                    json.push_str(line);
+                    json.push('\n');
+                }
+                false => tracing::info!("{line}"),
             }
+        })
+        .await?;
+
+        let output = diagnostics::parse_json_output(&json, root);
+        if status.success() {
+            return Ok(output.warnings);
         }
+        if output.errors.is_empty() {
+            bail!("spago call with args: '{args:?}' returned a bad status: {status}");
+        }
+        Err(CompilerErrors(output.errors).into())
+    }
 
-        build_res?;
-        Ok(())
+    /// Check if the changes of this build cycle can be hot replaced, instead of reloading the page.
+    ///
+    /// That's the case for the main application in dev mode, if only its sources changed.
+    fn is_hot_update(&self) -> bool {
+        !self.cfg.release
+            && self.app_type == PureScriptAppType::Main
+            && !self.changed_paths.is_empty()
+            && self.changed_paths.iter().all(|path| {
+                path.extension().is_some_and(|ext| ext == "purs") || self.manifest.is_source(path)
+            })
+    }
+
+    /// The content hashes of the modules in spago's output directory.
+    async fn output_hashes(&self) -> Result<HashMap<String, u64>> {
+        let output_dir = self.manifest.target_directory();
+        tokio::task::spawn_blocking(move || graph::module_hashes(&output_dir))
+            .await
+            .context("error awaiting output hashes")?
+    }
+
+    /// The modules which changed since the `before` snapshot, as they were compiled.
+    async fn compiled_modules(&self, before: HashMap<String, u64>) -> Result<BTreeSet<String>> {
+        let output_dir = self.manifest.target_directory();
+        tokio::task::spawn_blocking(move || {
+            Ok(graph::changed_modules(
+                &before,
+                &graph::module_hashes(&output_dir)?,
+            ))
+        })
+        .await
+        .context("error awaiting changed modules")?
     }
 
-    /// Check if any of the changed paths are PureScript or FFI files.
+    /// The changed modules to hot replace, along with their dependents.
+    async fn hot_modules(&self, changed: BTreeSet<String>) -> Result<BTreeSet<String>> {
+        let output_dir = self.manifest.target_directory();
+        tokio::task::spawn_blocking(move || graph::with_dependents(&output_dir, changed))
+            .await
+            .context("error awaiting dependent modules")?
+    }
+
+    /// The PureScript modules to rebuild through `purs ide`, if the changes of this build cycle
+    /// allow for an incremental build.
+    ///
+    /// Only changed PureScript and FFI files can be rebuilt one by one. A change of the spago
+    /// manifest, the dependencies, or a removed module requires a full `spago build`.
+    fn incremental_files(&self) -> Option<Vec<PathBuf>> {
+        if self.cfg.release {
+            return None;
+        }
+
+        let mut files = BTreeSet::new();
+        for path in &self.changed_paths {
+            if is_manifest(path) || path.components().any(|c| c.as_os_str() == ".spago") {
+                return None;
+            }
+            let module = match path.extension() {
+                Some(ext) if ext == "purs" => path.clone(),
+                // FFI files are compiled along with the module next to them
+                Some(ext) if ext == "js" && self.manifest.is_source(path) => {
+                    path.with_extension("purs")
+                }
+                _ => continue,
+            };
+            if !module.is_file() {
+                return None;
+            }
+            files.insert(module);
+        }
+
+        (!files.is_empty()).then(|| files.into_iter().collect())
+    }
+
+    /// Check if any of the changed paths are PureScript or FFI files, or spago manifests.
     fn should_run_spago_build(&self) -> bool {
         if self.changed_paths.is_empty() {
             return true;
         }
         for path in &self.changed_paths {
-            if path.extension().is_some_and(|ext| ext == "purs") {
+            if path.extension().is_some_and(|ext| ext == "purs") || is_manifest(path) {
                 return true;
             }
-            // Check for JS files in `src/` (assuming FFI files are here)
-            if path.extension().is_some_and(|ext| ext == "js")
-                && path.components().any(|c| c.as_os_str() == "src")
-            {
+            // Check for FFI files of the package
+            if self.manifest.is_source(path) {
                 return true;
             }
         }
@@ -437,10 +1027,51 @@ main();"#
             .await
             .context("error creating bundle output directory")?;
 
-        let bundle_path = bundle_dir.join("index.js");
+        let bundle = self.manifest.package.bundle.clone().unwrap_or_default();
+
+        // Workers are bundled from their own main module, next to the application bundle.
+        let (bundle_path, main_module) = match self.app_type {
+            PureScriptAppType::Main => {
+                let file_name = bundle
+                    .outfile
+                    .as_deref()
+                    .and_then(Path::file_name)
+                    .unwrap_or("index.js".as_ref());
+                (bundle_dir.join(file_name), self.main_module())
+            }
+            PureScriptAppType::Worker => {
+                let main_module = self
+                    .main_module
+                    .as_deref()
+                    .ok_or_else(|| anyhow!("worker requires a main module"))?;
+                (bundle_dir.join(format!("{main_module}.js")), main_module)
+            }
+        };
         let bundle_path_str = bundle_path.to_string_lossy();
 
-        let args = vec!["bundle-app", "--to", &bundle_path_str];
+        let command = match self.bundles_module() {
+            true => "bundle-module",
+            false => "bundle-app",
+        };
+        let mut args = vec![command, "--to", &bundle_path_str, "--main", main_module];
+        if let Some(platform) = &bundle.platform {
+            args.extend(["--platform", platform]);
+        }
+        // Minifying the bundle afterwards would invalidate the source map, so let the bundler
+        // minify it instead. The report attributes the bytes of the bundle using its source map.
+        let source_maps = self.cfg.source_maps.enabled() || self.cfg.report;
+        if source_maps {
+            args.push("--source-maps");
+        }
+        if bundle.minify || (source_maps && self.cfg.should_minify()) {
+            args.push("--minify");
+        }
+        let corefn_dir = self.manifest.corefn_dir();
+        if let Some(corefn_dir) = &corefn_dir {
+            args.extend(["--corefn-dir", corefn_dir]);
+        }
+        args.extend(self.manifest.backend_args());
+        args.extend(bundle.extra_args.iter().map(String::as_str));
 
         tracing::debug!("bundling with purs-backend-es");
 
@@ -453,13 +1084,16 @@ main();"#
         )
         .await?;
 
-        common::run_command(
-            Application::PureScriptBackendEs.name(),
-            &purs_backend_es,
-            &args,
-            &self.cfg.core.working_directory,
-        )
-        .await?;
+        {
+            let _lock = COMPILER_LOCK.lock().await;
+            common::run_command(
+                Application::PureScriptBackendEs.name(),
+                &purs_backend_es,
+                &args,
+                &self.manifest.workspace_root,
+            )
+            .await?;
+        }
 
         if !path_exists(&bundle_path).await? {
             bail!(
@@ -471,6 +1105,117 @@ main();"#
         Ok(bundle_path)
     }
 
+    /// Run `purs-backend-es build`, and write the module graph of the main module to the dist dir
+    /// (for release mode, when sharing modules).
+    ///
+    /// The written modules are returned in dependency order, the entry module being the last one,
+    /// along with their grouping into chunks.
+    #[tracing::instrument(level = "trace", skip(self))]
+    async fn purs_link(&self) -> Result<(Vec<graph::WrittenModule>, graph::Chunks)> {
+        tracing::debug!("building ES modules with purs-backend-es");
+
+        let version = self.cfg.tools.purescript_backend_es.as_deref();
+        let purs_backend_es = tools::get(
+            Application::PureScriptBackendEs,
+            version,
+            self.cfg.offline,
+            &self.cfg.client_options(),
+        )
+        .await?;
+
+        let output_dir = self.manifest.backend_output_dir();
+        let dist_dir = self.dist_dir().await?;
+        let entry = PathBuf::from(self.main_module()).join("index.js");
+        let split = match self.splits_chunks() {
+            true => self
+                .split
+                .iter()
+                .map(|module| PathBuf::from(module).join("index.js"))
+                .collect(),
+            false => vec![],
+        };
+        let (hash, minify) = (self.cfg.filehash, self.cfg.should_minify());
+
+        // Other applications of the page share the output, so keep it locked until written.
+        let _lock = COMPILER_LOCK.lock().await;
+        let mut args = vec!["build"];
+        let corefn_dir = self.manifest.corefn_dir();
+        if let Some(corefn_dir) = &corefn_dir {
+            args.extend(["--corefn-dir", corefn_dir]);
+        }
+        args.extend(self.manifest.backend_args());
+        common::run_command(
+            Application::PureScriptBackendEs.name(),
+            &purs_backend_es,
+            &args,
+            &self.manifest.workspace_root,
+        )
+        .await?;
+
+        tokio::task::spawn_blocking(move || {
+            let graph = graph::ModuleGraph::load(&output_dir, &entry)?;
+            let chunks = graph.chunks(&split);
+            Ok((graph.write(&dist_dir, hash, minify)?, chunks))
+        })
+        .await
+        .context("error awaiting module graph")?
+    }
+
+    /// Create the bundle size report of a release build, whose code was written to the given files
+    /// (hrefs and paths) of the dist dir.
+    async fn report(
+        &self,
+        files: Vec<(String, PathBuf)>,
+        bytes: ModuleBytes,
+    ) -> Result<BundleReport> {
+        let output_dir = self.manifest.backend_output_dir();
+        let entry = PathBuf::from(self.main_module()).join("index.js");
+        let main_module = self.main_module().to_string();
+
+        // Other applications of the page might rebuild the output meanwhile.
+        let _lock = COMPILER_LOCK.lock().await;
+        tokio::task::spawn_blocking(move || {
+            BundleReport::new(&output_dir, &entry, &main_module, files, bytes)
+        })
+        .await
+        .context("error awaiting bundle report")?
+        .context("error creating bundle report")
+    }
+
+    /// Write the bootstrap code of a worker in dev mode into a worker entry module of its own.
+    ///
+    /// A worker can't be bootstrapped by an inline script, like the main application. Returns the
+    /// href and the path of the entry module.
+    async fn write_worker_entry(&self, run_script: &str) -> Result<(String, PathBuf)> {
+        let (href, path) = self
+            .dist_target(&format!("{}.worker.js", self.main_module()))
+            .await?;
+        fs::write(&path, run_script)
+            .await
+            .context("error writing worker entry module")?;
+        Ok((href, path))
+    }
+
+    /// Link the modules of the main module into a single dev bundle, written to the dist dir.
+    ///
+    /// Returns the href and the path of the bundle.
+    async fn dev_bundle(&self, bundler: Arc<DevBundler>) -> Result<(String, PathBuf)> {
+        let main_module = self.main_module();
+        let output_dir = self.manifest.target_directory();
+        let entry = PathBuf::from(main_module).join("index.js");
+        let public_path = format!("{}{}/", self.cfg.public_url, self.cfg.output_mount);
+        let bundle =
+            tokio::task::spawn_blocking(move || bundler.bundle(&output_dir, &entry, &public_path))
+                .await
+                .context("error awaiting dev bundle")??;
+
+        let (href, path) = self.dist_target(&format!("{main_module}.dev.js")).await?;
+        fs::write(&path, bundle)
+            .await
+            .context("error writing dev bundle")?;
+        Ok((href, path))
+    }
+
     /// create a cache busting hashed name based on a path, if enabled
     async fn hashed_name(&self, path: impl AsRef<Path>) -> Result<String> {
         let path = path.as_ref();
@@ -487,6 +1232,18 @@ main();"#
             .unwrap_or_else(|| name.clone()))
     }
 
+    /// Evaluate the href and the staging path of a file written by this pipeline, taking the
+    /// optional `data-target-path` into account.
+    async fn dist_target(&self, file_name: &str) -> Result<(String, PathBuf)> {
+        let path = self.dist_dir().await?.join(file_name);
+        Ok((common::dist_relative(&self.cfg.staging_dist, &path)?, path))
+    }
+
+    /// The directory inside the staging dist dir to write files of this pipeline to.
+    async fn dist_dir(&self) -> Result<PathBuf> {
+        common::target_path(&self.cfg.staging_dist, self.target_path.as_deref(), None).await
+    }
+
     /// create a cache busting string, if enabled
     async fn hashed(&self, path: &Path) -> Result<Option<String>> {
         // generate a hashed name, just for cache busting
@@ -542,9 +1299,31 @@ main();"#
         bundle_name: &str,  // In debug, this is a path like "output/Main/index.js"
         bundle_path: &Path, // This is the full path to the file in the staging dir
         dev_mode_run_script: Option<String>,
+        call_main: bool,
     ) -> Result<PureScriptAppOutput> {
         let mut sri = self.sri.clone();
 
+        // Workers are loaded by the application itself, there is nothing to preload or initialize.
+        if self.app_type == PureScriptAppType::Worker {
+            return Ok(PureScriptAppOutput {
+                id: self.id,
+                main_module: self.main_module().to_string(),
+                cfg: self.cfg.clone(),
+                bundle_output: bundle_name.to_string(),
+                r#type: self.app_type,
+                cross_origin: self.cross_origin,
+                integrities: sri,
+                initializer: None,
+                dev_mode_run_script,
+                call_main,
+                worker_name: self.main_module.clone(),
+                hot_modules: None,
+                warnings: vec![],
+                chunks: vec![],
+                report: None,
+            });
+        }
+
         // Record SRI for the main JS bundle or entry point
         sri.record_file(
             SriType::ModulePreload, // Or SriType::Script, depending on how it's loaded
@@ -579,6 +1358,7 @@ main();"#
 
         let res = PureScriptAppOutput {
             id: self.id,
+            main_module: self.main_module().to_string(),
             cfg: self.cfg.clone(),
             bundle_output: bundle_name.to_string(),
             r#type: self.app_type,
@@ -586,8 +1366,88 @@ main();"#
             integrities: sri,
             initializer: initializer_hashed_name,
             dev_mode_run_script,
+            call_main,
+            worker_name: None,
+            hot_modules: None,
+            warnings: vec![],
+            chunks: vec![],
+            report: None,
         };
         tracing::debug!("{:?}", res);
         Ok(res)
     }
 }
+
+/// The script running the main module of an application in dev mode.
+fn dev_run_script(entry_url: &str) -> String {
+    format!(
+        r#"import {{ main }} from '{entry_url}';
+main();"#
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    async fn app(attrs: &[(&str, &str)]) -> Result<(tempfile::TempDir, PureScriptApp)> {
+        let tmp = tempfile::tempdir()?;
+        std::fs::write(
+            tmp.path().join("spago.yaml"),
+            "package:\n  name: app\nworkspace:\n  extraPackages: {}\n",
+        )?;
+        let cfg = Arc::new(RtcBuild::new_test(tmp.path()).await?);
+        let attrs = attrs
+            .iter()
+            .map(|(name, value)| (name.to_string(), (*value).into()))
+            .collect::<HashMap<_, _>>();
+        let html_dir = Arc::new(tmp.path().to_path_buf());
+        let app =
+            PureScriptApp::new(cfg, html_dir, None, Default::default(), attrs, 0, vec![]).await?;
+        Ok((tmp, app))
+    }
+
+    #[test]
+    fn app_types() {
+        assert_eq!(
+            "main".parse::<PureScriptAppType>().ok(),
+            Some(PureScriptAppType::Main)
+        );
+        assert_eq!(
+            "worker".parse::<PureScriptAppType>().ok(),
+            Some(PureScriptAppType::Worker)
+        );
+        assert!("Worker".parse::<PureScriptAppType>().is_err());
+    }
+
+    #[tokio::test]
+    async fn worker_requires_main_module() -> Result<()> {
+        let err = app(&[("data-type", "worker")])
+            .await
+            .err()
+            .map(|err| err.to_string())
+            .unwrap_or_default();
+        assert!(err.contains("requires a `data-main` attribute"), "{err}");
+
+        let (_tmp, app) = app(&[("data-type", "worker"), ("data-main", "App.Worker")]).await?;
+        assert!(!app.is_main());
+        assert_eq!(app.main_module(), "App.Worker");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn writes_dev_worker_entry() -> Result<()> {
+        let (tmp, app) = app(&[("data-type", "worker"), ("data-main", "App.Worker")]).await?;
+        let script = dev_run_script("/output/App.Worker/index.js");
+        let (href, path) = app.write_worker_entry(&script).await?;
+
+        assert_eq!(href, "App.Worker.worker.js");
+        assert_eq!(path, tmp.path().join("dist/.stage/App.Worker.worker.js"));
+        assert_eq!(
+            std::fs::read_to_string(path)?,
+            "import { main } from '/output/App.Worker/index.js';\nmain();"
+        );
+        Ok(())
+    }
+}
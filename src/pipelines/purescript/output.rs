@@ -2,18 +2,29 @@ use super::super::prank_id_selector;
 use crate::{
     common::{html_rewrite::Document, nonce_attr},
     config::{rt::RtcBuild, types::CrossOrigin},
-    pipelines::purescript::{sri::SriBuilder, PureScriptAppType},
+    pipelines::{
+        purescript::{
+            sri::{SriBuilder, SriType},
+            BundleReport, Diagnostic, PureScriptAppType,
+        },
+        service_worker,
+    },
 };
 use anyhow::bail;
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    sync::Arc,
+};
 
 /// The output of a PureScript build pipeline.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct PureScriptAppOutput {
     /// The runtime build config.
     pub cfg: Arc<RtcBuild>,
     /// The ID of this pipeline.
     pub id: Option<usize>,
+    /// The main module of the application.
+    pub main_module: String,
     /// The filename of the generated JS bundle file written to the dist dir.
     pub bundle_output: String,
     /// Is this module main or a worker.
@@ -26,9 +37,32 @@ pub struct PureScriptAppOutput {
     pub initializer: Option<String>,
     /// An optional script to run the main module in dev mode.
     pub dev_mode_run_script: Option<String>,
+    /// Whether the bundle is a module exporting `main`, instead of a bundle running itself.
+    pub call_main: bool,
+    /// The name under which a worker gets registered, only set for workers.
+    pub worker_name: Option<String>,
+    /// The modules to hot replace in dev mode, `None` if the page needs to be reloaded.
+    pub hot_modules: Option<BTreeSet<String>>,
+    /// The warnings of the PureScript compiler.
+    pub warnings: Vec<Diagnostic>,
+    /// The lazily loaded chunks of a release build.
+    pub chunks: Vec<Chunk>,
+    /// The bundle size report of a release build, if enabled.
+    pub report: Option<BundleReport>,
     // REMOVED: js_output, wasm_output, wasm_size, import_bindings, import_bindings_name
 }
 
+/// A chunk of modules, loaded by a dynamic import of its entry module.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+pub struct Chunk {
+    /// The name of the dynamically imported module.
+    pub module: String,
+    /// The file of the dynamically imported module in the dist dir.
+    pub file: String,
+    /// All files of the chunk in the dist dir, including `file`.
+    pub files: Vec<String>,
+}
+
 pub fn pattern_evaluate(template: &str, params: &HashMap<String, String>) -> String {
     let mut result = template.to_string();
     for (k, v) in params.iter() {
@@ -45,12 +79,31 @@ pub fn pattern_evaluate(template: &str, params: &HashMap<String, String>) -> Str
 }
 
 impl PureScriptAppOutput {
+    /// The files loaded eagerly by the application, relative to the dist dir: its bundle, or the
+    /// entry module along with the preloaded modules of its main chunk.
+    pub fn main_chunk(&self) -> Vec<String> {
+        let preloaded = self
+            .integrities
+            .files_of(SriType::ModulePreload)
+            .filter(|file| {
+                *file != self.bundle_output && self.initializer.as_deref() != Some(*file)
+            })
+            .map(str::to_string);
+        std::iter::once(self.bundle_output.clone())
+            .chain(preloaded)
+            .collect()
+    }
+
     pub async fn finalize(self, dom: &mut Document) -> anyhow::Result<()> {
         if !self.cfg.inject_scripts {
             // Configuration directed we do not inject any scripts.
             return Ok(());
         }
 
+        if self.r#type == PureScriptAppType::Worker {
+            return self.finalize_worker(dom);
+        }
+
         let (base, bundle, head, body) = (
             &self.cfg.public_url,
             &self.bundle_output, // CHANGED
@@ -79,6 +132,10 @@ impl PureScriptAppOutput {
             )?;
         }
 
+        if !self.chunks.is_empty() {
+            dom.append_html(head, &self.chunk_manifest(base)?)?;
+        }
+
         let script = if let Some(dev_script) = self.dev_mode_run_script {
             format!(
                 r#"
@@ -96,6 +153,11 @@ impl PureScriptAppOutput {
                 None => self.default_initializer(base, bundle, fire), // CHANGED
             }
         };
+        // Register the service worker next to the bootstrap of the main application.
+        let script = match self.cfg.should_generate_service_worker() {
+            true => format!("{script}{}", service_worker::registration(&self.cfg)),
+            false => script,
+        };
 
         match self.id {
             Some(id) => dom.replace_with_html(&prank_id_selector(id), &script)?,
@@ -112,21 +174,68 @@ impl PureScriptAppOutput {
         Ok(())
     }
 
+    /// Register the worker script URL with the page, instead of loading it.
+    ///
+    /// Workers get registered in `window.prankWorkers`, keyed by their main module, so that the
+    /// application can start them using e.g. `new Worker(window.prankWorkers["Worker.Main"], { type: "module" })`.
+    fn finalize_worker(self, dom: &mut Document) -> anyhow::Result<()> {
+        let Some(id) = self.id else {
+            bail!("a PureScript worker requires a <link data-prank rel=\"purescript\"/> element");
+        };
+        let Some(name) = &self.worker_name else {
+            bail!(
+                "PureScript worker {} has no name to register it with",
+                self.main_module
+            );
+        };
+        let script = format!(
+            r#"
+<script{nonce}>(window.prankWorkers ??= {{}})["{name}"] = "{base}{bundle}";</script>"#,
+            nonce = nonce_attr(&self.cfg.create_nonce),
+            base = self.cfg.public_url,
+            bundle = self.bundle_output,
+        );
+        dom.replace_with_html(&prank_id_selector(id), &script)?;
+        Ok(())
+    }
+
+    /// Register the files of the lazily loaded chunks with the page.
+    ///
+    /// Chunks get registered in `window.prankChunks`, keyed by the module name, so that the
+    /// application can find the file of a chunk, e.g. to prefetch it.
+    fn chunk_manifest(&self, base: &str) -> anyhow::Result<String> {
+        let chunks = self
+            .chunks
+            .iter()
+            .map(|chunk| (chunk.module.as_str(), format!("{base}{}", chunk.file)))
+            .collect::<BTreeMap<_, _>>();
+        Ok(format!(
+            r#"
+<script{nonce}>Object.assign(window.prankChunks ??= {{}}, {chunks});</script>"#,
+            nonce = nonce_attr(&self.cfg.create_nonce),
+            chunks = serde_json::to_string(&chunks)?,
+        ))
+    }
+
     /// create the default initializer script section
     fn default_initializer(&self, base: &str, bundle: &str, fire: &str) -> String {
         // REWRITTEN: This function is now much simpler and handles JS modules.
         let nonce = nonce_attr(&self.cfg.create_nonce);
 
+        let import = match self.call_main {
+            true => format!("import {{ main }} from '{base}{bundle}';\nmain();"),
+            false => format!("import '{base}{bundle}';"),
+        };
+
         match &self.initializer {
             None => format!(
                 r#"
 <script type="module"{nonce}>
-import '{base}{bundle}';
+{import}
 {fire}
 </script>"#,
                 nonce = nonce,
-                base = base,
-                bundle = bundle,
+                import = import,
                 fire = fire
             ),
             Some(initializer) => format!(
@@ -136,15 +245,78 @@ import setup from '{base}{initializer}';
 if (typeof setup === 'function') {{
     await Promise.resolve(setup());
 }}
-import '{base}{bundle}';
+{import}
 {fire}
 </script>"#,
                 nonce = nonce,
                 base = base,
                 initializer = initializer,
-                bundle = bundle,
+                import = import,
                 fire = fire
             ),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{common::html_rewrite::DocumentOptions, processing::integrity::IntegrityType};
+
+    fn worker(cfg: RtcBuild, worker_name: Option<&str>) -> PureScriptAppOutput {
+        PureScriptAppOutput {
+            cfg: Arc::new(cfg),
+            id: Some(0),
+            main_module: "App.Worker".to_string(),
+            bundle_output: "App.Worker.worker.js".to_string(),
+            r#type: PureScriptAppType::Worker,
+            cross_origin: Default::default(),
+            integrities: SriBuilder::new(IntegrityType::None),
+            initializer: None,
+            dev_mode_run_script: None,
+            call_main: false,
+            worker_name: worker_name.map(str::to_string),
+            hot_modules: None,
+            warnings: vec![],
+            chunks: vec![],
+            report: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn registers_workers() -> anyhow::Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let mut cfg = RtcBuild::new_test(tmp.path()).await?;
+        cfg.public_url = "/app/".parse()?;
+        let mut dom = Document::new(
+            r#"<html><head><link data-prank-id="0"></head><body></body></html>"#,
+            DocumentOptions::default(),
+        )?;
+
+        worker(cfg, Some("App.Worker")).finalize(&mut dom).await?;
+
+        let html = String::from_utf8(dom.into_inner())?;
+        assert!(
+            html.contains(
+                r#"<script>(window.prankWorkers ??= {})["App.Worker"] = "/app/App.Worker.worker.js";</script>"#
+            ),
+            "{html}"
+        );
+        assert!(!html.contains("data-prank-id"), "{html}");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn requires_worker_name() -> anyhow::Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let cfg = RtcBuild::new_test(tmp.path()).await?;
+        let mut dom = Document::new(
+            r#"<html><head><link data-prank-id="0"></head></html>"#,
+            DocumentOptions::default(),
+        )?;
+
+        let res = worker(cfg, None).finalize(&mut dom).await;
+        assert!(res.is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,453 @@
+//! Bundle size reports of release builds.
+//!
+//! A report maps the bytes of an application back to the compiled PureScript modules and their
+//! foreign (FFI) modules it consists of. Every module is measured as emitted by the compiler
+//! (raw), by the bytes it takes up in the files written to the dist dir, and by those bytes
+//! compressed with gzip. The modules of a graph of shared modules are written to files of their
+//! own, while the bytes of a single bundle are attributed to the modules using its source map, so
+//! tree shaking and minification by the bundler are accounted for. The totals are the ones of the
+//! files written to the dist dir. The reports of all applications of a page are written as JSON,
+//! along with a self-contained HTML treemap of them.
+
+use super::{graph::ModuleGraph, source_map};
+use crate::{
+    config::{types::ByteSize, REPORT_DIR},
+    processing::compress::gzip,
+};
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    path::{Component, Path, PathBuf},
+};
+use tokio::fs;
+
+/// The HTML template of the treemap, the reports get injected as JSON.
+const TREEMAP_HTML: &str = include_str!("report.html");
+/// The placeholder of the reports in the HTML template.
+const TREEMAP_DATA: &str = "__PRANK_REPORT__";
+
+/// The sizes of some JS code, in bytes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Sizes {
+    /// The size of the code as emitted by the compiler.
+    pub raw: u64,
+    /// The size of the code as written to the dist dir, which is minified if enabled.
+    pub minified: u64,
+    /// The size of the written code, compressed with gzip.
+    pub gzip: u64,
+}
+
+/// The sizes of a module of an application.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ModuleSizes {
+    /// The name of the PureScript module, e.g. `Data.Maybe`.
+    pub module: String,
+    /// The path of the file in the output directory, e.g. `Data.Maybe/foreign.js`.
+    pub file: String,
+    /// Whether this is a foreign module.
+    pub foreign: bool,
+    #[serde(flatten)]
+    pub sizes: Sizes,
+}
+
+/// The bundle size report of an application.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct BundleReport {
+    /// The main module of the application.
+    pub main_module: String,
+    /// The files of the application, relative to the dist dir.
+    pub files: Vec<String>,
+    /// The total sizes of the files. The raw size is the one of all modules, before bundling.
+    pub total: Sizes,
+    /// The modules of the application, the largest (minified) ones first.
+    pub modules: Vec<ModuleSizes>,
+}
+
+/// Where the bytes of the modules of an application ended up.
+#[derive(Debug)]
+pub enum ModuleBytes {
+    /// The modules were written to files of their own, keyed by their path in the output
+    /// directory.
+    Files(HashMap<PathBuf, PathBuf>),
+    /// The modules were bundled into a single file (as written by the bundler), whose source map
+    /// is next to it.
+    Bundle(PathBuf),
+}
+
+impl Sizes {
+    /// Measure the raw code, and the code written to the dist dir.
+    fn measure(raw: u64, written: &[u8]) -> Result<Self> {
+        Ok(Self {
+            raw,
+            minified: written.len() as u64,
+            gzip: match written.is_empty() {
+                true => 0,
+                false => gzip(written)?.len() as u64,
+            },
+        })
+    }
+}
+
+impl BundleReport {
+    /// Create the report of an application, whose code is the module graph of `entry` in
+    /// `output_dir`, written to the given files of the dist dir.
+    pub fn new(
+        output_dir: &Path,
+        entry: &Path,
+        main_module: &str,
+        files: Vec<(String, PathBuf)>,
+        bytes: ModuleBytes,
+    ) -> Result<Self> {
+        let graph = ModuleGraph::load(output_dir, entry)?;
+
+        let mut bundled = match &bytes {
+            ModuleBytes::Files(_) => HashMap::new(),
+            ModuleBytes::Bundle(bundle) => attribute_bundle(bundle, output_dir)?,
+        };
+        let mut modules = graph
+            .modules
+            .iter()
+            .map(|module| {
+                let written = match &bytes {
+                    ModuleBytes::Files(files) => match files.get(&module.path) {
+                        Some(path) => std::fs::read(path)
+                            .with_context(|| format!("error reading module {}", path.display()))?,
+                        None => vec![],
+                    },
+                    ModuleBytes::Bundle(_) => bundled.remove(&module.path).unwrap_or_default(),
+                };
+                let foreign = module.path.file_name() != Some("index.js".as_ref());
+                Ok(ModuleSizes {
+                    module: module_name(&module.path),
+                    file: module.path.to_string_lossy().replace('\\', "/"),
+                    foreign,
+                    sizes: Sizes::measure(module.source.len() as u64, &written)?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        modules.sort_by(|a, b| {
+            b.sizes
+                .minified
+                .cmp(&a.sizes.minified)
+                .then_with(|| a.file.cmp(&b.file))
+        });
+
+        let mut total = Sizes::default();
+        for (_, path) in &files {
+            let bytes = std::fs::read(path)
+                .with_context(|| format!("error reading bundle {}", path.display()))?;
+            let sizes = Sizes::measure(0, &bytes)?;
+            total.minified += sizes.minified;
+            total.gzip += sizes.gzip;
+        }
+        total.raw = modules.iter().map(|module| module.sizes.raw).sum();
+
+        Ok(Self {
+            main_module: main_module.to_string(),
+            files: files.into_iter().map(|(name, _)| name).collect(),
+            total,
+            modules,
+        })
+    }
+
+    /// Log a summary of the report.
+    pub fn log_summary(&self) {
+        tracing::info!(
+            "{}: {} raw, {} minified, {} gzip ({} modules)",
+            self.main_module,
+            ByteSize(self.total.raw),
+            ByteSize(self.total.minified),
+            ByteSize(self.total.gzip),
+            self.modules.len(),
+        );
+    }
+}
+
+/// Write the reports of the applications of a page to the report directory of a dist dir.
+pub async fn write_reports(dist: &Path, reports: &[BundleReport]) -> Result<()> {
+    let dir = dist.join(REPORT_DIR);
+    fs::create_dir_all(&dir)
+        .await
+        .with_context(|| format!("error creating report directory {}", dir.display()))?;
+
+    let json = serde_json::to_string_pretty(reports).context("error serializing bundle report")?;
+    fs::write(dir.join("bundle-report.json"), &json)
+        .await
+        .context("error writing bundle report")?;
+    fs::write(dir.join("bundle-report.html"), treemap_html(&json))
+        .await
+        .context("error writing bundle report treemap")?;
+    Ok(())
+}
+
+/// Inject the reports into the treemap template.
+fn treemap_html(json: &str) -> String {
+    // the JSON is embedded into a script element, which must not be closed by its content
+    TREEMAP_HTML.replace(TREEMAP_DATA, &json.replace("</", "<\\/"))
+}
+
+/// Attribute the bytes of a bundle to the modules of the output directory, keyed by their path,
+/// using the source map of the bundle.
+fn attribute_bundle(bundle: &Path, output_dir: &Path) -> Result<HashMap<PathBuf, Vec<u8>>> {
+    let code = std::fs::read_to_string(bundle)
+        .with_context(|| format!("error reading bundle {}", bundle.display()))?;
+    let map_path = source_map::map_path(bundle);
+    let map = std::fs::read(&map_path)
+        .with_context(|| format!("error reading source map {}", map_path.display()))?;
+    let map: serde_json::Value =
+        serde_json::from_slice(&map).context("error parsing source map")?;
+
+    // The sources are relative to the source root, which is relative to the map.
+    let root = bundle
+        .parent()
+        .unwrap_or(Path::new(""))
+        .join(map["sourceRoot"].as_str().unwrap_or_default());
+    let output_dir = source_map::normalize(output_dir);
+    let sources = map["sources"]
+        .as_array()
+        .context("source map has no sources")?
+        .iter()
+        .map(|source| {
+            let source = source_map::normalize(&root.join(source.as_str()?));
+            source.strip_prefix(&output_dir).ok().map(Path::to_path_buf)
+        })
+        .collect::<Vec<_>>();
+    let mappings = map["mappings"]
+        .as_str()
+        .context("source map has no mappings")?;
+
+    let mut bytes = HashMap::<PathBuf, Vec<u8>>::new();
+    let mut source = 0;
+    for (line, segments) in code.split('\n').zip(mappings.split(';')) {
+        // The start of each segment in the line, along with its source.
+        let mut offsets = Utf16Offsets::new(line);
+        let mut column = 0;
+        let mut starts = vec![];
+        for segment in segments.split(',').filter(|segment| !segment.is_empty()) {
+            let fields = decode_vlq(segment)?;
+            column += fields[0];
+            let start = offsets.byte_offset(usize::try_from(column).unwrap_or_default());
+            match fields.get(1) {
+                Some(delta) => {
+                    source += delta;
+                    starts.push((start, usize::try_from(source).ok()));
+                }
+                None => starts.push((start, None)),
+            }
+        }
+        for (index, (start, source)) in starts.iter().enumerate() {
+            let end = starts.get(index + 1).map_or(line.len(), |(end, _)| *end);
+            let path = source.and_then(|source| sources.get(source)?.as_ref());
+            if let (Some(path), Some(code)) = (path, line.get(*start..end)) {
+                bytes
+                    .entry(path.clone())
+                    .or_default()
+                    .extend_from_slice(code.as_bytes());
+            }
+        }
+    }
+    Ok(bytes)
+}
+
+/// Converts the UTF-16 columns of a source map into byte offsets of a line, moving forward.
+struct Utf16Offsets<'a> {
+    chars: std::iter::Peekable<std::str::CharIndices<'a>>,
+    column: usize,
+    len: usize,
+}
+
+impl<'a> Utf16Offsets<'a> {
+    fn new(line: &'a str) -> Self {
+        Self {
+            chars: line.char_indices().peekable(),
+            column: 0,
+            len: line.len(),
+        }
+    }
+
+    /// The byte offset of a column, which must not be before the previous one.
+    fn byte_offset(&mut self, column: usize) -> usize {
+        while let Some((offset, c)) = self.chars.peek().copied() {
+            if self.column >= column {
+                return offset;
+            }
+            self.column += c.len_utf16();
+            self.chars.next();
+        }
+        self.len
+    }
+}
+
+/// Decode the Base64 VLQ fields of a source map segment.
+fn decode_vlq(segment: &str) -> Result<Vec<i64>> {
+    let mut fields = vec![];
+    let (mut value, mut shift) = (0i64, 0);
+    for c in segment.bytes() {
+        let digit = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => bail!("invalid source map segment {segment}"),
+        };
+        if shift > 60 {
+            bail!("invalid source map segment {segment}");
+        }
+        value += i64::from(digit & 31) << shift;
+        shift += 5;
+        if digit & 32 == 0 {
+            fields.push(match value & 1 {
+                1 => -(value >> 1),
+                _ => value >> 1,
+            });
+            (value, shift) = (0, 0);
+        }
+    }
+    if shift != 0 || fields.is_empty() {
+        bail!("invalid source map segment {segment}");
+    }
+    Ok(fields)
+}
+
+/// The name of the PureScript module of a file in the output directory, which is the directory
+/// of the module.
+fn module_name(path: &Path) -> String {
+    match path.components().next() {
+        Some(Component::Normal(dir)) if path.components().count() > 1 => {
+            dir.to_string_lossy().to_string()
+        }
+        _ => path.to_string_lossy().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::test_util::write;
+
+    /// Write the output of an application, returning the output directory.
+    fn output(root: &Path) -> Result<PathBuf> {
+        let output = root.join("output-es");
+        write(
+            &output,
+            "Main/index.js",
+            "import * as $foreign from \"./foreign.js\";\nimport * as Data$dMaybe from \"../Data.Maybe/index.js\";\nconst main = () => $foreign.log(Data$dMaybe.fromMaybe(\"nothing\"));\nexport {main};\n",
+        )?;
+        write(
+            &output,
+            "Main/foreign.js",
+            "export const log = (message) => console.log(message);\n",
+        )?;
+        write(
+            &output,
+            "Data.Maybe/index.js",
+            "const fromMaybe = (value) => value;\nexport {fromMaybe};\n",
+        )?;
+        Ok(output)
+    }
+
+    fn sizes(report: &BundleReport) -> Vec<(&str, bool, u64)> {
+        let mut sizes = report
+            .modules
+            .iter()
+            .map(|module| (module.file.as_str(), module.foreign, module.sizes.minified))
+            .collect::<Vec<_>>();
+        sizes.sort();
+        sizes
+    }
+
+    #[test]
+    fn reports_written_modules() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let output = output(tmp.path())?;
+        let dist = tmp.path().join("dist");
+        write(&dist, "main.js", "import\"./maybe.js\";main();")?;
+        write(&dist, "maybe.js", "const f=v=>v;")?;
+        let files = HashMap::from([
+            (PathBuf::from("Main/index.js"), dist.join("main.js")),
+            (PathBuf::from("Data.Maybe/index.js"), dist.join("maybe.js")),
+        ]);
+
+        let report = BundleReport::new(
+            &output,
+            Path::new("Main/index.js"),
+            "Main",
+            vec![("main.js".to_string(), dist.join("main.js"))],
+            ModuleBytes::Files(files),
+        )?;
+
+        assert_eq!(report.files, ["main.js"]);
+        assert_eq!(
+            sizes(&report),
+            [
+                ("Data.Maybe/index.js", false, 13),
+                ("Main/foreign.js", true, 0),
+                ("Main/index.js", false, 26),
+            ]
+        );
+        assert_eq!(report.modules[0].module, "Main");
+        assert!(report
+            .modules
+            .windows(2)
+            .all(|pair| pair[0].sizes.minified >= pair[1].sizes.minified));
+        assert_eq!(
+            report.total.raw,
+            report.modules.iter().map(|m| m.sizes.raw).sum::<u64>()
+        );
+        assert_eq!(report.total.minified, 26);
+        Ok(())
+    }
+
+    #[test]
+    fn attributes_bundles_by_source_map() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let output = output(tmp.path())?;
+        let bundle = tmp.path().join("output/bundle/index.js");
+        // "ä" takes up two bytes, but one UTF-16 column
+        write(
+            tmp.path(),
+            "output/bundle/index.js",
+            "var ä=v=>v;console.log(ä(1));\n//# sourceMappingURL=index.js.map\n",
+        )?;
+        write(
+            tmp.path(),
+            "output/bundle/index.js.map",
+            r#"{"version":3,"sources":["../../output-es/Data.Maybe/index.js","../../output-es/Main/foreign.js","esbuild:runtime"],"mappings":"AAAA,UCAA,EDAA"}"#,
+        )?;
+
+        let report = BundleReport::new(
+            &output,
+            Path::new("Main/index.js"),
+            "Main",
+            vec![("index.js".to_string(), bundle.clone())],
+            ModuleBytes::Bundle(bundle),
+        )?;
+
+        assert_eq!(
+            sizes(&report),
+            [
+                ("Data.Maybe/index.js", false, 11 + 18),
+                ("Main/foreign.js", true, 2),
+                ("Main/index.js", false, 0),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn decodes_vlq() -> Result<()> {
+        assert_eq!(decode_vlq("AAAA")?, [0, 0, 0, 0]);
+        assert_eq!(decode_vlq("QCDgB")?, [8, 1, -1, 16]);
+        assert!(decode_vlq("g").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn escapes_treemap_data() {
+        let html = treemap_html(r#"[{"file":"</script>"}]"#);
+        assert!(!html.contains(TREEMAP_DATA));
+        assert!(html.contains(r#"[{"file":"<\/script>"}]"#));
+    }
+}
@@ -0,0 +1,222 @@
+//! Source maps of the release bundles.
+//!
+//! The bundler writes the map next to the bundle, referencing it by its original name. Once the
+//! bundle got its final (hashed) name in the dist dir, the map is moved along and the reference is
+//! updated, or dropped for hidden source maps. The sources of the map are relative to the map, so
+//! they are rebased onto the directory the map ends up in.
+
+use crate::{config::types::SourceMaps, pipelines::include::relative_dir};
+use anyhow::{anyhow, Context, Result};
+use serde_json::Value;
+use std::path::{Component, Path, PathBuf};
+use tokio::fs;
+
+/// The prefix of the comment referencing the source map of a JS file.
+const SOURCE_MAPPING_URL: &str = "//# sourceMappingURL=";
+
+/// The path of the source map of a JS file.
+pub fn map_path(js_path: &Path) -> PathBuf {
+    let mut path = js_path.as_os_str().to_owned();
+    path.push(".map");
+    PathBuf::from(path)
+}
+
+/// Copy a bundle and its source map to the destination, updating the references between them.
+///
+/// The `final_dir` is the directory the bundle is served from, once the staging dist dir got
+/// published.
+pub async fn copy_with_source_map(
+    origin_path: &Path,
+    destination_path: &Path,
+    final_dir: &Path,
+    mode: SourceMaps,
+) -> Result<()> {
+    let code = fs::read_to_string(origin_path)
+        .await
+        .context("error reading JS bundle")?;
+    let origin_map_path = map_path(origin_path);
+    let map = fs::read(&origin_map_path).await.with_context(|| {
+        format!(
+            "error reading source map of JS bundle at {}",
+            origin_map_path.display()
+        )
+    })?;
+    let map: Value = serde_json::from_slice(&map).context("error parsing source map")?;
+
+    let file_name = destination_path
+        .file_name()
+        .ok_or_else(|| anyhow!("Must be a file: {}", destination_path.display()))?
+        .to_string_lossy();
+    let origin_dir = origin_path.parent().unwrap_or(Path::new(""));
+    let (code, map) = relink(&code, map, &file_name, (origin_dir, final_dir), mode);
+
+    fs::write(map_path(destination_path), serde_json::to_vec(&map)?)
+        .await
+        .context("error writing source map to stage dir")?;
+    fs::write(destination_path, code)
+        .await
+        .context("error writing JS bundle to stage dir")?;
+
+    Ok(())
+}
+
+/// Copy a bundle to the destination, dropping the reference to its source map.
+///
+/// The map stays next to the original bundle, e.g. to create the bundle size report.
+pub async fn copy_without_source_map(origin_path: &Path, destination_path: &Path) -> Result<()> {
+    let code = fs::read_to_string(origin_path)
+        .await
+        .context("error reading JS bundle")?;
+    fs::write(destination_path, strip_source_mapping_url(&code))
+        .await
+        .context("error writing JS bundle to stage dir")
+}
+
+/// Point the code and its source map to each other, using the final name of the code file, and
+/// rebase the sources of the map moving `(from, to)` another directory.
+fn relink(
+    code: &str,
+    mut map: Value,
+    file_name: &str,
+    (from, to): (&Path, &Path),
+    mode: SourceMaps,
+) -> (String, Value) {
+    let mut code = strip_source_mapping_url(code).to_string();
+    if mode == SourceMaps::External {
+        code.push_str(&format!("\n{SOURCE_MAPPING_URL}{file_name}.map\n"));
+    }
+
+    if let Some(map) = map.as_object_mut() {
+        map.insert("file".to_string(), Value::String(file_name.to_string()));
+        // The sources are relative to the source root, if it's set.
+        match map.get_mut("sourceRoot") {
+            Some(Value::String(root)) if !root.is_empty() => rebase(root, from, to),
+            _ => {
+                for source in map
+                    .get_mut("sources")
+                    .and_then(Value::as_array_mut)
+                    .into_iter()
+                    .flatten()
+                {
+                    if let Value::String(source) = source {
+                        rebase(source, from, to);
+                    }
+                }
+            }
+        }
+    }
+
+    (code, map)
+}
+
+/// Rebase a relative URL from one directory to another one.
+///
+/// Absolute URLs and paths are left alone.
+fn rebase(url: &mut String, from: &Path, to: &Path) {
+    if url.starts_with('/') || url.contains(':') {
+        return;
+    }
+    let target = normalize(&from.join(url.as_str()));
+    let to = normalize(to);
+    // A directory, like the source root, keeps its trailing slash.
+    let file_name = match url.ends_with('/') {
+        true => None,
+        false => target.file_name(),
+    };
+    *url = match file_name {
+        Some(file_name) => {
+            let dir = relative_dir(&to, target.parent().unwrap_or(Path::new("")));
+            format!("{dir}{}", file_name.to_string_lossy())
+        }
+        None => match relative_dir(&to, &target) {
+            dir if dir.is_empty() => "./".to_string(),
+            dir => dir,
+        },
+    };
+}
+
+/// Resolve the `.` and `..` components of a path, without accessing the file system.
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Remove a trailing source map reference from the code.
+fn strip_source_mapping_url(code: &str) -> &str {
+    let trimmed = code.trim_end();
+    match trimmed.rfind('\n') {
+        Some(pos) if trimmed[pos + 1..].starts_with(SOURCE_MAPPING_URL) => &trimmed[..pos],
+        None if trimmed.starts_with(SOURCE_MAPPING_URL) => "",
+        _ => code,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn relinks_source_maps() {
+        let code = "console.log(1);\n//# sourceMappingURL=index.js.map\n";
+        let map = json!({ "version": 3, "file": "index.js", "mappings": "AAAA" });
+
+        let dirs = (Path::new("/app/output/bundle"), Path::new("/app/dist"));
+        let (external, map) = relink(code, map, "1a2b-index.js", dirs, SourceMaps::External);
+        assert_eq!(
+            external,
+            "console.log(1);\n//# sourceMappingURL=1a2b-index.js.map\n"
+        );
+        assert_eq!(map["file"], "1a2b-index.js");
+        assert_eq!(map["mappings"], "AAAA");
+
+        let (hidden, _) = relink(code, map, "1a2b-index.js", dirs, SourceMaps::Hidden);
+        assert_eq!(hidden, "console.log(1);");
+
+        assert_eq!(
+            strip_source_mapping_url("console.log(1);\n"),
+            "console.log(1);\n"
+        );
+    }
+
+    #[test]
+    fn rebases_sources() {
+        let map = json!({
+            "version": 3,
+            "sources": ["../../src/Main.purs", "../Data.Maybe/index.js", "webpack:///x.js"],
+            "mappings": "AAAA",
+        });
+        let dirs = (Path::new("/app/output/bundle"), Path::new("/app/dist/js/."));
+        let (_, map) = relink("", map, "index.js", dirs, SourceMaps::Hidden);
+        assert_eq!(
+            map["sources"],
+            json!([
+                "../../src/Main.purs",
+                "../../output/Data.Maybe/index.js",
+                "webpack:///x.js"
+            ])
+        );
+
+        let map = json!({ "version": 3, "sourceRoot": "../../", "sources": ["src/Main.purs"] });
+        let (_, map) = relink("", map, "index.js", dirs, SourceMaps::Hidden);
+        assert_eq!(map["sourceRoot"], "../../");
+        assert_eq!(map["sources"], json!(["src/Main.purs"]));
+        let (_, map) = relink(
+            "",
+            map,
+            "index.js",
+            (dirs.0, Path::new("/app")),
+            SourceMaps::Hidden,
+        );
+        assert_eq!(map["sourceRoot"], "./");
+    }
+}
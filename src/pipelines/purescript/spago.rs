@@ -0,0 +1,516 @@
+//! Spago project metadata, read from `spago.yaml`.
+//!
+//! A `spago.yaml` may contain a `package` section, a `workspace` section, or both. A plain project
+//! has both in the same file. A workspace (monorepo) has a `workspace` section in its root
+//! `spago.yaml`, and one `spago.yaml` with a `package` section per package, somewhere below the
+//! root. All packages of a workspace share the same output directory, located at the root.
+
+use anyhow::{anyhow, bail, Context, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::Deserialize;
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+/// The file name of spago manifests.
+const SPAGO_YAML: &str = "spago.yaml";
+
+/// The default output directory of the compiler.
+const DEFAULT_OUTPUT: &str = "output";
+
+/// The default output directory of purs-backend-es.
+const DEFAULT_BACKEND_OUTPUT: &str = "output-es";
+
+/// Directories which are never searched for workspace packages.
+const IGNORED_DIRS: &[&str] = &["output", "output-es", "node_modules", "dist"];
+
+/// The content of a `spago.yaml` file.
+#[derive(Debug, Clone, Deserialize)]
+struct SpagoConfig {
+    /// The `package` section, absent for a workspace root.
+    package: Option<SpagoPackage>,
+    /// The `workspace` section, only present in the workspace root.
+    workspace: Option<SpagoWorkspace>,
+}
+
+impl SpagoConfig {
+    fn load(path: &Path) -> Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("error opening spago manifest {}", path.display()))?;
+        serde_yaml::from_reader(BufReader::new(file))
+            .with_context(|| format!("error parsing spago manifest {}", path.display()))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpagoPackage {
+    pub name: String,
+    /// The `test` section of the package
+    #[serde(default)]
+    pub test: Option<SpagoTest>,
+    /// The `bundle` section of the package
+    #[serde(default)]
+    pub bundle: Option<SpagoBundle>,
+}
+
+/// The `test` section of a package.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpagoTest {
+    /// The main module of the test suite
+    pub main: String,
+}
+
+/// The `bundle` section of a package, used by `spago bundle`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpagoBundle {
+    /// The entry module of the bundle
+    pub module: Option<String>,
+    /// The file to write the bundle to
+    pub outfile: Option<PathBuf>,
+    /// The platform to bundle for, `browser` or `node`
+    pub platform: Option<String>,
+    /// Minify the bundle
+    #[serde(default)]
+    pub minify: bool,
+    /// Bundle an application running `main`, or a module exporting it
+    #[serde(default)]
+    pub r#type: SpagoBundleType,
+    /// Additional arguments for the bundler
+    #[serde(default)]
+    pub extra_args: Vec<String>,
+}
+
+/// The type of a bundle.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpagoBundleType {
+    /// A bundle running the `main` function of its module.
+    #[default]
+    App,
+    /// A bundle exporting the `main` function of its module.
+    Module,
+}
+
+/// The `workspace` section of the spago.yaml.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpagoWorkspace {
+    /// The alternative backend to run on the compiler's output
+    #[serde(default)]
+    pub backend: Option<SpagoBackend>,
+    /// The options of the compiler
+    #[serde(default)]
+    pub build_opts: SpagoBuildOpts,
+}
+
+/// The `buildOpts` section of a workspace.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SpagoBuildOpts {
+    /// The output directory of the compiler, relative to the workspace root
+    pub output: Option<PathBuf>,
+}
+
+/// The `backend` section of a workspace.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpagoBackend {
+    /// The command of the backend
+    pub cmd: String,
+    /// The arguments of the backend command
+    #[serde(default)]
+    pub args: Option<Vec<String>>,
+}
+
+impl SpagoBackend {
+    /// Check if the backend is purs-backend-es.
+    pub fn is_purs_backend_es(&self) -> bool {
+        Path::new(&self.cmd)
+            .file_stem()
+            .is_some_and(|name| name == "purs-backend-es")
+    }
+
+    /// The output directory passed to the backend using `--output-dir`, if any.
+    pub fn output_dir(&self) -> Option<&str> {
+        let mut args = self.args.iter().flatten();
+        while let Some(arg) = args.next() {
+            if arg == "--output-dir" {
+                return args.next().map(String::as_str);
+            }
+            if let Some(dir) = arg.strip_prefix("--output-dir=") {
+                return Some(dir);
+            }
+        }
+        None
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SpagoMetadata {
+    /// The path to the spago.yaml manifest of the package
+    pub manifest_path: PathBuf,
+    /// The directory containing the manifest of the workspace
+    pub workspace_root: PathBuf,
+    /// The directory containing the manifest of the package
+    pub package_root: PathBuf,
+    /// The `package` section of the package's spago.yaml
+    pub package: SpagoPackage,
+    /// The `workspace` section of the workspace's spago.yaml
+    pub workspace: SpagoWorkspace,
+    /// The name to select the package with (`-p`), if the workspace has more than one package
+    pub selected_package: Option<String>,
+    /// Matches the source files (PureScript and FFI) of the package
+    sources: GlobSet,
+}
+
+impl SpagoMetadata {
+    /// Create a new instance by parsing a spago.yaml file.
+    ///
+    /// The manifest may be the one of a package, or the one of a workspace root. If `package` is
+    /// given, the package with that name is selected from the workspace. Otherwise, the package of
+    /// the manifest is used, or the only package of the workspace.
+    pub async fn new(manifest_path: &Path, package: Option<&str>) -> Result<Self> {
+        let manifest_path = manifest_path.to_path_buf();
+        let package = package.map(ToString::to_string);
+        tokio::task::spawn_blocking(move || Self::load(&manifest_path, package.as_deref()))
+            .await
+            .context("error awaiting spago manifest")?
+    }
+
+    fn load(manifest_path: &Path, package: Option<&str>) -> Result<Self> {
+        let config = SpagoConfig::load(manifest_path)?;
+        let manifest_dir = parent(manifest_path)?;
+
+        // the workspace is defined in the manifest itself, or in the one of a parent directory
+        let (workspace_root, workspace) = match config.workspace {
+            Some(workspace) => (manifest_dir.clone(), workspace),
+            None => find_workspace(&manifest_dir)?.unwrap_or_else(|| {
+                tracing::debug!(
+                    "no spago workspace found for {}, using its directory as root",
+                    manifest_path.display()
+                );
+                (manifest_dir.clone(), SpagoWorkspace::default())
+            }),
+        };
+
+        let (manifest_path, package) = match (package, config.package) {
+            (Some(name), Some(own)) if own.name == name => (manifest_path.to_path_buf(), own),
+            (None, Some(own)) => (manifest_path.to_path_buf(), own),
+            (name, _) => {
+                let mut packages = workspace_packages(&workspace_root)?;
+                match name {
+                    Some(name) => match packages.iter().position(|(_, p)| p.name == name) {
+                        Some(index) => packages.swap_remove(index),
+                        None => bail!(
+                            "package '{name}' not found in spago workspace {}, available packages: {}",
+                            workspace_root.display(),
+                            package_names(&packages)
+                        ),
+                    },
+                    None if packages.len() == 1 => packages.remove(0),
+                    None if packages.is_empty() => bail!(
+                        "spago workspace {} contains no packages",
+                        workspace_root.display()
+                    ),
+                    None => bail!(
+                        "spago workspace {} contains several packages ({}), select one using `data-package`",
+                        workspace_root.display(),
+                        package_names(&packages)
+                    ),
+                }
+            }
+        };
+
+        let package_root = parent(&manifest_path)?;
+        // Spago builds everything of the workspace, unless run inside of a package's directory.
+        // As we run it from the workspace root, a package in its own directory must be selected.
+        let selected_package = (package_root != workspace_root).then(|| package.name.clone());
+        let sources = source_globs(&package_root)?;
+
+        Ok(Self {
+            manifest_path,
+            workspace_root,
+            package_root,
+            package,
+            workspace,
+            selected_package,
+            sources,
+        })
+    }
+
+    /// Spago's output directory, shared by all packages of the workspace.
+    pub fn target_directory(&self) -> PathBuf {
+        let output = self.workspace.build_opts.output.as_deref();
+        self.workspace_root
+            .join(output.unwrap_or(Path::new(DEFAULT_OUTPUT)))
+    }
+
+    /// The output directory of purs-backend-es, relative to the workspace root unless configured
+    /// in the arguments of the workspace's backend.
+    pub fn backend_output_dir(&self) -> PathBuf {
+        let output = self
+            .workspace
+            .backend
+            .as_ref()
+            .filter(|backend| backend.is_purs_backend_es())
+            .and_then(SpagoBackend::output_dir);
+        self.workspace_root
+            .join(output.unwrap_or(DEFAULT_BACKEND_OUTPUT))
+    }
+
+    /// The build options of purs-backend-es, taken from the `backend` section of the workspace.
+    pub fn backend_args(&self) -> Vec<&str> {
+        let Some(backend) = &self.workspace.backend else {
+            return vec![];
+        };
+        if !backend.is_purs_backend_es() {
+            tracing::warn!(
+                "the spago workspace uses the backend '{}', but release builds always use purs-backend-es",
+                backend.cmd
+            );
+            return vec![];
+        }
+        backend
+            .args
+            .iter()
+            .flatten()
+            .map(String::as_str)
+            .skip_while(|arg| *arg == "build")
+            .collect()
+    }
+
+    /// The compiler output read by purs-backend-es, if it isn't at the default location.
+    pub fn corefn_dir(&self) -> Option<String> {
+        self.workspace
+            .build_opts
+            .output
+            .is_some()
+            .then(|| self.target_directory().to_string_lossy().to_string())
+    }
+
+    /// The globs of the PureScript sources and tests of the package, relative to its root.
+    pub fn purs_globs(&self) -> Vec<String> {
+        ["src", "test"]
+            .into_iter()
+            .filter(|dir| self.package_root.join(dir).is_dir())
+            .map(|dir| format!("{dir}/**/*.purs"))
+            .collect()
+    }
+
+    /// Check if a path is a source file (PureScript or FFI) of the package.
+    pub fn is_source(&self, path: &Path) -> bool {
+        self.sources.is_match(path)
+    }
+}
+
+/// The directory containing a manifest.
+fn parent(manifest_path: &Path) -> Result<PathBuf> {
+    Ok(manifest_path
+        .parent()
+        .ok_or_else(|| anyhow!("manifest path has no parent directory"))?
+        .to_path_buf())
+}
+
+/// Find the workspace root manifest in one of the parent directories of `dir`.
+fn find_workspace(dir: &Path) -> Result<Option<(PathBuf, SpagoWorkspace)>> {
+    for dir in dir.ancestors().skip(1) {
+        let path = dir.join(SPAGO_YAML);
+        if !path.is_file() {
+            continue;
+        }
+        if let Some(workspace) = SpagoConfig::load(&path)?.workspace {
+            return Ok(Some((dir.to_path_buf(), workspace)));
+        }
+    }
+    Ok(None)
+}
+
+/// Find all packages of a workspace, in the same way spago does: by looking for `spago.yaml`
+/// files with a `package` section in the workspace directory tree.
+fn workspace_packages(root: &Path) -> Result<Vec<(PathBuf, SpagoPackage)>> {
+    let mut packages = Vec::new();
+    let mut dirs = vec![root.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        let path = dir.join(SPAGO_YAML);
+        if path.is_file() {
+            if let Some(package) = SpagoConfig::load(&path)?.package {
+                packages.push((path, package));
+            }
+        }
+
+        let entries = std::fs::read_dir(&dir)
+            .with_context(|| format!("error reading directory {}", dir.display()))?;
+        for entry in entries {
+            let entry = entry?;
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if name.starts_with('.') || IGNORED_DIRS.contains(&name.as_ref()) {
+                continue;
+            }
+            if entry.file_type()?.is_dir() {
+                dirs.push(entry.path());
+            }
+        }
+    }
+
+    packages.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Ok(packages)
+}
+
+fn package_names(packages: &[(PathBuf, SpagoPackage)]) -> String {
+    packages
+        .iter()
+        .map(|(_, p)| p.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The source globs of a package, spago always looks for sources in `src/` of the package.
+fn source_globs(package_root: &Path) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for ext in ["purs", "js"] {
+        let glob = package_root.join("src").join("**").join(format!("*.{ext}"));
+        builder.add(Glob::new(&glob.to_string_lossy())?);
+    }
+    Ok(builder.build()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::test_util::write;
+
+    fn workspace() -> Result<tempfile::TempDir> {
+        let tmp = tempfile::tempdir()?;
+        write(
+            tmp.path(),
+            "spago.yaml",
+            "workspace:\n  packageSet:\n    registry: 67.0.1\n",
+        )?;
+        write(
+            tmp.path(),
+            "frontend/spago.yaml",
+            "package:\n  name: frontend\n  dependencies: []\n",
+        )?;
+        write(
+            tmp.path(),
+            "backend/spago.yaml",
+            "package:\n  name: backend\n  dependencies: []\n  test:\n    main: Test.Main\n    dependencies: []\n",
+        )?;
+        write(
+            tmp.path(),
+            "output/Ignored/spago.yaml",
+            "package:\n  name: ignored\n",
+        )?;
+        Ok(tmp)
+    }
+
+    #[test]
+    fn plain_project() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        write(
+            tmp.path(),
+            "spago.yaml",
+            "package:\n  name: app\nworkspace:\n  extraPackages: {}\n",
+        )?;
+        let metadata = SpagoMetadata::load(&tmp.path().join("spago.yaml"), None)?;
+        assert_eq!(metadata.package.name, "app");
+        assert!(metadata.package.test.is_none());
+        assert!(metadata.package.bundle.is_none());
+        assert!(metadata.workspace.backend.is_none());
+        assert_eq!(metadata.workspace_root, tmp.path());
+        assert_eq!(metadata.selected_package, None);
+        assert!(metadata.is_source(&tmp.path().join("src/Main.purs")));
+        assert!(!metadata.is_source(&tmp.path().join("test/Main.purs")));
+        Ok(())
+    }
+
+    #[test]
+    fn bundle_and_backend() -> Result<()> {
+        let tmp = workspace()?;
+        write(
+            tmp.path(),
+            "spago.yaml",
+            "workspace:\n  packageSet:\n    registry: 67.0.1\n  buildOpts:\n    output: output-purs\n  backend:\n    cmd: purs-backend-es\n    args: [build, --int-tags]\n",
+        )?;
+        write(
+            tmp.path(),
+            "frontend/spago.yaml",
+            "package:\n  name: frontend\n  bundle:\n    module: App.Main\n    outfile: dist/app.js\n    platform: browser\n    minify: true\n    type: module\n    extraArgs: [--timing]\n",
+        )?;
+        let metadata = SpagoMetadata::load(&tmp.path().join("frontend/spago.yaml"), None)?;
+        assert_eq!(metadata.target_directory(), tmp.path().join("output-purs"));
+        assert_eq!(metadata.backend_output_dir(), tmp.path().join("output-es"));
+
+        let bundle = metadata.package.bundle.unwrap_or_default();
+        assert_eq!(bundle.module.as_deref(), Some("App.Main"));
+        assert_eq!(bundle.outfile, Some(PathBuf::from("dist/app.js")));
+        assert_eq!(bundle.platform.as_deref(), Some("browser"));
+        assert!(bundle.minify);
+        assert_eq!(bundle.r#type, SpagoBundleType::Module);
+        assert_eq!(bundle.extra_args, ["--timing"]);
+
+        let backend = metadata
+            .workspace
+            .backend
+            .ok_or_else(|| anyhow!("missing backend"))?;
+        assert!(backend.is_purs_backend_es());
+        assert_eq!(backend.output_dir(), None);
+        assert_eq!(backend.args.unwrap_or_default(), ["build", "--int-tags"]);
+        Ok(())
+    }
+
+    #[test]
+    fn backend_output_dir() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        for (args, output) in [
+            ("[build, --output-dir, out/es]", "out/es"),
+            ("[build, --output-dir=out/es, --int-tags]", "out/es"),
+        ] {
+            write(
+                tmp.path(),
+                "spago.yaml",
+                format!("package:\n  name: app\nworkspace:\n  backend:\n    cmd: purs-backend-es\n    args: {args}\n"),
+            )?;
+            let metadata = SpagoMetadata::load(&tmp.path().join("spago.yaml"), None)?;
+            assert_eq!(metadata.backend_output_dir(), tmp.path().join(output));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn workspace_package_by_name() -> Result<()> {
+        let tmp = workspace()?;
+        let metadata = SpagoMetadata::load(&tmp.path().join("spago.yaml"), Some("frontend"))?;
+        assert_eq!(metadata.package.name, "frontend");
+        assert_eq!(metadata.workspace_root, tmp.path());
+        assert_eq!(metadata.package_root, tmp.path().join("frontend"));
+        assert_eq!(metadata.target_directory(), tmp.path().join("output"));
+        assert_eq!(metadata.selected_package.as_deref(), Some("frontend"));
+        assert!(metadata.purs_globs().is_empty());
+        write(tmp.path(), "frontend/src/Main.purs", "module Main where\n")?;
+        assert_eq!(metadata.purs_globs(), ["src/**/*.purs"]);
+        assert!(metadata.is_source(&tmp.path().join("frontend/src/App/Main.js")));
+        assert!(!metadata.is_source(&tmp.path().join("backend/src/Main.purs")));
+
+        assert!(SpagoMetadata::load(&tmp.path().join("spago.yaml"), Some("ignored")).is_err());
+        assert!(SpagoMetadata::load(&tmp.path().join("spago.yaml"), None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn workspace_package_manifest() -> Result<()> {
+        let tmp = workspace()?;
+        let metadata = SpagoMetadata::load(&tmp.path().join("backend/spago.yaml"), None)?;
+        assert_eq!(metadata.package.name, "backend");
+        assert_eq!(
+            metadata.package.test.map(|test| test.main).as_deref(),
+            Some("Test.Main")
+        );
+        assert_eq!(metadata.workspace_root, tmp.path());
+        assert_eq!(metadata.selected_package.as_deref(), Some("backend"));
+        Ok(())
+    }
+}
@@ -6,6 +6,7 @@ use crate::{
 };
 use anyhow::Context;
 use std::{
+    collections::BTreeMap,
     fmt::{Display, Formatter},
     future::Future,
     path::Path,
@@ -15,6 +16,8 @@ use std::{
 pub enum SriType {
     Preload,
     ModulePreload,
+    /// A lazily loaded module, which is not preloaded. Its integrity is declared by an import map.
+    ImportMap,
 }
 
 impl Display for SriType {
@@ -22,6 +25,7 @@ impl Display for SriType {
         match self {
             Self::Preload => f.write_str("preload"),
             Self::ModulePreload => f.write_str("modulepreload"),
+            Self::ImportMap => f.write_str("importmap"),
         }
     }
 }
@@ -44,6 +48,32 @@ impl SriBuilder {
         self.result
     }
 
+    /// The digest recorded for a file, of any type
+    pub fn digest(&self, name: &str) -> Option<&OutputDigest> {
+        self.result
+            .integrities
+            .iter()
+            .find(|(key, _)| key.name == name)
+            .map(|(_, entry)| &entry.digest)
+    }
+
+    /// The recorded files, of any type, along with their digests
+    pub fn files(&self) -> impl Iterator<Item = (&str, &OutputDigest)> {
+        self.result
+            .integrities
+            .iter()
+            .map(|(key, entry)| (key.name.as_str(), &entry.digest))
+    }
+
+    /// The recorded files of a type
+    pub fn files_of(&self, r#type: SriType) -> impl Iterator<Item = &str> {
+        self.result
+            .integrities
+            .iter()
+            .filter(move |(key, _)| key.r#type == r#type)
+            .map(|(key, _)| key.name.as_str())
+    }
+
     /// Record the content of a file for SRI
     pub async fn record_file(
         &mut self,
@@ -152,7 +182,31 @@ impl SriResult {
         create_nonce: &Option<String>,
     ) -> anyhow::Result<()> {
         let nonce = nonce_attr(create_nonce);
+
+        // The import map must precede the module loads, so it goes first.
+        let import_map = self
+            .integrities
+            .iter()
+            .filter(|(key, _)| key.r#type == SriType::ImportMap)
+            .filter_map(|(key, entry)| {
+                let integrity = entry.digest.to_integrity_value()?;
+                Some((format!("{base}{}", key.name), integrity.to_string()))
+            })
+            .collect::<BTreeMap<_, _>>();
+        if !import_map.is_empty() {
+            let import_map = serde_json::json!({ "integrity": import_map });
+            location
+                .prepend_html(
+                    head,
+                    &format!(r#"<script type="importmap"{nonce}>{import_map}</script>"#),
+                )
+                .context("Unable to write SRI import map.")?;
+        }
+
         for (SriKey { r#type, name }, SriEntry { digest, options }) in &self.integrities {
+            if *r#type == SriType::ImportMap {
+                continue;
+            }
             let preload = if let Some(integrity) = digest.to_integrity_value() {
                 format!(
                     r#"<link rel="{type}"{nonce} href="{base}{name}" crossorigin="{cross_origin}" integrity="{integrity}"{options}>"#,
@@ -170,3 +224,47 @@ impl SriResult {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::html_rewrite::DocumentOptions;
+    use std::convert::Infallible;
+
+    #[tokio::test]
+    async fn declares_lazy_modules_in_import_map() -> anyhow::Result<()> {
+        let mut sri = SriBuilder::new(IntegrityType::Sha384);
+        for (r#type, name) in [
+            (SriType::ModulePreload, "main.js"),
+            (SriType::ImportMap, "admin.js"),
+        ] {
+            sri.record(r#type, name, SriOptions::default(), || async {
+                Ok::<_, Infallible>(name)
+            })
+            .await?;
+        }
+        let mut dom = Document::new(
+            "<html><head><title>App</title></head></html>",
+            DocumentOptions::default(),
+        )?;
+        sri.build()
+            .inject(&mut dom, "html head", "/", CrossOrigin::Anonymous, &None)?;
+
+        let html = String::from_utf8(dom.into_inner())?;
+        let admin = OutputDigest::generate_from(IntegrityType::Sha384, "admin.js");
+        let admin = admin.to_integrity_value().map(|value| value.to_string());
+        assert!(
+            html.starts_with(&format!(
+                r#"<html><head><script type="importmap">{{"integrity":{{"/admin.js":"{}"}}}}</script><title>"#,
+                admin.unwrap_or_default()
+            )),
+            "{html}"
+        );
+        assert!(
+            html.contains(r#"<link rel="modulepreload" href="/main.js""#),
+            "{html}"
+        );
+        assert!(!html.contains(r#"href="/admin.js""#), "{html}");
+        Ok(())
+    }
+}
@@ -0,0 +1,227 @@
+//! The compiler warnings of a workspace, kept between builds.
+//!
+//! The compiler only reports the warnings of the modules it compiles. As spago and `purs ide` only
+//! compile the modules which changed, a warning is reported once, and then forgotten by the next
+//! build. The warnings are kept in the output directory instead, by source file, until the file
+//! changes or its module is compiled again. So denied warnings fail every build until fixed.
+
+use super::diagnostics::Diagnostic;
+use anyhow::{Context, Result};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use seahash::SeaHasher;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    hash::Hasher,
+    path::Path,
+};
+
+/// The file of the output directory the warnings are kept in.
+const WARNINGS_FILE: &str = "prank-warnings.json";
+
+/// The module header of a PureScript source file.
+static MODULE_HEADER: Lazy<Regex> = Lazy::new(|| {
+    #[allow(clippy::expect_used)]
+    Regex::new(r"(?m)^module\s+([\p{Lu}][\w.']*)").expect("valid module header regex")
+});
+
+/// The warnings of the source files of a workspace, by file relative to the workspace root.
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Warnings(BTreeMap<String, FileWarnings>);
+
+/// The warnings of a source file.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct FileWarnings {
+    /// The hash of the file's content, when the warnings were reported.
+    hash: u64,
+    /// The module of the file, if found.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    module: Option<String>,
+    warnings: Vec<Diagnostic>,
+}
+
+impl Warnings {
+    /// Load the warnings kept in an output directory.
+    ///
+    /// Missing or unreadable warnings are considered empty, they're reported again by the next
+    /// full build.
+    pub fn load(output_dir: &Path) -> Self {
+        let path = output_dir.join(WARNINGS_FILE);
+        let content = match std::fs::read(&path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Self::default(),
+            Err(err) => {
+                tracing::warn!("error reading {}: {err}", path.display());
+                return Self::default();
+            }
+        };
+        serde_json::from_slice(&content).unwrap_or_else(|err| {
+            tracing::warn!("error parsing {}: {err}", path.display());
+            Self::default()
+        })
+    }
+
+    /// Write the warnings to an output directory.
+    pub fn save(&self, output_dir: &Path) -> Result<()> {
+        let path = output_dir.join(WARNINGS_FILE);
+        if self.0.is_empty() && !path.exists() {
+            return Ok(());
+        }
+        std::fs::create_dir_all(output_dir)
+            .with_context(|| format!("error creating {}", output_dir.display()))?;
+        std::fs::write(&path, serde_json::to_vec(self)?)
+            .with_context(|| format!("error writing {}", path.display()))
+    }
+
+    /// Check if no warnings are kept.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Merge the warnings reported by a build, returning all warnings of the workspace.
+    ///
+    /// The reported warnings replace the kept ones of their files. Kept warnings are dropped once
+    /// their file changed or vanished, or once the module of the file was compiled again (its
+    /// output being one of the `compiled` modules).
+    pub fn update(
+        &mut self,
+        root: &Path,
+        reported: Vec<Diagnostic>,
+        compiled: &BTreeSet<String>,
+    ) -> Vec<Diagnostic> {
+        let mut by_file = BTreeMap::<String, Vec<Diagnostic>>::new();
+        let mut unplaced = vec![];
+        for warning in reported {
+            match &warning.file {
+                Some(file) => by_file.entry(file.clone()).or_default().push(warning),
+                None => unplaced.push(warning),
+            }
+        }
+
+        self.0.retain(|file, kept| {
+            !by_file.contains_key(file)
+                && kept
+                    .module
+                    .as_ref()
+                    .map_or(true, |module| !compiled.contains(module))
+                && read_source(&root.join(file)).is_some_and(|(hash, _)| hash == kept.hash)
+        });
+
+        for (file, warnings) in by_file {
+            let Some((hash, module)) = read_source(&root.join(&file)) else {
+                unplaced.extend(warnings);
+                continue;
+            };
+            self.0.insert(
+                file,
+                FileWarnings {
+                    hash,
+                    module,
+                    warnings,
+                },
+            );
+        }
+
+        self.0
+            .values()
+            .flat_map(|kept| kept.warnings.iter().cloned())
+            .chain(unplaced)
+            .collect()
+    }
+}
+
+/// The hash of a source file, along with the name of its module.
+fn read_source(path: &Path) -> Option<(u64, Option<String>)> {
+    let source = std::fs::read(path).ok()?;
+    let mut hasher = SeaHasher::new();
+    hasher.write(&source);
+    let module = MODULE_HEADER
+        .captures(&String::from_utf8_lossy(&source))
+        .map(|captures| captures[1].to_string());
+    Some((hasher.finish(), module))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipelines::purescript::diagnostics::Severity;
+
+    fn warning(file: &str, code: &str) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            code: code.to_string(),
+            message: code.to_string(),
+            file: Some(file.to_string()),
+            span: None,
+            suggestion: None,
+            excerpt: vec![],
+        }
+    }
+
+    fn codes(warnings: &[Diagnostic]) -> Vec<&str> {
+        warnings
+            .iter()
+            .map(|warning| warning.code.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn keeps_warnings_until_fixed() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let root = tmp.path();
+        std::fs::create_dir_all(root.join("src"))?;
+        std::fs::write(root.join("src/Main.purs"), "module Main where\n")?;
+        std::fs::write(
+            root.join("src/Util.purs"),
+            "-- utils\nmodule App.Util where\n",
+        )?;
+        let none = BTreeSet::new();
+
+        let mut warnings = Warnings::default();
+        let reported = vec![
+            warning("src/Main.purs", "UnusedImport"),
+            warning("src/Util.purs", "ShadowedName"),
+        ];
+        let all = warnings.update(root, reported, &none);
+        assert_eq!(codes(&all), ["UnusedImport", "ShadowedName"]);
+
+        // Nothing compiled, the warnings are kept, also by the next process.
+        let output = root.join("output");
+        warnings.save(&output)?;
+        let mut warnings = Warnings::load(&output);
+        assert_eq!(
+            codes(&warnings.update(root, vec![], &none)),
+            ["UnusedImport", "ShadowedName"]
+        );
+
+        // A changed file reports its warnings again, if it still has some.
+        std::fs::write(root.join("src/Main.purs"), "module Main where\n\nx = 1\n")?;
+        assert_eq!(
+            codes(&warnings.update(root, vec![], &none)),
+            ["ShadowedName"]
+        );
+
+        // A module compiled again without warnings is fixed, even if its file didn't change.
+        let compiled = BTreeSet::from(["App.Util".to_string()]);
+        assert!(warnings.update(root, vec![], &compiled).is_empty());
+        assert!(warnings.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn ignores_unreadable_warnings() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        assert_eq!(Warnings::load(tmp.path()), Warnings::default());
+        std::fs::write(tmp.path().join(WARNINGS_FILE), "{")?;
+        assert_eq!(Warnings::load(tmp.path()), Warnings::default());
+
+        // Warnings without a readable file are reported, but not kept.
+        let mut warnings = Warnings::default();
+        let reported = vec![warning("src/Missing.purs", "UnusedImport")];
+        let all = warnings.update(tmp.path(), reported, &BTreeSet::new());
+        assert_eq!(codes(&all), ["UnusedImport"]);
+        assert!(warnings.is_empty());
+        Ok(())
+    }
+}
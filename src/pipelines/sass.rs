@@ -1,8 +1,8 @@
 //! Sass/Scss asset pipeline.
 
 use super::{
-    data_target_path, AssetFile, AttrWriter, Attrs, PrankAssetPipelineOutput, ATTR_HREF,
-    ATTR_INLINE, ATTR_NO_MINIFY,
+    css_urls::rewrite_css_urls, data_target_path, AssetFile, AttrWriter, Attrs,
+    PrankAssetPipelineOutput, ATTR_HREF, ATTR_INLINE, ATTR_NO_MINIFY,
 };
 use crate::{
     common::{self, dist_relative, html_rewrite::Document, nonce_attr, target_path},
@@ -141,6 +141,18 @@ impl Sass {
             .with_context(|| format!("error reading CSS result file '{temp_target_file_path}'"))?;
         fs::remove_file(&temp_target_file_path).await?;
 
+        let result_dir =
+            target_path(&self.cfg.staging_dist, self.target_path.as_deref(), None).await?;
+        let css = rewrite_css_urls(
+            &self.cfg,
+            css,
+            &self.asset.path,
+            &result_dir,
+            self.use_inline,
+            self.cfg.minify_asset(self.no_minify),
+        )
+        .await?;
+
         // Check if the specified SASS/SCSS file should be inlined.
         let css_ref = if self.use_inline {
             // Avoid writing any files, return the CSS as a String.
@@ -160,8 +172,6 @@ impl Sass {
                 temp_target_file_name
             };
 
-            let result_dir =
-                target_path(&self.cfg.staging_dist, self.target_path.as_deref(), None).await?;
             let file_path = result_dir.join(&file_name);
             let file_href = dist_relative(&self.cfg.staging_dist, &file_path)?;
 
@@ -190,6 +200,7 @@ impl Sass {
 }
 
 /// The output of a sass/scss build pipeline.
+#[derive(Clone)]
 pub struct SassOutput {
     /// The runtime build config.
     pub cfg: Arc<RtcBuild>,
@@ -202,6 +213,7 @@ pub struct SassOutput {
 }
 
 /// The resulting CSS of the SASS/SCSS compilation.
+#[derive(Clone)]
 pub enum CssRef {
     /// CSS to be inlined (for `data-inline`).
     Inline(String),
@@ -0,0 +1,42 @@
+// The service worker generated by Prank, precaching the files of a build.
+const CACHE = "__PRANK_CACHE__";
+const PRECACHE = __PRANK_PRECACHE__;
+const INDEX = "__PRANK_INDEX__";
+
+self.addEventListener("install", (event) => {
+  event.waitUntil(
+    caches
+      .open(CACHE)
+      // bypass the HTTP cache, so that files without a hashed name are up to date as well
+      .then((cache) => cache.addAll(PRECACHE.map((url) => new Request(url, { cache: "reload" }))))
+      .then(() => self.skipWaiting()),
+  );
+});
+
+self.addEventListener("activate", (event) => {
+  event.waitUntil(
+    caches
+      .keys()
+      .then((keys) => Promise.all(keys
+        .filter((key) => key.startsWith("prank-") && key !== CACHE)
+        .map((key) => caches.delete(key))))
+      .then(() => self.clients.claim()),
+  );
+});
+
+self.addEventListener("fetch", (event) => {
+  const request = event.request;
+  if (request.method !== "GET") return;
+
+  if (request.mode === "navigate") {
+    // pages come from the network, unless offline
+    event.respondWith(fetch(request).catch(() => caches
+      .match(request, { cacheName: CACHE })
+      .then((cached) => cached ?? caches.match(INDEX, { cacheName: CACHE }))));
+    return;
+  }
+
+  event.respondWith(
+    caches.match(request, { cacheName: CACHE }).then((cached) => cached ?? fetch(request)),
+  );
+});
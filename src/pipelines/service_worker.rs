@@ -0,0 +1,123 @@
+//! The generated service worker of release builds.
+//!
+//! The service worker precaches every file of the dist dir. Its cache is named after the content
+//! hashes of the files, the ones used for hashed file names, so that a build changing any file
+//! installs a new cache, and removes the previous one once activated.
+
+use crate::{
+    common::{self, nonce_attr},
+    config::rt::RtcBuild,
+};
+use anyhow::{Context, Result};
+use std::{hash::Hasher, path::Path};
+use tokio::fs;
+
+/// The name of the service worker file, in the dist dir.
+pub const SERVICE_WORKER_FILE: &str = "sw.js";
+
+/// The template of the service worker.
+const TEMPLATE: &str = include_str!("service_worker.js");
+
+/// The script registering the service worker.
+pub fn registration(cfg: &RtcBuild) -> String {
+    format!(
+        r#"
+<script{nonce}>if ("serviceWorker" in navigator) navigator.serviceWorker.register("{base}{SERVICE_WORKER_FILE}");</script>"#,
+        nonce = nonce_attr(&cfg.create_nonce),
+        base = cfg.public_url,
+    )
+}
+
+/// Write the service worker to the dist dir, precaching all of its files except for source maps.
+///
+/// Returns the number of precached files.
+pub async fn write_service_worker(cfg: &RtcBuild) -> Result<usize> {
+    let files = {
+        let dist = cfg.staging_dist.clone();
+        tokio::task::spawn_blocking(move || content_hashes(&dist))
+            .await
+            .context("error awaiting service worker precache")??
+    };
+
+    // offline navigations to pages which aren't cached fall back to the first page
+    let index = match cfg.pages.first() {
+        Some(page) => format!("{}{}", cfg.public_url, page.output),
+        None => cfg.public_url.to_string(),
+    };
+    let service_worker = render(&cfg.public_url, &index, &files)?;
+    fs::write(cfg.staging_dist.join(SERVICE_WORKER_FILE), service_worker)
+        .await
+        .context("error writing service worker")?;
+
+    Ok(files.len())
+}
+
+/// The files to precache, relative to the dist dir, with the hashes of their content.
+fn content_hashes(dist: &Path) -> Result<Vec<(String, u64)>> {
+    let mut files = Vec::new();
+    for path in common::dist_files(dist)? {
+        let name = common::dist_relative(dist, &path)?;
+        if name == SERVICE_WORKER_FILE || name.ends_with(".map") {
+            continue;
+        }
+        let bytes =
+            std::fs::read(&path).with_context(|| format!("error reading {}", path.display()))?;
+        files.push((name, seahash::hash(&bytes)));
+    }
+    Ok(files)
+}
+
+/// Render the service worker, for the given files and their content hashes.
+fn render(public_url: &str, index: &str, files: &[(String, u64)]) -> Result<String> {
+    let mut hasher = seahash::SeaHasher::new();
+    for (name, hash) in files {
+        hasher.write(name.as_bytes());
+        hasher.write_u64(*hash);
+    }
+    let cache = format!("prank-{:0>16x}", hasher.finish());
+
+    let urls = files
+        .iter()
+        .map(|(name, _)| format!("{public_url}{name}"))
+        .collect::<Vec<_>>();
+    let precache = serde_json::to_string(&urls).context("error serializing precached files")?;
+
+    Ok(TEMPLATE
+        .replace("__PRANK_CACHE__", &cache)
+        .replace("__PRANK_PRECACHE__", &precache)
+        .replace("__PRANK_INDEX__", index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_cache() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        std::fs::write(tmp.path().join("index.html"), "<html></html>")?;
+        std::fs::write(tmp.path().join("index-1234.js"), "main();")?;
+        std::fs::write(tmp.path().join("index-1234.js.map"), "{}")?;
+        std::fs::write(tmp.path().join(SERVICE_WORKER_FILE), "")?;
+
+        let files = content_hashes(tmp.path())?;
+        let names = files
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["index-1234.js", "index.html"]);
+
+        let service_worker = render("/app/", "/app/index.html", &files)?;
+        assert!(service_worker
+            .contains(r#"const PRECACHE = ["/app/index-1234.js","/app/index.html"];"#));
+        assert!(service_worker.contains(r#"const INDEX = "/app/index.html";"#));
+        assert!(!service_worker.contains("__PRANK_"));
+
+        // changing a file changes the name of the cache
+        std::fs::write(tmp.path().join("index.html"), "<html><body></body></html>")?;
+        let changed = render("/app/", "/app/index.html", &content_hashes(tmp.path())?)?;
+        let cache = |service_worker: &str| service_worker.lines().nth(1).map(str::to_string);
+        assert_ne!(cache(&service_worker), cache(&changed));
+        Ok(())
+    }
+}
@@ -0,0 +1,184 @@
+//! Asset pipelines shared by the pages of a build.
+
+use super::{
+    purescript::{PureScriptApp, ATTR_INITIALIZER},
+    Attrs, PrankAsset, PrankAssetPipelineOutput, ATTR_HREF, ATTR_REL, ATTR_SRC, PRANK_ID,
+};
+use anyhow::{Context, Result};
+use std::{
+    collections::HashMap,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+use tokio::{
+    sync::{Mutex, OnceCell},
+    task::JoinHandle,
+};
+
+/// The output of a shared asset pipeline, `None` if it failed.
+type SharedOutput = Arc<OnceCell<Option<PrankAssetPipelineOutput>>>;
+
+/// The asset pipelines of a build, shared by its pages.
+///
+/// An asset referenced by the same element in several pages is only built once, by the first page
+/// spawning it. Every page then finalizes a copy of the output for its own element. If the
+/// pipeline fails, only the page which built it reports the error.
+#[derive(Clone, Default)]
+pub struct SharedAssets(Arc<Mutex<HashMap<String, SharedOutput>>>);
+
+impl SharedAssets {
+    /// Spawn the pipeline of an asset, unless it was spawned for the same key already.
+    ///
+    /// The output is assigned to the element of the given ID.
+    pub fn spawn(
+        &self,
+        key: String,
+        id: Option<usize>,
+        asset: PrankAsset,
+    ) -> JoinHandle<Result<PrankAssetPipelineOutput>> {
+        let outputs = self.0.clone();
+        tokio::spawn(async move {
+            let cell = outputs.lock().await.entry(key).or_default().clone();
+
+            let mut error = None;
+            let output = cell
+                .get_or_init(|| async {
+                    let result = asset
+                        .spawn()
+                        .await
+                        .context("failed to await asset pipeline")
+                        .and_then(|result| result);
+                    match result {
+                        Ok(output) => Some(output),
+                        Err(err) => {
+                            error = Some(err);
+                            None
+                        }
+                    }
+                })
+                .await;
+
+            match (output, error) {
+                (Some(output), _) => {
+                    let mut output = output.clone();
+                    if let Some(id) = id {
+                        output.set_id(id);
+                    }
+                    Ok(output)
+                }
+                (None, Some(err)) => Err(err),
+                // the page which built the asset reports the error
+                (None, None) => Ok(PrankAssetPipelineOutput::None),
+            }
+        })
+    }
+}
+
+/// The attributes holding paths relative to the directory of the page.
+const PAGE_RELATIVE: &[&str] = &[ATTR_HREF, ATTR_SRC, ATTR_INITIALIZER];
+
+/// The key of an asset element, identifying the asset independently of the page referencing it.
+///
+/// The paths of the attributes are resolved relative to the directory of the page. A PureScript
+/// link without `href` uses the `spago.yaml` next to the page, so the directory of the page is
+/// part of its key.
+pub fn asset_key(tag: &str, attrs: &Attrs, html_dir: &Path) -> String {
+    let mut key = attrs
+        .iter()
+        .filter(|(name, _)| name.as_str() != PRANK_ID)
+        .map(
+            |(name, attr)| match PAGE_RELATIVE.contains(&name.as_str()) {
+                true => {
+                    let path = normalize(&html_dir.join(&attr.value));
+                    format!("{name}={path:?}")
+                }
+                false => format!("{name}={:?}", attr.value),
+            },
+        )
+        .collect::<Vec<_>>();
+    let is_purescript = attrs
+        .get(ATTR_REL)
+        .is_some_and(|rel| rel.value == PureScriptApp::TYPE_PURESCRIPT_APP);
+    if tag == "link" && is_purescript && !attrs.contains_key(ATTR_HREF) {
+        key.push(format!("dir={:?}", normalize(html_dir)));
+    }
+    key.sort();
+    format!("{tag} {}", key.join(" "))
+}
+
+/// Normalize a path lexically, resolving `.` and `..` components.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attrs(attrs: &[(&str, &str)]) -> Attrs {
+        attrs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.into()))
+            .collect()
+    }
+
+    #[test]
+    fn keys_assets_by_path() {
+        let root = Path::new("/site");
+        let style = asset_key(
+            "link",
+            &attrs(&[("rel", "css"), ("href", "style.css"), (PRANK_ID, "0")]),
+            root,
+        );
+        let nested = asset_key(
+            "link",
+            &attrs(&[("href", "../style.css"), (PRANK_ID, "3"), ("rel", "css")]),
+            &root.join("app"),
+        );
+        assert_eq!(style, nested);
+
+        let inlined = asset_key(
+            "link",
+            &attrs(&[("rel", "css"), ("href", "style.css"), ("data-inline", "")]),
+            root,
+        );
+        assert_ne!(style, inlined);
+    }
+
+    #[test]
+    fn keys_page_relative_applications_by_page() {
+        let root = Path::new("/site");
+        let app = attrs(&[("rel", "purescript"), ("data-type", "main")]);
+        assert_ne!(
+            asset_key("link", &app, root),
+            asset_key("link", &app, &root.join("app"))
+        );
+
+        let initialized = attrs(&[
+            ("rel", "purescript"),
+            ("href", "/site/spago.yaml"),
+            (ATTR_INITIALIZER, "init.js"),
+        ]);
+        assert_ne!(
+            asset_key("link", &initialized, root),
+            asset_key("link", &initialized, &root.join("app"))
+        );
+
+        // the same files are the same application
+        let linked = attrs(&[("rel", "purescript"), ("href", "../spago.yaml")]);
+        assert_eq!(
+            asset_key("link", &linked, &root.join("app")),
+            asset_key("link", &linked, &root.join("blog"))
+        );
+    }
+}
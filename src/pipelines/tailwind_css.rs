@@ -1,8 +1,8 @@
 //! Tailwind CSS asset pipeline.
 
 use super::{
-    data_target_path, AssetFile, AttrWriter, Attrs, PrankAssetPipelineOutput, ATTR_CONFIG,
-    ATTR_HREF, ATTR_INLINE, ATTR_NO_MINIFY,
+    css_urls::rewrite_css_urls, data_target_path, AssetFile, AttrWriter, Attrs,
+    PrankAssetPipelineOutput, ATTR_CONFIG, ATTR_HREF, ATTR_INLINE, ATTR_NO_MINIFY,
 };
 use crate::{
     common::{self, dist_relative, html_rewrite::Document, nonce_attr, target_path},
@@ -122,6 +122,18 @@ impl TailwindCss {
         let css = fs::read_to_string(&file_path).await?;
         fs::remove_file(&file_path).await?;
 
+        let result_dir =
+            target_path(&self.cfg.staging_dist, self.target_path.as_deref(), None).await?;
+        let css = rewrite_css_urls(
+            &self.cfg,
+            css,
+            &self.asset.path,
+            &result_dir,
+            self.use_inline,
+            self.cfg.minify_asset(self.no_minify),
+        )
+        .await?;
+
         // Check if the specified tailwind css file should be inlined.
         let css_ref = if self.use_inline {
             // Avoid writing any files, return the CSS as a String.
@@ -140,8 +152,6 @@ impl TailwindCss {
                 file_name
             };
 
-            let result_dir =
-                target_path(&self.cfg.staging_dist, self.target_path.as_deref(), None).await?;
             let file_path = result_dir.join(&file_name);
             let file_href = dist_relative(&self.cfg.staging_dist, &file_path)?;
 
@@ -167,6 +177,7 @@ impl TailwindCss {
 }
 
 /// The output of a Tailwind CSS build pipeline.
+#[derive(Clone)]
 pub struct TailwindCssOutput {
     /// The runtime build config.
     pub cfg: Arc<RtcBuild>,
@@ -179,6 +190,7 @@ pub struct TailwindCssOutput {
 }
 
 /// The resulting CSS of the Tailwind CSS compilation.
+#[derive(Clone)]
 pub enum CssRef {
     /// CSS to be inlined (for `data-inline`).
     Inline(String),
@@ -0,0 +1,236 @@
+//! Web app manifest asset pipeline.
+
+use super::{
+    data_target_path, prank_id_selector, AssetFile, Attrs, PrankAssetPipelineOutput, ATTR_HREF,
+    ATTR_NO_MINIFY,
+};
+use crate::{
+    common::{dist_relative, html_rewrite::Document, nonce_attr, target_path},
+    config::rt::RtcBuild,
+    pipelines::AssetFileType,
+    processing::image::ImageType,
+};
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::{fs, task::JoinHandle};
+
+/// A web app manifest asset pipeline.
+pub struct WebManifest {
+    /// The ID of this pipeline's source HTML element.
+    id: usize,
+    /// Runtime build config.
+    cfg: Arc<RtcBuild>,
+    /// The asset file being processed.
+    asset: AssetFile,
+    /// Whether to minify or not
+    no_minify: bool,
+    /// Optional target path inside the dist dir.
+    target_path: Option<PathBuf>,
+}
+
+impl WebManifest {
+    pub const TYPE_WEBMANIFEST: &'static str = "webmanifest";
+
+    pub async fn new(
+        cfg: Arc<RtcBuild>,
+        html_dir: Arc<PathBuf>,
+        attrs: Attrs,
+        id: usize,
+    ) -> Result<Self> {
+        // Build the path to the target asset.
+        let href_attr = attrs.get(ATTR_HREF).context(
+            r#"required attr `href` missing for <link data-prank rel="webmanifest" .../> element"#,
+        )?;
+        let mut path = PathBuf::new();
+        path.extend(href_attr.split('/'));
+        let asset = AssetFile::new(&html_dir, path).await?;
+
+        let no_minify = attrs.contains_key(ATTR_NO_MINIFY);
+        let target_path = data_target_path(&attrs)?;
+
+        Ok(Self {
+            id,
+            cfg,
+            asset,
+            no_minify,
+            target_path,
+        })
+    }
+
+    /// Spawn the pipeline for this asset type.
+    #[tracing::instrument(level = "trace", skip(self))]
+    pub fn spawn(self) -> JoinHandle<Result<PrankAssetPipelineOutput>> {
+        tokio::spawn(self.run())
+    }
+
+    /// Run this pipeline.
+    #[tracing::instrument(level = "trace", skip(self))]
+    async fn run(self) -> Result<PrankAssetPipelineOutput> {
+        let rel_path = crate::common::strip_prefix(&self.asset.path);
+        tracing::debug!(path = ?rel_path, "processing web app manifest");
+
+        let mut manifest: Value = serde_json::from_str(&self.asset.read_to_string().await?)
+            .with_context(|| format!("error parsing web app manifest {rel_path:?}"))?;
+
+        let result_dir =
+            target_path(&self.cfg.staging_dist, self.target_path.as_deref(), None).await?;
+        let source_dir = self
+            .asset
+            .path
+            .parent()
+            .context("web app manifest has no parent directory")?;
+
+        // The icons are written next to the manifest, so that their URLs stay relative to it.
+        for image in images(&mut manifest) {
+            let Some(src) = image.get("src").and_then(Value::as_str) else {
+                continue;
+            };
+            if !is_relative_url(src) {
+                continue;
+            }
+            let file_name = self.copy_image(source_dir, src, &result_dir).await?;
+            image["src"] = Value::String(file_name);
+        }
+
+        let content = match self.cfg.minify_asset(self.no_minify) {
+            true => serde_json::to_string(&manifest),
+            false => serde_json::to_string_pretty(&manifest),
+        }
+        .context("error serializing web app manifest")?;
+
+        let file_name = match self.cfg.filehash {
+            true => format!(
+                "{}-{:0>16x}.webmanifest",
+                &self.asset.file_stem.to_string_lossy(),
+                seahash::hash(content.as_bytes())
+            ),
+            false => format!("{}.webmanifest", &self.asset.file_stem.to_string_lossy()),
+        };
+        let file_path = result_dir.join(&file_name);
+        let file = dist_relative(&self.cfg.staging_dist, &file_path)?;
+        fs::write(&file_path, content)
+            .await
+            .with_context(|| format!("error writing web app manifest '{}'", file_path.display()))?;
+
+        tracing::debug!(path = ?rel_path, "finished processing web app manifest");
+        Ok(PrankAssetPipelineOutput::WebManifest(WebManifestOutput {
+            cfg: self.cfg.clone(),
+            id: self.id,
+            file,
+        }))
+    }
+
+    /// Copy an image referenced by the manifest to the result dir, returning its file name.
+    async fn copy_image(&self, source_dir: &Path, src: &str, result_dir: &Path) -> Result<String> {
+        let mut path = PathBuf::new();
+        path.extend(src.split(['?', '#']).next().unwrap_or(src).split('/'));
+        let image = AssetFile::new(source_dir, path)
+            .await
+            .with_context(|| format!("error reading image '{src}' of the web app manifest"))?;
+
+        let image_type = ImageType::from_path(&image.path);
+        let file = image
+            .copy(
+                &self.cfg.staging_dist,
+                result_dir,
+                self.cfg.filehash,
+                self.cfg.minify_asset(self.no_minify),
+                AssetFileType::Image(image_type),
+            )
+            .await?;
+
+        Ok(Path::new(&file)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or(file))
+    }
+}
+
+/// The image objects of a manifest: its icons and screenshots, and the icons of its shortcuts.
+fn images(manifest: &mut Value) -> Vec<&mut Value> {
+    let Value::Object(object) = manifest else {
+        return vec![];
+    };
+    let mut images = vec![];
+    for (key, value) in object.iter_mut() {
+        let Some(entries) = value.as_array_mut() else {
+            continue;
+        };
+        match key.as_str() {
+            "icons" | "screenshots" => images.extend(entries.iter_mut()),
+            "shortcuts" => images.extend(
+                entries
+                    .iter_mut()
+                    .filter_map(|shortcut| shortcut.get_mut("icons")?.as_array_mut())
+                    .flatten(),
+            ),
+            _ => {}
+        }
+    }
+    images
+}
+
+/// Check if a URL of the manifest refers to a file relative to it.
+fn is_relative_url(url: &str) -> bool {
+    !(url.is_empty() || url.starts_with('/') || url.starts_with('#') || url.contains(':'))
+}
+
+/// The output of a web app manifest build pipeline.
+#[derive(Clone)]
+pub struct WebManifestOutput {
+    /// The runtime build config.
+    pub cfg: Arc<RtcBuild>,
+    /// The ID of this pipeline.
+    pub id: usize,
+    /// Name of the finalized output file.
+    pub file: String,
+}
+
+impl WebManifestOutput {
+    pub async fn finalize(self, dom: &mut Document) -> Result<()> {
+        dom.replace_with_html(
+            &prank_id_selector(self.id),
+            &format!(
+                r#"<link rel="manifest" href="{base}{file}"{nonce}/>"#,
+                base = &self.cfg.public_url,
+                file = self.file,
+                nonce = nonce_attr(&self.cfg.create_nonce),
+            ),
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn finds_images() {
+        let mut manifest = json!({
+            "name": "App",
+            "icons": [{ "src": "icons/192.png", "sizes": "192x192" }],
+            "screenshots": [{ "src": "screenshot.png" }],
+            "shortcuts": [{ "name": "New", "url": "/new", "icons": [{ "src": "new.svg" }] }],
+        });
+        let srcs = images(&mut manifest)
+            .into_iter()
+            .map(|image| image["src"].as_str().unwrap_or_default().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(srcs, ["icons/192.png", "screenshot.png", "new.svg"]);
+    }
+
+    #[test]
+    fn relative_urls() {
+        assert!(is_relative_url("icons/192.png"));
+        assert!(is_relative_url("./icon.png"));
+        assert!(!is_relative_url("/icon.png"));
+        assert!(!is_relative_url("https://example.com/icon.png"));
+        assert!(!is_relative_url("data:image/png;base64,AAAA"));
+    }
+}
@@ -0,0 +1,166 @@
+//! Compression of assets.
+
+use crate::{common, config::types::Precompress};
+use anyhow::{Context, Result};
+use flate2::{write::GzEncoder, Compression};
+use futures_util::{stream, StreamExt};
+use std::{
+    io::Write,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+};
+
+/// The extensions of files worth compressing, other files (e.g. images) are compressed already.
+const COMPRESSIBLE: &[&str] = &[
+    "css",
+    "html",
+    "ico",
+    "js",
+    "json",
+    "map",
+    "mjs",
+    "svg",
+    "txt",
+    "wasm",
+    "webmanifest",
+    "xml",
+];
+
+/// Compress some bytes with gzip, at the best compression level.
+pub fn gzip(bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+    encoder
+        .write_all(bytes)
+        .context("error compressing with gzip")?;
+    encoder.finish().context("error compressing with gzip")
+}
+
+/// Compress some bytes with brotli, at the best compression level.
+pub fn brotli(bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = brotli::CompressorWriter::new(Vec::new(), 4096, 11, 22);
+    encoder
+        .write_all(bytes)
+        .context("error compressing with brotli")?;
+    encoder.flush().context("error compressing with brotli")?;
+    Ok(encoder.into_inner())
+}
+
+/// Precompress the files of a dist dir, writing the compressed variants next to them (e.g.
+/// `index.js.gz`), like expected by `gzip_static` of nginx.
+///
+/// Only compressible files of at least `threshold` bytes are compressed, and variants which
+/// aren't smaller than the file itself are dropped. The files are compressed in parallel. Returns
+/// the number of files which got a compressed variant.
+pub async fn precompress_dist(
+    dist: &Path,
+    formats: &[Precompress],
+    threshold: u64,
+) -> Result<usize> {
+    let files = {
+        let dist = dist.to_path_buf();
+        tokio::task::spawn_blocking(move || common::dist_files(&dist))
+            .await
+            .context("error awaiting dist files")??
+    };
+
+    let parallelism = std::thread::available_parallelism().map_or(1, NonZeroUsize::get);
+    let mut results = stream::iter(files.into_iter().filter(|path| is_compressible(path)))
+        .map(|path| {
+            let formats = formats.to_vec();
+            tokio::task::spawn_blocking(move || precompress_file(&path, &formats, threshold))
+        })
+        .buffer_unordered(parallelism);
+
+    let mut compressed = 0;
+    while let Some(result) = results.next().await {
+        if result.context("error awaiting compression")?? {
+            compressed += 1;
+        }
+    }
+    Ok(compressed)
+}
+
+/// Write the compressed variants of a file, returning if any was written.
+fn precompress_file(path: &Path, formats: &[Precompress], threshold: u64) -> Result<bool> {
+    let bytes = std::fs::read(path).with_context(|| format!("error reading {}", path.display()))?;
+    if (bytes.len() as u64) < threshold {
+        return Ok(false);
+    }
+
+    let mut written = false;
+    for format in formats {
+        let compressed = match format {
+            Precompress::Gzip => gzip(&bytes)?,
+            Precompress::Brotli => brotli(&bytes)?,
+        };
+        if compressed.len() < bytes.len() {
+            let target = compressed_path(path, *format);
+            std::fs::write(&target, compressed)
+                .with_context(|| format!("error writing {}", target.display()))?;
+            written = true;
+        }
+    }
+    Ok(written)
+}
+
+/// Check if a file is worth compressing.
+fn is_compressible(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| COMPRESSIBLE.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+/// The path of the compressed variant of a file.
+fn compressed_path(path: &Path, format: Precompress) -> PathBuf {
+    let mut target = path.as_os_str().to_owned();
+    target.push(".");
+    target.push(format.extension());
+    target.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    #[tokio::test]
+    async fn precompresses_gzip() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let script = "console.log('hello, world');\n".repeat(100);
+        std::fs::write(tmp.path().join("index.js"), &script)?;
+        std::fs::write(tmp.path().join("small.css"), "body{}")?;
+        std::fs::write(tmp.path().join("image.png"), vec![0u8; 4096])?;
+
+        let written = precompress_dist(tmp.path(), &[Precompress::Gzip], 1024).await?;
+        assert_eq!(written, 1);
+        assert!(!tmp.path().join("small.css.gz").exists());
+        assert!(!tmp.path().join("image.png.gz").exists());
+
+        let mut decompressed = String::new();
+        GzDecoder::new(std::fs::File::open(tmp.path().join("index.js.gz"))?)
+            .read_to_string(&mut decompressed)?;
+        assert_eq!(decompressed, script);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn precompresses_brotli() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let script = "console.log('hello, world');\n".repeat(100);
+        std::fs::write(tmp.path().join("index.js"), &script)?;
+        std::fs::write(tmp.path().join("style.css"), "body{}".repeat(200))?;
+
+        // Files are counted once, whatever the number of their variants.
+        let formats = [Precompress::Gzip, Precompress::Brotli];
+        let written = precompress_dist(tmp.path(), &formats, 1024).await?;
+        assert_eq!(written, 2);
+        assert!(tmp.path().join("style.css.gz").exists());
+
+        let mut decompressed = String::new();
+        brotli::Decompressor::new(std::fs::File::open(tmp.path().join("index.js.br"))?, 4096)
+            .read_to_string(&mut decompressed)?;
+        assert_eq!(decompressed, script);
+        Ok(())
+    }
+}
@@ -0,0 +1,614 @@
+//! Optimization, resizing and conversion of images.
+//!
+//! Images are optimized losslessly: PNG files are recompressed with oxipng, SVG files are
+//! minified, and the metadata not affecting the rendering is stripped from JPEG, WebP and GIF
+//! files. Resizing images and re-encoding them, either to another format or with a given quality,
+//! requires ImageMagick.
+
+use crate::common::{self, check_target_not_found_err};
+use anyhow::{Context, Result};
+use oxipng::Options;
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+const PNG_OPTIMIZATION_LEVEL: u8 = 6;
+
+/// The type of an image, by its file format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageType {
+    Png,
+    Jpeg,
+    Webp,
+    Avif,
+    Gif,
+    Svg,
+    Other,
+}
+
+impl ImageType {
+    /// The type of an image file, guessed from its extension.
+    pub fn from_path(path: &Path) -> Self {
+        match mime_guess::from_path(path)
+            .first_or_octet_stream()
+            .essence_str()
+        {
+            "image/png" => Self::Png,
+            "image/jpeg" => Self::Jpeg,
+            "image/webp" => Self::Webp,
+            "image/avif" => Self::Avif,
+            "image/gif" => Self::Gif,
+            "image/svg+xml" => Self::Svg,
+            _ => Self::Other,
+        }
+    }
+
+    /// The type of an output format, e.g. `webp`.
+    pub fn from_format(format: &str) -> Option<Self> {
+        match format {
+            "png" => Some(Self::Png),
+            "jpg" | "jpeg" => Some(Self::Jpeg),
+            "webp" => Some(Self::Webp),
+            "avif" => Some(Self::Avif),
+            "gif" => Some(Self::Gif),
+            _ => None,
+        }
+    }
+
+    /// The file extension of the format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Jpeg => "jpg",
+            Self::Webp => "webp",
+            Self::Avif => "avif",
+            Self::Gif => "gif",
+            Self::Svg => "svg",
+            Self::Other => "",
+        }
+    }
+
+    /// The MIME type of the format.
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            Self::Png => "image/png",
+            Self::Jpeg => "image/jpeg",
+            Self::Webp => "image/webp",
+            Self::Avif => "image/avif",
+            Self::Gif => "image/gif",
+            Self::Svg => "image/svg+xml",
+            Self::Other => "application/octet-stream",
+        }
+    }
+
+    /// Check if the format is a raster format, which can be resized and converted.
+    pub fn is_raster(self) -> bool {
+        !matches!(self, Self::Svg | Self::Other)
+    }
+}
+
+/// Optimize an image losslessly, keeping the original if it can't be made smaller.
+pub fn optimize(bytes: Vec<u8>, image_type: ImageType) -> Result<Vec<u8>> {
+    let optimized = match image_type {
+        ImageType::Png => Some(
+            oxipng::optimize_from_memory(&bytes, &Options::from_preset(PNG_OPTIMIZATION_LEVEL))
+                .context("error optimizing PNG")?,
+        ),
+        ImageType::Svg => std::str::from_utf8(&bytes)
+            .ok()
+            .map(|svg| minify_svg(svg).into_bytes()),
+        ImageType::Jpeg => strip_jpeg(&bytes),
+        ImageType::Webp => strip_webp(&bytes),
+        ImageType::Gif => strip_gif(&bytes),
+        ImageType::Avif | ImageType::Other => None,
+    };
+    Ok(match optimized {
+        Some(optimized) if optimized.len() < bytes.len() => optimized,
+        _ => bytes,
+    })
+}
+
+/// The width of an image in pixels, read from its header.
+pub fn width(bytes: &[u8], image_type: ImageType) -> Option<u32> {
+    let u16_le = |at: usize| Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?));
+    let u16_be = |at: usize| Some(u16::from_be_bytes(bytes.get(at..at + 2)?.try_into().ok()?));
+    match image_type {
+        ImageType::Png => {
+            (bytes.get(12..16)? == b"IHDR").then_some(())?;
+            Some(u32::from_be_bytes(bytes.get(16..20)?.try_into().ok()?))
+        }
+        ImageType::Gif => u16_le(6).map(u32::from),
+        ImageType::Jpeg => jpeg_segments(bytes)?
+            .into_iter()
+            // the start of frame markers, except for the DHT, JPG and DAC ones
+            .find(|(marker, _)| {
+                (0xC0..=0xCF).contains(marker) && ![0xC4, 0xC8, 0xCC].contains(marker)
+            })
+            .and_then(|(_, range)| u16_be(range.start + 3))
+            .map(u32::from),
+        ImageType::Webp => {
+            let (_, chunks) = webp_chunks(bytes)?;
+            let (fourcc, range) = chunks.first()?;
+            let data = &bytes[range.clone()];
+            match *fourcc {
+                b"VP8X" => {
+                    let width =
+                        u32::from_le_bytes([*data.get(4)?, *data.get(5)?, *data.get(6)?, 0]);
+                    Some(width + 1)
+                }
+                b"VP8L" => {
+                    let bits = u32::from_le_bytes(data.get(1..5)?.try_into().ok()?);
+                    Some((bits & 0x3FFF) + 1)
+                }
+                b"VP8 " => {
+                    let width = u16::from_le_bytes(data.get(6..8)?.try_into().ok()?);
+                    Some(u32::from(width & 0x3FFF))
+                }
+                _ => None,
+            }
+        }
+        ImageType::Avif | ImageType::Svg | ImageType::Other => None,
+    }
+}
+
+/// Find the ImageMagick command, required for resizing and converting images.
+///
+/// ImageMagick 6 only has a `convert` command, which is only used if it is the one of
+/// ImageMagick, and not e.g. the file system conversion tool of Windows.
+pub fn magick() -> Result<PathBuf> {
+    which::which("magick")
+        .or_else(|err| {
+            which::which("convert")
+                .ok()
+                .filter(|convert| is_imagemagick(convert))
+                .ok_or(err)
+        })
+        .map_err(|err| check_target_not_found_err(err.into(), "magick"))
+        .context("resizing or converting images requires ImageMagick (the 'magick' command)")
+}
+
+/// Check if a command is the one of ImageMagick, by its version.
+fn is_imagemagick(command: &Path) -> bool {
+    std::process::Command::new(command)
+        .arg("-version")
+        .stdin(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .output()
+        .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).contains("ImageMagick"))
+}
+
+/// Encode an image using ImageMagick, optionally resized to a width and with a quality.
+pub async fn encode(
+    magick: &Path,
+    source: &Path,
+    source_type: ImageType,
+    width: Option<u32>,
+    format: ImageType,
+    quality: Option<u8>,
+) -> Result<Vec<u8>> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let target = std::env::temp_dir().join(format!(
+        "prank-image-{}-{}.{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed),
+        format.extension()
+    ));
+
+    let mut args = vec![source.as_os_str().to_owned()];
+    if source_type == ImageType::Gif {
+        args.push("-coalesce".into());
+    }
+    // strip the metadata, but keep the color profile
+    args.push("+profile".into());
+    args.push("!icc,*".into());
+    if let Some(width) = width {
+        args.push("-resize".into());
+        args.push(format!("{width}x").into());
+    }
+    if let Some(quality) = quality {
+        args.push("-quality".into());
+        args.push(quality.to_string().into());
+    }
+    args.push(target.as_os_str().to_owned());
+
+    let dir = source.parent().unwrap_or(Path::new("."));
+    let result = common::run_command("ImageMagick", magick, &args, dir).await;
+    let bytes = match result {
+        Ok(()) => tokio::fs::read(&target)
+            .await
+            .with_context(|| format!("error reading {}", target.display())),
+        Err(err) => Err(err),
+    };
+    let _ = tokio::fs::remove_file(&target).await;
+    bytes
+}
+
+/// Minify an SVG image, removing comments, the XML declaration, metadata and the indentation
+/// between elements.
+fn minify_svg(svg: &str) -> String {
+    let mut out = String::with_capacity(svg.len());
+    let mut rest = svg;
+    // whitespace is significant within text elements
+    let mut text_depth = 0usize;
+
+    /// Split off the input up to and including a terminator, or all of it.
+    fn through<'a>(rest: &'a str, end: &str) -> (&'a str, &'a str) {
+        match rest.find(end) {
+            Some(index) => rest.split_at(index + end.len()),
+            None => (rest, ""),
+        }
+    }
+
+    while !rest.is_empty() {
+        if rest.starts_with("<!--") {
+            rest = through(rest, "-->").1;
+        } else if rest.starts_with("<![CDATA[") {
+            let (cdata, next) = through(rest, "]]>");
+            out.push_str(cdata);
+            rest = next;
+        } else if rest.starts_with("<?xml ") || rest.starts_with("<?xml?") {
+            rest = through(rest, "?>").1;
+        } else if rest.starts_with("<!DOCTYPE") {
+            // keep doctypes declaring entities
+            let (doctype, next) = match rest.find(['[', '>']).map(|index| &rest[index..=index]) {
+                Some("[") => through(rest, "]>"),
+                _ => through(rest, ">"),
+            };
+            if doctype.contains('[') {
+                out.push_str(doctype);
+            }
+            rest = next;
+        } else if is_tag(rest, "metadata") {
+            let (tag, next) = split_tag(rest);
+            rest = match tag.ends_with("/>") {
+                true => next,
+                false => through(next, "</metadata>").1,
+            };
+        } else if rest.starts_with('<') {
+            let (tag, next) = split_tag(rest);
+            if is_tag(tag, "text") && !tag.ends_with("/>") {
+                text_depth += 1;
+            } else if tag.starts_with("</text") {
+                text_depth = text_depth.saturating_sub(1);
+            }
+            out.push_str(tag);
+            rest = next;
+        } else {
+            let (text, next) = rest.split_at(rest.find('<').unwrap_or(rest.len()));
+            let indentation = text.trim().is_empty() && text.contains('\n');
+            if text_depth > 0 || !indentation {
+                out.push_str(text);
+            }
+            rest = next;
+        }
+    }
+    out
+}
+
+/// Check if the input starts with an opening tag of the given name.
+fn is_tag(input: &str, name: &str) -> bool {
+    input
+        .strip_prefix('<')
+        .and_then(|input| input.strip_prefix(name))
+        .and_then(|input| input.chars().next())
+        .is_some_and(|c| c.is_whitespace() || c == '>' || c == '/')
+}
+
+/// Split off a tag, respecting quoted attribute values.
+fn split_tag(input: &str) -> (&str, &str) {
+    let mut quote = None;
+    for (index, c) in input.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            (None, '>') => return input.split_at(index + 1),
+            _ => {}
+        }
+    }
+    (input, "")
+}
+
+/// The segments of a JPEG file before the image data, by their marker, with the range of their
+/// content (after the length).
+fn jpeg_segments(bytes: &[u8]) -> Option<Vec<(u8, std::ops::Range<usize>)>> {
+    (bytes.get(0..2)? == [0xFF, 0xD8]).then_some(())?;
+    let mut segments = Vec::new();
+    let mut at = 2;
+    loop {
+        (*bytes.get(at)? == 0xFF).then_some(())?;
+        let marker = *bytes.get(at + 1)?;
+        let length = usize::from(u16::from_be_bytes([
+            *bytes.get(at + 2)?,
+            *bytes.get(at + 3)?,
+        ]));
+        (length >= 2 && at + 2 + length <= bytes.len()).then_some(())?;
+        segments.push((marker, at + 4..at + 2 + length));
+        at += 2 + length;
+        // the start of scan is followed by the image data
+        if marker == 0xDA {
+            return Some(segments);
+        }
+    }
+}
+
+/// Strip the metadata from a JPEG file: comments, XMP data and application segments other than
+/// the JFIF, ICC profile and Adobe ones. EXIF data is kept if it rotates the image.
+fn strip_jpeg(bytes: &[u8]) -> Option<Vec<u8>> {
+    let segments = jpeg_segments(bytes)?;
+    let (_, last) = segments.last()?;
+    let mut out = vec![0xFF, 0xD8];
+    for (marker, range) in &segments {
+        let data = &bytes[range.clone()];
+        let keep = match marker {
+            0xE1 => data.starts_with(b"Exif\0\0") && exif_rotates(&data[6..]),
+            0xE0 | 0xE2 | 0xEE => true,
+            0xE3..=0xEF | 0xFE => false,
+            _ => true,
+        };
+        if keep {
+            out.extend_from_slice(&bytes[range.start - 4..range.end]);
+        }
+    }
+    out.extend_from_slice(&bytes[last.end..]);
+    Some(out)
+}
+
+/// Check if EXIF data has an orientation other than the default one.
+fn exif_rotates(tiff: &[u8]) -> bool {
+    let little_endian = tiff.starts_with(b"II");
+    let u16_at = |at: usize| -> Option<u16> {
+        let bytes = tiff.get(at..at + 2)?.try_into().ok()?;
+        Some(match little_endian {
+            true => u16::from_le_bytes(bytes),
+            false => u16::from_be_bytes(bytes),
+        })
+    };
+    let u32_at = |at: usize| -> Option<u32> {
+        let bytes = tiff.get(at..at + 4)?.try_into().ok()?;
+        Some(match little_endian {
+            true => u32::from_le_bytes(bytes),
+            false => u32::from_be_bytes(bytes),
+        })
+    };
+    let orientation = || -> Option<u16> {
+        let ifd = usize::try_from(u32_at(4)?).ok()?;
+        (0..usize::from(u16_at(ifd)?))
+            .map(|entry| ifd + 2 + entry * 12)
+            .find(|&entry| u16_at(entry) == Some(0x0112))
+            .and_then(|entry| u16_at(entry + 8))
+    };
+    // keep the data if it can't be read
+    orientation() != Some(1)
+}
+
+/// The chunks of a WebP file, by their FourCC, with the range of their data.
+type WebpChunks<'a> = Vec<(&'a [u8; 4], std::ops::Range<usize>)>;
+
+/// The header and chunks of a WebP file.
+fn webp_chunks(bytes: &[u8]) -> Option<(&[u8], WebpChunks<'_>)> {
+    (bytes.get(0..4)? == b"RIFF" && bytes.get(8..12)? == b"WEBP").then_some(())?;
+    let mut chunks = Vec::new();
+    let mut at = 12;
+    while at < bytes.len() {
+        let fourcc: &[u8; 4] = bytes.get(at..at + 4)?.try_into().ok()?;
+        let size = usize::try_from(u32::from_le_bytes(
+            bytes.get(at + 4..at + 8)?.try_into().ok()?,
+        ))
+        .ok()?;
+        let end = at + 8 + size;
+        (end <= bytes.len()).then_some(())?;
+        chunks.push((fourcc, at + 8..end));
+        // chunks are padded to an even size
+        at = end + size % 2;
+    }
+    Some((&bytes[..12], chunks))
+}
+
+/// Strip the EXIF and XMP chunks from a WebP file.
+fn strip_webp(bytes: &[u8]) -> Option<Vec<u8>> {
+    let (header, chunks) = webp_chunks(bytes)?;
+    let mut out = header.to_vec();
+    for (fourcc, range) in chunks {
+        if fourcc == b"EXIF" || fourcc == b"XMP " {
+            continue;
+        }
+        let start = out.len();
+        out.extend_from_slice(&bytes[range.start - 8..range.end]);
+        if range.len() % 2 == 1 {
+            out.push(0);
+        }
+        if fourcc == b"VP8X" {
+            // clear the flags of the EXIF and XMP chunks
+            *out.get_mut(start + 8)? &= !0x0C;
+        }
+    }
+    let size = u32::try_from(out.len() - 8).ok()?;
+    out[4..8].copy_from_slice(&size.to_le_bytes());
+    Some(out)
+}
+
+/// Strip the comments and application extensions other than the looping ones from a GIF file.
+fn strip_gif(bytes: &[u8]) -> Option<Vec<u8>> {
+    (bytes.get(0..3)? == b"GIF").then_some(())?;
+    let color_table = |packed: u8| match packed & 0x80 {
+        0 => 0,
+        _ => 3 << ((packed & 0x07) + 1),
+    };
+    // the length of the data sub-blocks starting at an offset, including the terminator
+    let sub_blocks = |mut at: usize| -> Option<usize> {
+        let start = at;
+        loop {
+            let size = usize::from(*bytes.get(at)?);
+            at += 1 + size;
+            if size == 0 {
+                return Some(at - start);
+            }
+        }
+    };
+
+    let mut at = 13 + color_table(*bytes.get(10)?);
+    let mut out = bytes.get(..at)?.to_vec();
+    loop {
+        match *bytes.get(at)? {
+            0x21 => {
+                let label = *bytes.get(at + 1)?;
+                let end = at + 2 + sub_blocks(at + 2)?;
+                let keep = match label {
+                    0xFE => false,
+                    0xFF => {
+                        let id = bytes.get(at + 3..at + 14)?;
+                        id == b"NETSCAPE2.0" || id == b"ANIMEXTS1.0"
+                    }
+                    _ => true,
+                };
+                if keep {
+                    out.extend_from_slice(bytes.get(at..end)?);
+                }
+                at = end;
+            }
+            0x2C => {
+                let data = at + 10 + color_table(*bytes.get(at + 9)?) + 1;
+                let end = data + sub_blocks(data)?;
+                out.extend_from_slice(bytes.get(at..end)?);
+                at = end;
+            }
+            0x3B => {
+                out.push(0x3B);
+                return Some(out);
+            }
+            _ => return None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn minifies_svg() {
+        let svg = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!-- Generator: Editor -->
+<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10">
+  <metadata><rdf:RDF/></metadata>
+  <title>a > b</title>
+  <text x="0"><tspan>a</tspan>
+    <tspan>b</tspan></text>
+  <path d="M0 0h10"/>
+</svg>
+"#;
+        assert_eq!(
+            minify_svg(svg),
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10"><title>a > b</title><text x="0"><tspan>a</tspan>
+    <tspan>b</tspan></text><path d="M0 0h10"/></svg>"#
+        );
+    }
+
+    /// A JPEG file with the given segments, and a start of scan followed by some image data.
+    fn jpeg(segments: &[(u8, &[u8])]) -> Vec<u8> {
+        let mut bytes = vec![0xFF, 0xD8];
+        for (marker, data) in segments.iter().chain([&(0xDA, &[1, 2][..])]) {
+            bytes.extend([0xFF, *marker]);
+            bytes.extend(
+                u16::try_from(data.len() + 2)
+                    .unwrap_or_default()
+                    .to_be_bytes(),
+            );
+            bytes.extend(*data);
+        }
+        bytes.extend([7, 7, 7, 0xFF, 0xD9]);
+        bytes
+    }
+
+    #[test]
+    fn strips_jpeg() {
+        // an EXIF orientation of 6, rotating the image
+        let rotated = b"Exif\0\0MM\0\x2a\0\0\0\x08\0\x01\x01\x12\0\x03\0\0\0\x01\0\x06\0\0";
+        let frame = [8, 0, 20, 0, 30, 1, 1, 0x11, 0];
+        let bytes = jpeg(&[
+            (0xE0, b"JFIF\0"),
+            (0xE1, b"http://ns.adobe.com/xap/1.0/\0<x/>"),
+            (0xFE, b"comment"),
+            (0xC0, &frame),
+        ]);
+        assert_eq!(
+            strip_jpeg(&bytes),
+            Some(jpeg(&[(0xE0, b"JFIF\0"), (0xC0, &frame)]))
+        );
+        assert_eq!(width(&bytes, ImageType::Jpeg), Some(30));
+
+        let bytes = jpeg(&[(0xE1, rotated)]);
+        assert_eq!(strip_jpeg(&bytes), Some(bytes));
+        assert_eq!(strip_jpeg(b"not a jpeg"), None);
+    }
+
+    #[test]
+    fn strips_webp() {
+        let chunk = |fourcc: &[u8], data: &[u8]| {
+            let mut chunk = fourcc.to_vec();
+            chunk.extend(u32::try_from(data.len()).unwrap_or_default().to_le_bytes());
+            chunk.extend(data);
+            if data.len() % 2 == 1 {
+                chunk.push(0);
+            }
+            chunk
+        };
+        let webp = |chunks: &[Vec<u8>]| {
+            let body = chunks.concat();
+            let mut bytes = b"RIFF".to_vec();
+            bytes.extend(
+                u32::try_from(body.len() + 4)
+                    .unwrap_or_default()
+                    .to_le_bytes(),
+            );
+            bytes.extend(b"WEBP");
+            bytes.extend(body);
+            bytes
+        };
+        let bytes = webp(&[
+            chunk(b"VP8X", &[0x0C, 0, 0, 0, 99, 0, 0, 49, 0, 0]),
+            chunk(b"VP8L", &[0x2F, 0, 0, 0, 0]),
+            chunk(b"EXIF", b"exif"),
+            chunk(b"XMP ", b"<x/>"),
+        ]);
+        assert_eq!(
+            strip_webp(&bytes),
+            Some(webp(&[
+                chunk(b"VP8X", &[0, 0, 0, 0, 99, 0, 0, 49, 0, 0]),
+                chunk(b"VP8L", &[0x2F, 0, 0, 0, 0]),
+            ]))
+        );
+        assert_eq!(width(&bytes, ImageType::Webp), Some(100));
+    }
+
+    #[test]
+    fn strips_gif() {
+        let mut image = vec![0x2C, 0, 0, 0, 0, 2, 0, 2, 0, 0, 2, 2, 0x4C, 0x01, 0];
+        let mut gif = b"GIF89a\x02\0\x02\0\x80\0\0".to_vec();
+        gif.extend([0; 6]);
+        let mut expected = gif.clone();
+
+        let looping = b"\x21\xFF\x0BNETSCAPE2.0\x03\x01\0\0\0";
+        gif.extend(looping);
+        gif.extend(b"\x21\xFE\x05hello\0");
+        gif.extend(b"\x21\xFF\x0BXMP DataXMP\x02<>\0");
+        gif.extend(&image);
+        gif.push(0x3B);
+
+        expected.extend(looping);
+        expected.append(&mut image);
+        expected.push(0x3B);
+        assert_eq!(strip_gif(&gif), Some(expected));
+        assert_eq!(width(&gif, ImageType::Gif), Some(2));
+    }
+
+    #[test]
+    fn guesses_types() {
+        assert_eq!(ImageType::from_path(Path::new("a.jpeg")), ImageType::Jpeg);
+        assert_eq!(ImageType::from_path(Path::new("a.svg")), ImageType::Svg);
+        assert_eq!(ImageType::from_path(Path::new("a.txt")), ImageType::Other);
+        assert_eq!(ImageType::from_format("avif"), Some(ImageType::Avif));
+        assert_eq!(ImageType::from_format("bmp"), None);
+    }
+}
@@ -1,4 +1,7 @@
 //! Functionality for processing
 
+pub mod compress;
+pub mod image;
 pub mod integrity;
 pub mod minify;
+pub mod template;
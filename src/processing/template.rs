@@ -0,0 +1,459 @@
+//! Templates of the source HTML of the pages.
+//!
+//! The template language is deliberately small:
+//!
+//! - `{{ post.title }}` outputs the value of a variable, HTML escaped.
+//! - `{% if release %}…{% else %}…{% endif %}` renders a block conditionally. A condition is a
+//!   value, optionally negated by `not`, or compared to another one using `==` or `!=`, e.g.
+//!   `{% if profile == "release" %}`.
+//! - `{% for post in posts %}…{% endfor %}` repeats a block for the items of an array, which can
+//!   be loaded from a data file using a quoted path, e.g. `{% for post in "data/posts.json" %}`.
+//!   The `loop` variable holds the `index` (starting at 1) and whether the item is the `first` or
+//!   `last` one.
+//!
+//! Placeholders of Prank itself, like the nonce placeholder `{{__PRANK NONCE__}}`, are left as
+//! they are.
+
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+
+/// The prefix of the placeholders of Prank, which aren't template expressions.
+const PLACEHOLDER_PREFIX: &str = "__PRANK";
+
+/// A parsed template.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Node {
+    Text(String),
+    Value {
+        path: Vec<String>,
+        line: usize,
+    },
+    If {
+        condition: Condition,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+    For {
+        name: String,
+        items: Items,
+        body: Vec<Node>,
+        line: usize,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Operand {
+    Path(Vec<String>),
+    Literal(Value),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Condition {
+    negate: bool,
+    left: Operand,
+    /// The operand compared to, and whether it must be equal.
+    compare: Option<(bool, Operand)>,
+    line: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Items {
+    Path(Vec<String>),
+    File(String),
+}
+
+/// The tag ending a block, and its line.
+type EndTag<'a> = Option<(&'a str, usize)>;
+
+/// A lexical token of a template.
+enum Token<'a> {
+    Text(&'a str),
+    Value(&'a str, usize),
+    Tag(&'a str, usize),
+}
+
+impl Template {
+    /// Parse a template.
+    pub fn parse(source: &str) -> Result<Self> {
+        let tokens = tokenize(source)?;
+        let mut tokens = tokens.into_iter();
+        let (nodes, end) = parse_block(&mut tokens)?;
+        if let Some((tag, line)) = end {
+            bail!("line {line}: unexpected {{% {tag} %}}");
+        }
+        Ok(Self { nodes })
+    }
+
+    /// Render the template with the variables of an object.
+    ///
+    /// Data files are loaded by the given function, from their path as written in the template.
+    pub fn render(
+        &self,
+        vars: &Value,
+        load: &mut dyn FnMut(&str) -> Result<Value>,
+    ) -> Result<String> {
+        let mut out = String::new();
+        let mut scope = Scope {
+            vars,
+            locals: Vec::new(),
+        };
+        render(&self.nodes, &mut scope, load, &mut out)?;
+        Ok(out)
+    }
+}
+
+/// The variables visible while rendering, the innermost local ones first.
+struct Scope<'a> {
+    vars: &'a Value,
+    locals: Vec<(String, Value)>,
+}
+
+impl Scope<'_> {
+    fn lookup(&self, path: &[String]) -> Option<&Value> {
+        let (first, rest) = path.split_first()?;
+        let root = match self.locals.iter().rev().find(|(name, _)| name == first) {
+            Some((_, value)) => value,
+            None => self.vars.get(first)?,
+        };
+        rest.iter().try_fold(root, |value, key| match value {
+            Value::Array(items) => items.get(key.parse::<usize>().ok()?),
+            value => value.get(key),
+        })
+    }
+
+    fn value(&self, operand: &Operand, line: usize) -> Result<Value> {
+        match operand {
+            Operand::Literal(value) => Ok(value.clone()),
+            Operand::Path(path) => self.lookup(path).cloned().with_context(|| {
+                format!(
+                    "line {line}: undefined template variable `{}`",
+                    path.join(".")
+                )
+            }),
+        }
+    }
+}
+
+fn render(
+    nodes: &[Node],
+    scope: &mut Scope,
+    load: &mut dyn FnMut(&str) -> Result<Value>,
+    out: &mut String,
+) -> Result<()> {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Value { path, line } => {
+                let value = scope.value(&Operand::Path(path.clone()), *line)?;
+                let text = match value {
+                    Value::Null => String::new(),
+                    Value::Bool(value) => value.to_string(),
+                    Value::Number(value) => value.to_string(),
+                    Value::String(value) => value,
+                    _ => bail!(
+                        "line {line}: template variable `{}` is not a single value",
+                        path.join(".")
+                    ),
+                };
+                out.push_str(&htmlescape::encode_minimal(&text));
+            }
+            Node::If {
+                condition,
+                then,
+                otherwise,
+            } => {
+                let left = scope.value(&condition.left, condition.line)?;
+                let result = match &condition.compare {
+                    Some((equal, right)) => (left == scope.value(right, condition.line)?) == *equal,
+                    None => truthy(&left),
+                };
+                match result != condition.negate {
+                    true => render(then, scope, load, out)?,
+                    false => render(otherwise, scope, load, out)?,
+                }
+            }
+            Node::For {
+                name,
+                items,
+                body,
+                line,
+            } => {
+                let items = match items {
+                    Items::Path(path) => scope.value(&Operand::Path(path.clone()), *line)?,
+                    Items::File(file) => {
+                        load(file).with_context(|| format!("line {line}: error loading {file}"))?
+                    }
+                };
+                let Value::Array(items) = items else {
+                    bail!("line {line}: can only loop over an array");
+                };
+                let count = items.len();
+                for (index, item) in items.into_iter().enumerate() {
+                    let state = serde_json::json!({
+                        "index": index + 1,
+                        "first": index == 0,
+                        "last": index + 1 == count,
+                    });
+                    scope.locals.push(("loop".to_string(), state));
+                    scope.locals.push((name.clone(), item));
+                    let result = render(body, scope, load, out);
+                    scope.locals.truncate(scope.locals.len() - 2);
+                    result?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check if a value counts as true in a condition.
+fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(value) => *value,
+        Value::Number(value) => value.as_f64() != Some(0.0),
+        Value::String(value) => !value.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(entries) => !entries.is_empty(),
+    }
+}
+
+/// Split a template into its text and its expressions.
+fn tokenize(source: &str) -> Result<Vec<Token<'_>>> {
+    let mut tokens = Vec::new();
+    let mut rest = source;
+    let line = |rest: &str| source[..source.len() - rest.len()].matches('\n').count() + 1;
+    while let Some(start) = [rest.find("{{"), rest.find("{%")]
+        .into_iter()
+        .flatten()
+        .min()
+    {
+        let close = match &rest[start..start + 2] {
+            "{{" => "}}",
+            _ => "%}",
+        };
+        let Some(length) = rest[start + 2..].find(close) else {
+            bail!(
+                "line {}: unclosed {}",
+                line(&rest[start..]),
+                &rest[start..start + 2]
+            );
+        };
+        let end = start + 2 + length + 2;
+        let content = rest[start + 2..end - 2].trim();
+        if close == "}}" && content.starts_with(PLACEHOLDER_PREFIX) {
+            tokens.push(Token::Text(&rest[..end]));
+        } else {
+            tokens.push(Token::Text(&rest[..start]));
+            let at = line(&rest[start..]);
+            tokens.push(match close {
+                "}}" => Token::Value(content, at),
+                _ => Token::Tag(content, at),
+            });
+        }
+        rest = &rest[end..];
+    }
+    tokens.push(Token::Text(rest));
+    Ok(tokens)
+}
+
+/// Parse the nodes up to the end of a block, returning the tag ending it, if any.
+fn parse_block<'a>(
+    tokens: &mut impl Iterator<Item = Token<'a>>,
+) -> Result<(Vec<Node>, EndTag<'a>)> {
+    let mut nodes = Vec::new();
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Text(text) => match nodes.last_mut() {
+                Some(Node::Text(last)) => last.push_str(text),
+                _ if text.is_empty() => {}
+                _ => nodes.push(Node::Text(text.to_string())),
+            },
+            Token::Value(expr, line) => nodes.push(Node::Value {
+                path: parse_path(expr)
+                    .with_context(|| format!("line {line}: invalid expression"))?,
+                line,
+            }),
+            Token::Tag(tag, line) => {
+                let (keyword, args) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+                match keyword {
+                    "if" => nodes.push(parse_if(tokens, args.trim(), line)?),
+                    "for" => nodes.push(parse_for(tokens, args.trim(), line)?),
+                    "else" | "endif" | "endfor" => return Ok((nodes, Some((keyword, line)))),
+                    _ => bail!("line {line}: unknown tag {{% {keyword} %}}"),
+                }
+            }
+        }
+    }
+    Ok((nodes, None))
+}
+
+fn parse_if<'a>(
+    tokens: &mut impl Iterator<Item = Token<'a>>,
+    args: &str,
+    line: usize,
+) -> Result<Node> {
+    let condition = parse_condition(args, line)
+        .with_context(|| format!("line {line}: invalid condition `{args}`"))?;
+    let (then, end) = parse_block(tokens)?;
+    let otherwise = match end {
+        Some(("endif", _)) => vec![],
+        Some(("else", _)) => match parse_block(tokens)? {
+            (otherwise, Some(("endif", _))) => otherwise,
+            _ => bail!("line {line}: {{% if %}} without {{% endif %}}"),
+        },
+        _ => bail!("line {line}: {{% if %}} without {{% endif %}}"),
+    };
+    Ok(Node::If {
+        condition,
+        then,
+        otherwise,
+    })
+}
+
+fn parse_for<'a>(
+    tokens: &mut impl Iterator<Item = Token<'a>>,
+    args: &str,
+    line: usize,
+) -> Result<Node> {
+    let Some((name, items)) = args.split_once(" in ") else {
+        bail!("line {line}: expected {{% for <name> in <items> %}}");
+    };
+    let name = name.trim();
+    if !is_identifier(name) {
+        bail!("line {line}: invalid loop variable `{name}`");
+    }
+    let items = match parse_operand(items.trim())? {
+        Operand::Path(path) => Items::Path(path),
+        Operand::Literal(Value::String(file)) => Items::File(file),
+        Operand::Literal(_) => bail!("line {line}: can only loop over a variable or a data file"),
+    };
+    let body = match parse_block(tokens)? {
+        (body, Some(("endfor", _))) => body,
+        _ => bail!("line {line}: {{% for %}} without {{% endfor %}}"),
+    };
+    Ok(Node::For {
+        name: name.to_string(),
+        items,
+        body,
+        line,
+    })
+}
+
+fn parse_condition(args: &str, line: usize) -> Result<Condition> {
+    let (negate, args) = match args.strip_prefix("not ") {
+        Some(args) => (true, args.trim()),
+        None => (false, args),
+    };
+    let compare = [("==", true), ("!=", false)]
+        .into_iter()
+        .find_map(|(operator, equal)| Some((args.split_once(operator)?, equal)));
+    Ok(match compare {
+        Some(((left, right), equal)) => Condition {
+            negate,
+            left: parse_operand(left.trim())?,
+            compare: Some((equal, parse_operand(right.trim())?)),
+            line,
+        },
+        None => Condition {
+            negate,
+            left: parse_operand(args)?,
+            compare: None,
+            line,
+        },
+    })
+}
+
+fn parse_operand(operand: &str) -> Result<Operand> {
+    if let Some(string) = operand.strip_prefix('"') {
+        let Some(string) = string.strip_suffix('"') else {
+            bail!("unterminated string {operand}");
+        };
+        return Ok(Operand::Literal(Value::String(string.to_string())));
+    }
+    match operand {
+        "true" => Ok(Operand::Literal(Value::Bool(true))),
+        "false" => Ok(Operand::Literal(Value::Bool(false))),
+        _ => match operand.parse::<serde_json::Number>() {
+            Ok(number) => Ok(Operand::Literal(Value::Number(number))),
+            Err(_) => Ok(Operand::Path(parse_path(operand)?)),
+        },
+    }
+}
+
+fn parse_path(expr: &str) -> Result<Vec<String>> {
+    let path = expr.split('.').map(str::to_string).collect::<Vec<_>>();
+    if !path.iter().all(|segment| is_identifier(segment)) {
+        bail!("expected a variable, like `post.title`, found `{expr}`");
+    }
+    Ok(path)
+}
+
+fn is_identifier(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn render(template: &str, vars: Value) -> Result<String> {
+        Template::parse(template)?.render(&vars, &mut |file| match file {
+            "posts.json" => Ok(json!([{ "title": "First" }, { "title": "<Second>" }])),
+            _ => bail!("no such file"),
+        })
+    }
+
+    #[test]
+    fn renders_values() -> Result<()> {
+        let vars = json!({ "site": { "name": "Blog & more" }, "count": 3 });
+        assert_eq!(
+            render("<h1>{{ site.name }}</h1>{{count}}", vars.clone())?,
+            "<h1>Blog &amp; more</h1>3"
+        );
+        assert!(render("{{ missing }}", vars.clone()).is_err());
+        assert!(render("{{ site }}", vars.clone()).is_err());
+        // placeholders of Prank are left alone
+        assert_eq!(
+            render(r#"<script nonce="{{__PRANK NONCE__}}">"#, vars)?,
+            r#"<script nonce="{{__PRANK NONCE__}}">"#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn renders_conditions() -> Result<()> {
+        let template = r#"{% if release %}min{% else %}dev{% endif %}{% if not profile == "debug" %}!{% endif %}"#;
+        assert_eq!(
+            render(template, json!({ "release": true, "profile": "release" }))?,
+            "min!"
+        );
+        assert_eq!(
+            render(template, json!({ "release": false, "profile": "debug" }))?,
+            "dev"
+        );
+        assert!(render("{% if release %}", json!({ "release": true })).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn renders_loops() -> Result<()> {
+        let template = r#"{% for post in "posts.json" %}{{ loop.index }}. {{ post.title }}{% if not loop.last %}, {% endif %}{% endfor %}"#;
+        assert_eq!(render(template, json!({}))?, "1. First, 2. &lt;Second&gt;");
+
+        let template = "{% for tag in tags %}#{{ tag }} {% endfor %}";
+        assert_eq!(render(template, json!({ "tags": ["a", "b"] }))?, "#a #b ");
+        assert!(render(template, json!({ "tags": "a" })).is_err());
+        assert!(render("{% for tag in tags %}", json!({ "tags": [] })).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,113 @@
+//! Hot module replacement for the PureScript output served in dev mode.
+//!
+//! The browser keeps one instance per module URL. To make it import a hot replaced module again,
+//! the imports of the modules served from `/output` are rewritten to carry the module's version
+//! (e.g. `../Main/index.js?v=3`). Modules which never changed keep their plain URL, so they are
+//! shared between the old and the new module graph, and keep their state.
+
+use super::State;
+use crate::pipelines::{import_specifiers, imported_module};
+use axum::{
+    body::Bytes,
+    extract,
+    http::{
+        header::{
+            CACHE_CONTROL, CONTENT_LENGTH, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+        },
+        HeaderValue,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::{collections::BTreeMap, sync::Arc};
+
+/// Rewrite the imports of PureScript modules, once modules were hot replaced.
+pub(crate) async fn hmr_middleware(
+    extract::State(state): extract::State<Arc<State>>,
+    mut request: extract::Request,
+    next: Next,
+) -> Response {
+    let versions = state.ws_state.borrow().hot_versions().cloned();
+    let Some(versions) = versions.filter(|versions| !versions.is_empty()) else {
+        return next.run(request).await;
+    };
+
+    // the path is relative to the output dir, e.g. `/Main/index.js`
+    let Some(module) = request
+        .uri()
+        .path()
+        .strip_prefix('/')
+        .and_then(|path| path.split_once('/'))
+        .filter(|(_, file)| file.ends_with(".js"))
+        .map(|(module, _)| module.to_string())
+    else {
+        return next.run(request).await;
+    };
+
+    // the content depends on the versions, so we can't let the browser revalidate its cache
+    request.headers_mut().remove(IF_MODIFIED_SINCE);
+    request.headers_mut().remove(IF_NONE_MATCH);
+
+    let response = next.run(request).await;
+    if !response.status().is_success() {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, 100 * 1024 * 1024).await {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            tracing::debug!("Unable to intercept: {err}");
+            return (parts, Bytes::default()).into_response();
+        }
+    };
+    let source = match String::from_utf8(bytes.to_vec()) {
+        Ok(source) => source,
+        Err(err) => {
+            tracing::debug!("Unable to parse module for rewriting: {err}");
+            return (parts, bytes).into_response();
+        }
+    };
+
+    let source = rewrite_imports(&module, source, &versions);
+    parts.headers.remove(ETAG);
+    parts.headers.remove(LAST_MODIFIED);
+    parts
+        .headers
+        .insert(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+    parts.headers.insert(CONTENT_LENGTH, source.len().into());
+
+    (parts, source).into_response()
+}
+
+/// Append the version of the imported module to each import of a hot replaced module.
+fn rewrite_imports(module: &str, mut source: String, versions: &BTreeMap<String, u64>) -> String {
+    let imports = import_specifiers(&source);
+    for (range, specifier) in imports.into_iter().rev() {
+        if let Some(version) = imported_module(module, &specifier).and_then(|m| versions.get(m)) {
+            source.replace_range(range, &format!("{specifier}?v={version}"));
+        }
+    }
+    source
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rewrites_imports_of_changed_modules() {
+        let source = r#"import * as $foreign from "./foreign.js";
+import * as Data$dMaybe from "../Data.Maybe/index.js";
+import * as App$dView from "../App.View/index.js";
+"#;
+        let versions = BTreeMap::from([("App.View".to_string(), 2), ("Main".to_string(), 3)]);
+        assert_eq!(
+            rewrite_imports("Main", source.to_string(), &versions),
+            r#"import * as $foreign from "./foreign.js?v=3";
+import * as Data$dMaybe from "../Data.Maybe/index.js";
+import * as App$dView from "../App.View/index.js?v=2";
+"#
+        );
+    }
+}
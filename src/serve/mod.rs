@@ -1,8 +1,13 @@
+mod hmr;
 mod proxy;
 
 use crate::{
     common::{nonce, LOCAL, NETWORK, SERVER},
-    config::rt::RtcServe,
+    config::{
+        rt::{RtcPage, RtcServe},
+        types::Precompress,
+    },
+    pipelines::OutputDir,
     tls::TlsConfig,
     watch::WatchSystem,
     ws,
@@ -12,7 +17,10 @@ use axum::{
     body::{Body, Bytes},
     extract::{self, ws::WebSocketUpgrade},
     http::{
-        header::{HeaderName, CONTENT_LENGTH, CONTENT_TYPE, HOST},
+        header::{
+            HeaderName, ACCEPT, ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE,
+            HOST,
+        },
         HeaderValue, StatusCode,
     },
     middleware::Next,
@@ -27,7 +35,7 @@ use proxy::{ProxyBuilder, ProxyClientOptions};
 use std::{
     collections::{BTreeSet, HashMap, HashSet},
     net::{IpAddr, Ipv4Addr, SocketAddr},
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::Arc,
     time::Duration,
 };
@@ -55,17 +63,21 @@ pub struct ServeSystem {
     //  N.B. we use a broadcast channel here because a watch channel triggers a
     //  false positive on the first read of channel
     ws_state: watch::Receiver<ws::State>,
+    /// The PureScript output directory, found by the builds.
+    output_dir: Arc<OutputDir>,
 }
 
 impl ServeSystem {
     /// Construct a new instance.
     pub async fn new(cfg: Arc<RtcServe>, shutdown: broadcast::Sender<()>) -> Result<Self> {
         let (ws_state_tx, ws_state) = watch::channel(ws::State::default());
+        let output_dir = Arc::new(OutputDir::new());
         let watch = WatchSystem::new(
             cfg.watch.clone(),
             shutdown.clone(),
             Some(ws_state_tx),
             cfg.ws_protocol,
+            Some(output_dir.clone()),
         )
         .await?;
         let prefix = if cfg.tls.is_some() { "https" } else { "http" };
@@ -81,6 +93,7 @@ impl ServeSystem {
             open_http_addr,
             shutdown_tx: shutdown,
             ws_state,
+            output_dir,
         })
     }
 
@@ -94,6 +107,7 @@ impl ServeSystem {
             self.cfg.clone(),
             self.shutdown_tx.subscribe(),
             self.ws_state,
+            self.output_dir,
         )
         .await?;
 
@@ -134,6 +148,7 @@ impl ServeSystem {
         cfg: Arc<RtcServe>,
         shutdown_rx: broadcast::Receiver<()>,
         ws_state: watch::Receiver<ws::State>,
+        output_dir: Arc<OutputDir>,
     ) -> Result<JoinHandle<Result<()>>> {
         let serve_base_url = cfg.serve_base()?;
 
@@ -143,6 +158,7 @@ impl ServeSystem {
             serve_base_url.to_string(),
             cfg.clone(),
             ws_state,
+            output_dir,
         )?);
         let router = router(state, cfg.clone())?;
 
@@ -181,6 +197,72 @@ impl ServeSystem {
     }
 }
 
+/// Serve a file of the PureScript output directory.
+///
+/// The directory is looked up for each request, as it is only known once a build found it.
+async fn serve_output(
+    extract::State(state): extract::State<Arc<State>>,
+    request: extract::Request,
+) -> Response {
+    let output_dir = state
+        .output_dir
+        .get()
+        .unwrap_or_else(|| state.cfg.watch.build.working_directory.join("output"));
+    match ServeDir::new(output_dir).try_call(request).await {
+        Ok(response) => response.into_response(),
+        Err(error) => {
+            tracing::error!(?error, "failed serving PureScript static file");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Serve the page of a single page application, for a path without a file of the dist dir.
+async fn serve_spa_page(
+    extract::State(state): extract::State<Arc<State>>,
+    request: extract::Request,
+) -> Response {
+    let page = spa_page(&state.spa_pages, request.uri().path())
+        .cloned()
+        .unwrap_or_else(|| state.dist_dir.join(INDEX_HTML));
+    match ServeFile::new(page).try_call(request).await {
+        Ok(response) => response.into_response(),
+        Err(error) => {
+            tracing::error!(?error, "failed serving single page application");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// The pages serving the paths below their directory, by the paths of their directories in the
+/// dist dir, longest first.
+///
+/// Only the pages written to an `index.html` file serve the paths below their directory.
+fn spa_pages(dist_dir: &Path, pages: &[RtcPage]) -> Vec<(String, PathBuf)> {
+    let mut spa_pages = pages
+        .iter()
+        .filter_map(|page| {
+            let dir = match page.output.rsplit_once('/') {
+                Some((dir, INDEX_HTML)) => format!("{dir}/"),
+                None if page.output == INDEX_HTML => String::new(),
+                _ => return None,
+            };
+            Some((dir, dist_dir.join(&page.output)))
+        })
+        .collect::<Vec<_>>();
+    spa_pages.sort_by_key(|(dir, _)| std::cmp::Reverse(dir.len()));
+    spa_pages
+}
+
+/// Find the page serving a path, relative to the serve base.
+fn spa_page<'a>(spa_pages: &'a [(String, PathBuf)], path: &str) -> Option<&'a PathBuf> {
+    let path = path.trim_start_matches('/');
+    spa_pages
+        .iter()
+        .find(|(dir, _)| path.starts_with(dir.as_str()))
+        .map(|(_, page)| page)
+}
+
 /// Show where `serve` is listening
 ///
 /// We'll look up addresses, and simply append aliases.
@@ -345,6 +427,8 @@ async fn run_server(
 pub struct State {
     /// The location of the dist dir.
     pub dist_dir: PathBuf,
+    /// The pages of a single page application, by their directories in the dist dir.
+    pub spa_pages: Vec<(String, PathBuf)>,
     /// The public URL from which assets are being served.
     pub serve_base: String,
     /// The channel for WS client messages.
@@ -355,6 +439,8 @@ pub struct State {
     pub headers: HashMap<String, String>,
     /// Configuration
     pub cfg: Arc<RtcServe>,
+    /// The PureScript output directory, served in dev mode.
+    pub output_dir: Arc<OutputDir>,
 }
 
 impl State {
@@ -364,6 +450,7 @@ impl State {
         serve_base: String,
         cfg: Arc<RtcServe>,
         ws_state: watch::Receiver<ws::State>,
+        output_dir: Arc<OutputDir>,
     ) -> Result<Self> {
         let mut ws_base = cfg.ws_base()?.to_string();
         if !ws_base.ends_with('/') {
@@ -371,41 +458,51 @@ impl State {
         }
 
         Ok(Self {
+            spa_pages: spa_pages(&dist_dir, &cfg.watch.build.pages),
             dist_dir,
             serve_base,
             ws_state,
             ws_base,
             headers: cfg.headers.clone(),
             cfg,
+            output_dir,
         })
     }
 }
 
 /// Build the Prank router, this includes that static file server, the WebSocket server,
-/// (for autoreload & HMR in the future), as well as any user-defined proxies.
+/// (for autoreload & HMR), as well as any user-defined proxies.
 fn router(state: Arc<State>, cfg: Arc<RtcServe>) -> Result<Router> {
     // Build static file server, middleware, error handler & WS route for reloads.
 
+    // Serve the precompressed files of the build, as far as the client accepts them.
+    let mut dist_dir = ServeDir::new(&state.dist_dir);
+    for format in &cfg.watch.build.precompress {
+        dist_dir = match format {
+            Precompress::Gzip => dist_dir.precompressed_gzip(),
+            Precompress::Brotli => dist_dir.precompressed_br(),
+        };
+    }
+
     let mut serve_dir = if cfg.no_spa {
-        get_service(ServeDir::new(&state.dist_dir))
+        get_service(dist_dir)
     } else {
-        get_service(
-            ServeDir::new(&state.dist_dir)
-                .fallback(ServeFile::new(state.dist_dir.join(INDEX_HTML))),
-        )
+        get_service(dist_dir.fallback(get(serve_spa_page).with_state(state.clone())))
     };
 
     // If we are in dev mode, serve the PureScript output directory as well.
     let mut router = Router::new();
     if !cfg.watch.build.release {
-        let purescript_output_dir = state.cfg.watch.build.working_directory.join("output");
-        tracing::info!("{} serving PureScript assets at /output", SERVER);
+        let mount = format!("/{}", cfg.watch.build.output_mount);
+        tracing::info!("{} serving PureScript assets at {mount}", SERVER);
         router = router.nest_service(
-            "/output",
-            get_service(ServeDir::new(purescript_output_dir)).handle_error(|error| async move {
-                tracing::error!(?error, "failed serving PureScript static file");
-                StatusCode::INTERNAL_SERVER_ERROR
-            }),
+            &mount,
+            get(serve_output)
+                .layer(axum::middleware::from_fn_with_state(
+                    state.clone(),
+                    hmr::hmr_middleware,
+                ))
+                .with_state(state.clone()),
         );
     }
     for (key, value) in &state.headers {
@@ -485,15 +582,25 @@ fn router(state: Arc<State>, cfg: Arc<RtcServe>) -> Result<Router> {
 
 async fn html_address_middleware(
     extract::State(state): extract::State<Arc<State>>,
-    request: extract::Request,
+    mut request: extract::Request,
     next: Next,
 ) -> Response {
     let host = request.headers().get(HOST).cloned();
 
+    // pages get rewritten below, so don't let them be served precompressed
+    let accepts_html = request
+        .headers()
+        .get(ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/html"));
+    if accepts_html {
+        request.headers_mut().remove(ACCEPT_ENCODING);
+    }
+
     let response = next.run(request).await;
 
-    // if it's not a success, we don't modify it
-    if !response.status().is_success() {
+    // if it's not a success, or compressed, we don't modify it
+    if !response.status().is_success() || response.headers().contains_key(CONTENT_ENCODING) {
         return response;
     }
 
@@ -601,3 +708,35 @@ impl IntoResponse for ServerError {
         res
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_spa_page() {
+        let dist = Path::new("/dist");
+        let page = |output: &str| RtcPage {
+            target: PathBuf::from(output),
+            output: output.to_string(),
+        };
+        let pages = spa_pages(
+            dist,
+            &[
+                page("index.html"),
+                page("about.html"),
+                page("admin/index.html"),
+                page("admin/users/index.html"),
+            ],
+        );
+        let find = |path| spa_page(&pages, path).and_then(|page| page.to_str());
+        assert_eq!(find("/settings"), Some("/dist/index.html"));
+        assert_eq!(find("/admin/"), Some("/dist/admin/index.html"));
+        assert_eq!(find("/admin/settings"), Some("/dist/admin/index.html"));
+        assert_eq!(find("/admin/users/1"), Some("/dist/admin/users/index.html"));
+        assert_eq!(find("/administration"), Some("/dist/index.html"));
+
+        let pages = spa_pages(dist, &[page("app.html")]);
+        assert_eq!(spa_page(&pages, "/settings"), None);
+    }
+}
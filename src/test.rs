@@ -0,0 +1,286 @@
+//! Test system, running the spago test suite.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context, Result};
+use tokio::sync::mpsc;
+
+use crate::build::{BuildOutput, BuildResult};
+use crate::common::{self, ERROR, SUCCESS, TESTING};
+use crate::config::rt::RtcBuild;
+use crate::pipelines::{page_manifest, spago_args, SpagoMetadata};
+use crate::tools::{self, Application};
+use crate::watch::Runner;
+
+/// A system used for running the test suite of a spago package.
+pub struct TestSystem {
+    /// Runtime config.
+    cfg: Arc<RtcBuild>,
+    /// All metadata associated with the package under test.
+    manifest: SpagoMetadata,
+    /// Run the tests on the output of purs-backend-es, instead of the one of spago.
+    optimize: bool,
+    /// The compiler output read by purs-backend-es, if it isn't at the default location.
+    corefn_dir: Option<String>,
+    /// An optional channel to be used to communicate paths to ignore back to the watcher.
+    ignore_chan: Option<mpsc::Sender<Vec<PathBuf>>>,
+}
+
+impl TestSystem {
+    /// Create a new instance, for the spago project of the build target's main application.
+    ///
+    /// The package defaults to the one selected by the application's link.
+    pub async fn new(
+        cfg: Arc<RtcBuild>,
+        package: Option<&str>,
+        optimize: bool,
+        ignore_chan: Option<mpsc::Sender<Vec<PathBuf>>>,
+    ) -> Result<Self> {
+        let (manifest_path, page_package) = page_manifest(cfg.clone()).await?;
+        let package = package.or(page_package.as_deref());
+        let manifest = SpagoMetadata::new(&manifest_path, package).await?;
+        Ok(Self {
+            cfg,
+            corefn_dir: manifest.corefn_dir(),
+            manifest,
+            optimize,
+            ignore_chan,
+        })
+    }
+
+    /// Run the test suite.
+    #[tracing::instrument(level = "trace", skip(self))]
+    pub async fn test(&mut self) -> Result<()> {
+        tracing::info!("{}running tests", TESTING);
+        let res = self.do_test().await;
+        match res {
+            Ok(_) => {
+                tracing::info!("{}tests passed", SUCCESS);
+                Ok(())
+            }
+            Err(err) => {
+                tracing::error!("{}tests failed\n{:?}", ERROR, err);
+                Err(err)
+            }
+        }
+    }
+
+    /// Internal business logic of `test`.
+    async fn do_test(&mut self) -> Result<()> {
+        let spago = tools::get(
+            Application::Spago,
+            self.cfg.tools.spago.as_deref(),
+            self.cfg.offline,
+            &self.cfg.client_options(),
+        )
+        .await?;
+
+        let res = match self.optimize {
+            true => self.test_optimized(&spago).await,
+            false => common::run_command(
+                Application::Spago.name(),
+                &spago,
+                &self.test_args(),
+                &self.manifest.workspace_root,
+            )
+            .await
+            .context("error during spago test execution"),
+        };
+
+        // Send the compiler output dirs over to the watcher to be ignored.
+        if let Some(chan) = &mut self.ignore_chan {
+            let mut ignore = vec![];
+            for dir in [
+                self.manifest.target_directory(),
+                self.manifest.backend_output_dir(),
+            ] {
+                if let Ok(dir) = dir.canonicalize() {
+                    ignore.push(dir.join("**"));
+                    ignore.push(dir);
+                }
+            }
+            let _ = chan.send(ignore).await;
+        }
+
+        res
+    }
+
+    /// The arguments of `spago test`.
+    fn test_args(&self) -> Vec<&str> {
+        spago_args("test", &self.cfg, &self.manifest)
+    }
+
+    /// The arguments of `purs-backend-es build`, optimizing the compiled tests.
+    fn backend_args(&self) -> Vec<&str> {
+        let mut args = vec!["build"];
+        if let Some(corefn_dir) = &self.corefn_dir {
+            args.extend(["--corefn-dir", corefn_dir]);
+        }
+        args.extend(self.manifest.backend_args());
+        args
+    }
+
+    /// The script running the main module of the optimized tests with node.
+    fn optimized_script(&self) -> Result<String> {
+        let main = self
+            .manifest
+            .package
+            .test
+            .as_ref()
+            .map(|test| test.main.as_str())
+            .ok_or_else(|| {
+                anyhow!(
+                    "package '{}' has no `test` section in {}",
+                    self.manifest.package.name,
+                    self.manifest.manifest_path.display()
+                )
+            })?;
+        let path = self
+            .manifest
+            .backend_output_dir()
+            .join(main)
+            .join("index.js");
+        let path = path.to_string_lossy().replace('\\', "/");
+        let url = match path.starts_with('/') {
+            true => format!("file://{path}"),
+            false => format!("file:///{path}"),
+        };
+        Ok(format!("import {{ main }} from '{url}';\nmain();"))
+    }
+
+    /// Compile the tests with spago, optimize them using purs-backend-es, and run them with node.
+    async fn test_optimized(&self, spago: &PathBuf) -> Result<()> {
+        let script = self.optimized_script()?;
+
+        common::run_command(
+            Application::Spago.name(),
+            spago,
+            &spago_args("build", &self.cfg, &self.manifest),
+            &self.manifest.workspace_root,
+        )
+        .await
+        .context("error during spago build execution")?;
+
+        let purs_backend_es = tools::get(
+            Application::PureScriptBackendEs,
+            self.cfg.tools.purescript_backend_es.as_deref(),
+            self.cfg.offline,
+            &self.cfg.client_options(),
+        )
+        .await?;
+        common::run_command(
+            Application::PureScriptBackendEs.name(),
+            &purs_backend_es,
+            &self.backend_args(),
+            &self.manifest.workspace_root,
+        )
+        .await?;
+
+        common::run_command(
+            "node",
+            "node",
+            &["--input-type=module", "--eval", &script],
+            &self.manifest.workspace_root,
+        )
+        .await
+        .context("error running the optimized tests")
+    }
+}
+
+impl Runner for TestSystem {
+    async fn run(&mut self, _changed_paths: Vec<PathBuf>) -> BuildResult {
+        self.test().await.map(|()| BuildOutput::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::test_util::write;
+
+    async fn test_system(tmp: &std::path::Path, package: Option<&str>) -> Result<TestSystem> {
+        let cfg = Arc::new(RtcBuild::new_test(tmp).await?);
+        TestSystem::new(cfg, package, true, None).await
+    }
+
+    #[tokio::test]
+    async fn uses_the_manifest_of_the_page() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        write(
+            tmp.path(),
+            "index.html",
+            r#"<html><head><link data-prank rel="purescript" href="app/" data-type="main"/></head></html>"#,
+        )?;
+        write(
+            tmp.path(),
+            "app/spago.yaml",
+            "package:\n  name: app\n  test:\n    main: Test.Main\nworkspace:\n  backend:\n    cmd: purs-backend-es\n    args: [build, --output-dir, out-es]\n",
+        )?;
+
+        let system = test_system(tmp.path(), None).await?;
+        let root = tmp.path().join("app");
+        assert_eq!(system.manifest.workspace_root, root);
+        assert_eq!(system.test_args(), ["test"]);
+        assert_eq!(system.backend_args(), ["build", "--output-dir", "out-es"]);
+        let script = system.optimized_script()?;
+        let main = root.join("out-es/Test.Main/index.js");
+        let main = main.to_string_lossy().replace('\\', "/");
+        assert!(
+            script.starts_with("import { main } from 'file://"),
+            "{script}"
+        );
+        assert!(script.contains(&main), "{script}");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn selects_the_package_of_the_page() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        write(
+            tmp.path(),
+            "index.html",
+            r#"<html><head><link data-prank rel="purescript" data-package="frontend"/></head></html>"#,
+        )?;
+        write(
+            tmp.path(),
+            "spago.yaml",
+            "workspace:\n  buildOpts:\n    output: out\n",
+        )?;
+        write(
+            tmp.path(),
+            "frontend/spago.yaml",
+            "package:\n  name: frontend\n",
+        )?;
+        write(
+            tmp.path(),
+            "backend/spago.yaml",
+            "package:\n  name: backend\n",
+        )?;
+
+        let system = test_system(tmp.path(), None).await?;
+        assert_eq!(system.test_args(), ["test", "-p", "frontend"]);
+        let corefn_dir = tmp.path().join("out").to_string_lossy().to_string();
+        assert_eq!(
+            system.backend_args(),
+            ["build", "--corefn-dir", corefn_dir.as_str()]
+        );
+        assert!(system.optimized_script().is_err());
+
+        // The command line takes precedence over the page.
+        let system = test_system(tmp.path(), Some("backend")).await?;
+        assert_eq!(system.test_args(), ["test", "-p", "backend"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn defaults_to_the_manifest_next_to_the_target() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        write(tmp.path(), "spago.yaml", "package:\n  name: app\n")?;
+
+        let system = test_system(tmp.path(), None).await?;
+        assert_eq!(system.manifest.package.name, "app");
+        assert_eq!(system.backend_args(), ["build"]);
+        Ok(())
+    }
+}
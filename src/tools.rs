@@ -22,6 +22,8 @@ pub enum Application {
     Spago,
     /// purs-tidy for formatting PureScript code
     PursTidy,
+    /// purs, the PureScript compiler
+    Purs,
     /// purs-backend-es for compiling PureScript to ES
     PureScriptBackendEs,
     /// sass for generating css
@@ -57,6 +59,7 @@ impl Application {
             Self::PureScriptBackendEs => "purs-backend-es",
             Self::Spago => "spago",
             Self::PursTidy => "purs-tidy",
+            Self::Purs => "purs",
         }
     }
 
@@ -65,6 +68,7 @@ impl Application {
         match self {
             Self::Spago => Some("spago@next"),
             Self::PursTidy => Some("purs-tidy"),
+            Self::Purs => Some("purescript"),
             Self::PureScriptBackendEs => Some("purs-backend-es"),
             _ => None,
         }
@@ -84,6 +88,7 @@ impl Application {
                 Self::PureScriptBackendEs => "purs-backend-es.exe",
                 Self::Spago => "spago.exe",
                 Self::PursTidy => "purs-tidy.exe",
+                Self::Purs => "purs.exe",
             }
         } else {
             match self {
@@ -93,6 +98,7 @@ impl Application {
                 Self::PureScriptBackendEs => "purs-backend-es",
                 Self::Spago => "spago",
                 Self::PursTidy => "purs-tidy",
+                Self::Purs => "purs",
             }
         }
     }
@@ -112,6 +118,7 @@ impl Application {
             Self::PureScriptBackendEs => &[],
             Self::Spago => &[], // Archive only contains the binary
             Self::PursTidy => &[],
+            Self::Purs => &[],
         }
     }
 
@@ -124,6 +131,7 @@ impl Application {
             Self::PureScriptBackendEs => "1.4.3",
             Self::Spago => "0.93.44",
             Self::PursTidy => "0.11.0",
+            Self::Purs => "0.15.15",
         }
     }
 
@@ -169,7 +177,7 @@ impl Application {
                 _ => bail!("Unable to download tailwindcss for {target_os} {target_arch}")
             },
 
-            Self::Spago | Self::PursTidy | Self::PureScriptBackendEs => {
+            Self::Spago | Self::PursTidy | Self::Purs | Self::PureScriptBackendEs => {
                  bail!("{} does not have a binary download available. Get it from NPM.", self.name());
             }
         })
@@ -184,6 +192,7 @@ impl Application {
             Application::PureScriptBackendEs => "--version",
             Application::Spago => "--version",
             Application::PursTidy => "--version",
+            Application::Purs => "--version",
         }
     }
 
@@ -194,11 +203,12 @@ impl Application {
 
         let text = text.trim();
         let formatted_version = match self {
-            Application::Sass | Application::Spago | Application::PursTidy => text
-                .split_whitespace()
-                .next()
-                .with_context(|| format!("missing or malformed version output: {text}"))?
-                .to_owned(),
+            Application::Sass | Application::Spago | Application::PursTidy | Application::Purs => {
+                text.split_whitespace()
+                    .next()
+                    .with_context(|| format!("missing or malformed version output: {text}"))?
+                    .to_owned()
+            }
             Application::PureScriptBackendEs => {
                 println!("{:?}", text);
                 text.split_whitespace()
@@ -863,6 +873,12 @@ mod tests {
         "1.37.5 compiled with dart2js 2.18.4",
         "1.37.5"
     );
+    table_test_format_version!(
+        purs_development_build,
+        Application::Purs,
+        "0.15.15 [development build; commit: 1a2b3c]",
+        "0.15.15"
+    );
     table_test_format_version!(
         tailwindcss_pre_compiled,
         Application::TailwindCss,
@@ -1,9 +1,10 @@
 use crate::{
-    build::{BuildResult, BuildSystem},
+    build::{BuildMode, BuildOutput, BuildResult, BuildSystem},
     config::{
         rt::{GlobMatcher, RtcWatch},
         types::WsProtocol,
     },
+    pipelines::{CompilerErrors, DevBundler, DevSession, Diagnostic, OutputDir, PursIde},
     ws,
 };
 use anyhow::{Context, Result};
@@ -16,7 +17,14 @@ use notify_debouncer_full::{
     new_debouncer_opt, DebounceEventResult, DebouncedEvent, Debouncer, FileIdMap,
 };
 use std::path::Path;
-use std::{fmt::Write, path::PathBuf, sync::Arc, time::Duration};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+    future::Future,
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
 use tokio::{
     sync::{broadcast, mpsc, watch, Mutex},
     time::Instant,
@@ -57,10 +65,22 @@ const DEBOUNCE_DURATION: Duration = Duration::from_millis(25);
 /// will be ignored.
 const WATCHER_COOLDOWN: Duration = Duration::from_secs(1);
 
-/// A watch system wrapping a build system and a watcher.
-pub struct WatchSystem {
+/// A system which is run by the [`WatchSystem`] on relevant changes.
+pub trait Runner: Send + 'static {
+    /// Run the system, passing the paths which changed since the last run.
+    fn run(&mut self, changed_paths: Vec<PathBuf>) -> impl Future<Output = BuildResult> + Send;
+}
+
+impl Runner for BuildSystem {
+    async fn run(&mut self, changed_paths: Vec<PathBuf>) -> BuildResult {
+        self.build(BuildMode::Full, changed_paths).await
+    }
+}
+
+/// A watch system wrapping a build system (or another [`Runner`]) and a watcher.
+pub struct WatchSystem<S = BuildSystem> {
     /// The build system.
-    build: Arc<Mutex<BuildSystem>>,
+    build: Arc<Mutex<S>>,
     /// The current vector of paths to be ignored.
     ignored_paths: GlobMatcher,
     /// The current vector of paths to reload on change.
@@ -101,6 +121,12 @@ pub struct WatchSystem {
     release: bool,
     /// Paths that have changed in the current build cycle.
     changed_paths: Arc<Mutex<Vec<PathBuf>>>,
+    /// The versions of the modules hot replaced since the last reload.
+    hot_versions: BTreeMap<String, u64>,
+    /// The number of hot replacements so far.
+    hot_version: u64,
+    /// The compiler warnings of the last successful build.
+    warnings: Vec<Diagnostic>,
 }
 
 enum EventRelevance {
@@ -116,7 +142,38 @@ impl WatchSystem {
         shutdown: broadcast::Sender<()>,
         ws_state: Option<watch::Sender<ws::State>>,
         ws_protocol: Option<WsProtocol>,
+        output_dir: Option<Arc<OutputDir>>,
     ) -> Result<Self> {
+        let build = cfg.build.clone();
+        // The server and the cache are kept as long as the build system, so for the watch system.
+        let dev = !build.release;
+        let session = DevSession {
+            purs_ide: (cfg.purs_ide && dev).then(|| Arc::new(PursIde::new())),
+            dev_bundler: (cfg.dev_bundle && dev).then(|| Arc::new(DevBundler::new())),
+            output_dir,
+        };
+        Self::with_runner(cfg, shutdown, ws_state, |ignore_tx| {
+            BuildSystem::new(build, Some(ignore_tx), session, ws_protocol)
+        })
+        .await
+    }
+}
+
+impl<S: Runner> WatchSystem<S> {
+    /// Create a new instance, running a custom system instead of the build system.
+    ///
+    /// The system is created from a channel, which it can use to communicate paths to ignore
+    /// back to the watcher.
+    pub async fn with_runner<F, Fut>(
+        cfg: Arc<RtcWatch>,
+        shutdown: broadcast::Sender<()>,
+        ws_state: Option<watch::Sender<ws::State>>,
+        runner: F,
+    ) -> Result<Self>
+    where
+        F: FnOnce(mpsc::Sender<Vec<PathBuf>>) -> Fut,
+        Fut: Future<Output = Result<S>>,
+    {
         // Create a channel for being able to listen for new paths to ignore while running.
         let (watch_tx, watch_rx) = mpsc::channel(1);
         let (ignore_tx, ignore_rx) = mpsc::channel(1);
@@ -133,9 +190,7 @@ impl WatchSystem {
         );
 
         // Build dependencies.
-        let build = Arc::new(Mutex::new(
-            BuildSystem::new(cfg.build.clone(), Some(ignore_tx), ws_protocol).await?,
-        ));
+        let build = Arc::new(Mutex::new(runner(ignore_tx).await?));
         Ok(Self {
             build,
             ignored_paths: cfg.ignored_paths.clone(),
@@ -155,17 +210,31 @@ impl WatchSystem {
             no_error_reporting: cfg.no_error_reporting,
             release: cfg.build.release,
             changed_paths: Arc::new(Mutex::new(Vec::new())),
+            hot_versions: BTreeMap::new(),
+            hot_version: 0,
+            warnings: vec![],
         })
     }
 
     /// Run a build.
+    ///
+    /// The paths to ignore are taken while building, so that the build never waits for the
+    /// watcher to run.
     #[tracing::instrument(level = "trace", skip(self))]
-    pub async fn build(&mut self) -> Result<()> {
-        self.build
-            .lock()
-            .await
-            .build(crate::build::BuildMode::Full, Vec::new())
-            .await
+    pub async fn build(&mut self) -> BuildResult {
+        let build = self.build.clone();
+        let run = async move { build.lock().await.run(Vec::new()).await };
+        tokio::pin!(run);
+        let res = loop {
+            tokio::select! {
+                res = &mut run => break res,
+                Some(ign) = self.ignore_rx.recv() => ign.into_iter().for_each(|ign| self.update_ignore_list(ign)),
+            }
+        };
+        while let Ok(ign) = self.ignore_rx.try_recv() {
+            ign.into_iter().for_each(|ign| self.update_ignore_list(ign));
+        }
+        res
     }
 
     /// Run the watch system, responding to events and triggering builds.
@@ -184,21 +253,33 @@ impl WatchSystem {
     }
 
     #[tracing::instrument(level = "trace", skip(self))]
-    async fn build_complete(&mut self, build_result: Result<(), anyhow::Error>) {
+    async fn build_complete(&mut self, build_result: BuildResult) {
         tracing::debug!("Build reported completion");
 
         // record last finish timestamp
         self.last_build_finished = Instant::now();
 
-        if let Some(tx) = &mut self.ws_state {
+        if self.ws_state.is_some() {
             match build_result {
-                Ok(()) => {
-                    let _ = tx.send_replace(ws::State::Ok);
+                Ok(BuildOutput {
+                    hot_modules,
+                    warnings,
+                    ..
+                }) => {
+                    self.warnings = warnings;
+                    match hot_modules {
+                        Some(modules) => self.hot_replace(modules),
+                        None => self.reload(),
+                    }
                 }
                 Err(err) => {
-                    if !self.no_error_reporting {
+                    if let Some(tx) = self.ws_state.as_mut().filter(|_| !self.no_error_reporting) {
+                        let diagnostics = CompilerErrors::find(&err)
+                            .map(|errors| errors.0.clone())
+                            .unwrap_or_default();
                         let _ = tx.send_replace(ws::State::Failed {
                             reason: build_error_reason(err),
+                            diagnostics,
                         });
                     }
                 }
@@ -209,6 +290,33 @@ impl WatchSystem {
         self.check_spawn_build().await;
     }
 
+    /// Reload the page, which also discards all hot replaced modules.
+    fn reload(&mut self) {
+        self.hot_versions.clear();
+        if let Some(tx) = &mut self.ws_state {
+            let _ = tx.send_replace(ws::State::Ok {
+                warnings: self.warnings.clone(),
+            });
+        }
+    }
+
+    /// Hot replace modules, giving them a new version, so that the browser imports them again.
+    fn hot_replace(&mut self, modules: BTreeSet<String>) {
+        self.hot_version += 1;
+        for module in &modules {
+            self.hot_versions.insert(module.clone(), self.hot_version);
+        }
+        tracing::debug!(?modules, "Hot replacing modules");
+
+        if let Some(tx) = &mut self.ws_state {
+            let _ = tx.send_replace(ws::State::ModulesChanged {
+                modules: modules.into_iter().collect(),
+                versions: self.hot_versions.clone(),
+                warnings: self.warnings.clone(),
+            });
+        }
+    }
+
     /// check if a build is active
     fn is_build_active(&self) -> bool {
         self.last_build_started > self.last_build_finished
@@ -224,11 +332,7 @@ impl WatchSystem {
 
         tokio::spawn(async move {
             // run the build
-            let result = build
-                .lock()
-                .await
-                .build(crate::build::BuildMode::Full, changed_paths)
-                .await;
+            let result = build.lock().await.run(changed_paths).await;
             // report the result
             build_tx.send(result).await
         });
@@ -282,9 +386,7 @@ impl WatchSystem {
             }
             EventRelevance::Reload(path) => {
                 tracing::debug!(path = ?path, "Reloading due to change");
-                if let Some(tx) = &mut self.ws_state {
-                    let _ = tx.send_replace(ws::State::Ok);
-                }
+                self.reload();
             }
             EventRelevance::Relevant(path) => {
                 tracing::debug!(path = ?path, "Triggering build due to change");
@@ -458,3 +560,64 @@ fn build_error_reason(error: anyhow::Error) -> String {
 
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::rt::RtcBuild;
+
+    /// A runner recording its runs, and ignoring a path on each run.
+    struct Recorder {
+        ignore_chan: mpsc::Sender<Vec<PathBuf>>,
+        runs: Arc<std::sync::Mutex<Vec<Vec<PathBuf>>>>,
+    }
+
+    impl Runner for Recorder {
+        async fn run(&mut self, changed_paths: Vec<PathBuf>) -> BuildResult {
+            if let Ok(mut runs) = self.runs.lock() {
+                runs.push(changed_paths);
+            }
+            // more messages than the channel holds
+            for path in ["/output/**", "/.prank/**"] {
+                let _ = self.ignore_chan.send(vec![path.into()]).await;
+            }
+            Ok(BuildOutput::default())
+        }
+    }
+
+    #[tokio::test]
+    async fn runs_custom_runners() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let build = Arc::new(RtcBuild::new_test(tmp.path()).await?);
+        let cfg = Arc::new(RtcWatch {
+            paths: vec![tmp.path().canonicalize()?],
+            build,
+            ignored_paths: GlobMatcher::new(),
+            reload_paths: GlobMatcher::new(),
+            poll: None,
+            enable_cooldown: false,
+            clear_screen: false,
+            no_error_reporting: false,
+            purs_ide: false,
+            dev_bundle: false,
+        });
+        let (shutdown, _) = broadcast::channel(1);
+        let runs = Arc::new(std::sync::Mutex::new(vec![]));
+
+        let mut system = WatchSystem::with_runner(cfg, shutdown, None, |ignore_chan| {
+            let runs = runs.clone();
+            async move { Ok(Recorder { ignore_chan, runs }) }
+        })
+        .await?;
+        assert_eq!(system.build().await?, BuildOutput::default());
+        assert_eq!(
+            runs.lock().map(|runs| runs.clone()).ok(),
+            Some(vec![vec![]])
+        );
+
+        // The paths sent by the runner reach the watcher, while building.
+        assert!(system.ignored_paths.is_match("/output/Main/index.js"));
+        assert!(system.ignored_paths.is_match("/.prank/env/Prank/Env.js"));
+        Ok(())
+    }
+}
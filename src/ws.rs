@@ -1,7 +1,7 @@
-use crate::serve;
+use crate::{pipelines::Diagnostic, serve};
 use axum::extract::ws::{Message, WebSocket};
 use futures_util::{SinkExt, StreamExt};
-use std::sync::Arc;
+use std::{collections::BTreeMap, sync::Arc};
 use tokio_stream::wrappers::WatchStream;
 
 /// (outgoing) communication messages with the websocket
@@ -10,25 +10,65 @@ use tokio_stream::wrappers::WatchStream;
 #[serde(tag = "type", content = "data")]
 pub enum ClientMessage {
     Reload,
-    BuildFailure { reason: String },
+    BuildFailure {
+        reason: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        diagnostics: Vec<Diagnostic>,
+    },
+    ModulesChanged {
+        modules: Vec<String>,
+    },
+    /// The compiler warnings of the current build, an empty list clears them.
+    Warnings {
+        diagnostics: Vec<Diagnostic>,
+    },
 }
 
-#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum State {
-    #[default]
-    Ok,
+    Ok {
+        /// The compiler warnings of the build.
+        warnings: Vec<Diagnostic>,
+    },
     Failed {
         reason: String,
+        /// The diagnostics of the compiler, if it caused the failure.
+        diagnostics: Vec<Diagnostic>,
+    },
+    /// Modules which can be hot replaced changed.
+    ModulesChanged {
+        /// The modules changed by the last build.
+        modules: Vec<String>,
+        /// The versions of all modules changed since the last reload.
+        versions: BTreeMap<String, u64>,
+        /// The compiler warnings of the build.
+        warnings: Vec<Diagnostic>,
     },
 }
 
+impl Default for State {
+    fn default() -> Self {
+        Self::Ok { warnings: vec![] }
+    }
+}
+
+impl State {
+    /// The versions of the hot replaced modules, if any.
+    pub fn hot_versions(&self) -> Option<&BTreeMap<String, u64>> {
+        match self {
+            Self::ModulesChanged { versions, .. } => Some(versions),
+            _ => None,
+        }
+    }
+}
+
 pub(crate) async fn handle_ws(mut ws: WebSocket, state: Arc<serve::State>) {
     let mut rx = WatchStream::new(state.ws_state.clone());
     tracing::debug!("autoreload websocket opened");
 
     let mut first = true;
 
-    loop {
+    'ws: loop {
         tokio::select! {
             msg = ws.recv() => {
                 match msg {
@@ -67,26 +107,37 @@ pub(crate) async fn handle_ws(mut ws: WebSocket, state: Arc<serve::State>) {
 
                 tracing::trace!("Build state changed: {state:?}");
 
-                let msg = match state {
-                    State::Ok if first => {
-                        // If the state is ok, and it's the first message we would send, discard it,
-                        // as this would cause a reload right after connecting. On the other side,
-                        // we want to send out a failed build even after reconnecting.
+                let msgs = match state {
+                    State::Ok { warnings } if first => {
+                        // If the state is ok, and it's the first message we would send, don't
+                        // reload, as this would cause a reload right after connecting. On the other
+                        // side, we want to send out a failed build even after reconnecting.
                         first = false;
                         tracing::trace!("Discarding first reload trigger");
-                        None
+                        warnings_message(warnings)
                     },
-                    State::Ok  => Some(ClientMessage::Reload),
-                    State::Failed { reason } => Some(ClientMessage::BuildFailure { reason }),
+                    State::ModulesChanged { warnings, .. } if first => {
+                        // Same as above, the page was loaded after the modules changed.
+                        first = false;
+                        warnings_message(warnings)
+                    },
+                    // the warnings are sent once the page got reloaded
+                    State::Ok { .. } => vec![ClientMessage::Reload],
+                    State::Failed { reason, diagnostics } => vec![ClientMessage::BuildFailure { reason, diagnostics }],
+                    State::ModulesChanged { modules, warnings, .. } => vec![
+                        ClientMessage::ModulesChanged { modules },
+                        // also clears the warnings of the previous build
+                        ClientMessage::Warnings { diagnostics: warnings },
+                    ],
                 };
 
-                tracing::trace!("Message to send: {msg:?}");
+                tracing::trace!("Messages to send: {msgs:?}");
 
-                if let Some(msg) = msg {
+                for msg in msgs {
                     if let Ok(text) = serde_json::to_string(&msg) {
                         if let Err(err) = ws.send(Message::Text(text.into())).await {
                             tracing::info!("autoload websocket failed to send: {err}");
-                            break;
+                            break 'ws;
                         }
                     }
                 }
@@ -96,3 +147,13 @@ pub(crate) async fn handle_ws(mut ws: WebSocket, state: Arc<serve::State>) {
 
     tracing::debug!("exiting WS handler");
 }
+
+/// The message showing the warnings of a build, if there are any.
+fn warnings_message(warnings: Vec<Diagnostic>) -> Vec<ClientMessage> {
+    match warnings.is_empty() {
+        true => vec![],
+        false => vec![ClientMessage::Warnings {
+            diagnostics: warnings,
+        }],
+    }
+}